@@ -0,0 +1,156 @@
+use curvo::prelude::{FloatingPoint, NurbsCurve};
+use nalgebra::{allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, U1};
+
+/// Nodes and weights of the 5-point Gauss-Legendre quadrature on [-1, 1]
+const GAUSS_LEGENDRE: [(f64, f64); 5] = [
+    (0., 0.568_888_888_888_888_9),
+    (-0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+    (0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+    (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+];
+
+/// Arc length reparameterization table of a NURBS curve
+#[derive(Debug, Clone)]
+pub struct ArcLengthTable<T: FloatingPoint = f32> {
+    parameters: Vec<T>,
+    lengths: Vec<T>,
+}
+
+impl<T: FloatingPoint> ArcLengthTable<T> {
+    /// Builds the table by integrating the speed of the curve over uniformly divided segments
+    /// * `segments` - The number of segments the domain of the curve is divided into
+    pub fn new<D>(curve: &NurbsCurve<T, D>, segments: usize) -> Self
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let segments = segments.max(1);
+        let (start, end) = curve.knots_domain();
+        let parameters: Vec<T> = (0..=segments)
+            .map(|i| {
+                start + (end - start) * T::from_usize(i).unwrap() / T::from_usize(segments).unwrap()
+            })
+            .collect();
+        let lengths = std::iter::once(T::zero())
+            .chain(parameters.windows(2).scan(T::zero(), |acc, w| {
+                *acc += integrate_speed(curve, w[0], w[1]);
+                Some(*acc)
+            }))
+            .collect();
+        Self {
+            parameters,
+            lengths,
+        }
+    }
+
+    /// Returns the sampled parameters
+    pub fn parameters(&self) -> &[T] {
+        &self.parameters
+    }
+
+    /// Returns the arc lengths from the start of the curve at the sampled parameters
+    pub fn lengths(&self) -> &[T] {
+        &self.lengths
+    }
+
+    /// Returns the total length of the curve
+    pub fn length(&self) -> T {
+        self.lengths[self.lengths.len() - 1]
+    }
+
+    /// Returns the arc length from the start of the curve to the parameter
+    pub fn length_at_parameter<D>(&self, curve: &NurbsCurve<T, D>, parameter: T) -> T
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let i = segment_by(&self.parameters, parameter);
+        self.lengths[i] + integrate_speed(curve, self.parameters[i], parameter)
+    }
+
+    /// Returns the parameter at the arc length from the start of the curve
+    ///
+    /// The table is linearly interpolated and then refined by newton iteration on the exact arc length.
+    pub fn parameter_at_length<D>(&self, curve: &NurbsCurve<T, D>, length: T) -> T
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let length = nalgebra::clamp(length, T::zero(), self.length());
+        let i = segment_by(&self.lengths, length);
+        let (t0, t1) = (self.parameters[i], self.parameters[i + 1]);
+        let (l0, l1) = (self.lengths[i], self.lengths[i + 1]);
+        let span = l1 - l0;
+        if span <= T::zero() {
+            return t0;
+        }
+
+        let mut t = t0 + (t1 - t0) * (length - l0) / span;
+        for _ in 0..4 {
+            let speed = curve.tangent_at(t).norm();
+            if speed <= T::default_epsilon() {
+                break;
+            }
+            let error = l0 + integrate_speed(curve, t0, t) - length;
+            t = nalgebra::clamp(t - error / speed, t0, t1);
+        }
+        t
+    }
+}
+
+/// Finds the index of the segment containing the value in the ascending values
+fn segment_by<T: PartialOrd>(values: &[T], value: T) -> usize {
+    let i = values.partition_point(|v| *v <= value);
+    i.saturating_sub(1).min(values.len() - 2)
+}
+
+/// Integrates the speed of the curve over the parameter range by Gauss-Legendre quadrature
+fn integrate_speed<T: FloatingPoint, D>(curve: &NurbsCurve<T, D>, a: T, b: T) -> T
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let half = (b - a) * T::from_f64(0.5).unwrap();
+    let mid = (a + b) * T::from_f64(0.5).unwrap();
+    GAUSS_LEGENDRE.iter().fold(T::zero(), |acc, (x, w)| {
+        let t = mid + half * T::from_f64(*x).unwrap();
+        acc + curve.tangent_at(t).norm() * T::from_f64(*w).unwrap()
+    }) * half
+}
+
+#[cfg(test)]
+mod tests {
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::Point4;
+
+    use super::*;
+
+    #[test]
+    fn reparameterizes_by_arc_length() {
+        // a straight line whose control points are clustered at the start, so the speed varies along it
+        let curve: NurbsCurve3D<f64> = NurbsCurve3D::try_new(
+            2,
+            vec![
+                Point4::new(0., 0., 0., 1.),
+                Point4::new(0.3, 0., 0., 1.),
+                Point4::new(3., 0., 0., 1.),
+            ],
+            vec![0., 0., 0., 1., 1., 1.],
+        )
+        .unwrap();
+        let table = ArcLengthTable::new(&curve, 8);
+        assert!((table.length() - 3.).abs() < 1e-9);
+
+        for length in [0., 0.25, 1., 1.7, 2.9, 3.] {
+            let t = table.parameter_at_length(&curve, length);
+            assert!((curve.point_at(t).x - length).abs() < 1e-6);
+            assert!((table.length_at_parameter(&curve, t) - length).abs() < 1e-6);
+        }
+        assert_eq!(table.parameter_at_length(&curve, 5.), 1.);
+    }
+}
@@ -0,0 +1,492 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    ecs::{
+        entity::Entity,
+        query::{Added, Changed, Or, With},
+        removal_detection::RemovedComponents,
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, ResMut, Resource},
+    },
+    math::{Affine3A, Mat3, Quat, Ray3d, Vec3, Vec3A},
+    render::primitives::{Aabb, Frustum},
+    transform::{components::GlobalTransform, TransformSystem},
+    utils::HashMap,
+};
+use curvo::prelude::{FloatingPoint, NurbsCurve3D};
+use nalgebra::{Const, Matrix3, Point4, SymmetricEigen, Vector3};
+
+use crate::prelude::{ControlNet, NurbsCurveGeometry, NurbsSurfaceGeometry};
+
+/// Oriented bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientedBoundingBox {
+    /// The center of the box
+    pub center: Vec3,
+    /// The half lengths of the box along its local axes
+    pub half_extents: Vec3,
+    /// The rotation from the local axes of the box
+    pub rotation: Quat,
+}
+
+impl OrientedBoundingBox {
+    /// Returns the corners of the box
+    pub fn corners(&self) -> [Vec3; 8] {
+        let e = self.half_extents;
+        [-1f32, 1.]
+            .into_iter()
+            .flat_map(|x| [-1f32, 1.].into_iter().map(move |y| (x, y)))
+            .flat_map(|(x, y)| [-1f32, 1.].into_iter().map(move |z| Vec3::new(x, y, z)))
+            .map(|s| self.center + self.rotation * (s * e))
+            .collect::<Vec<_>>()
+            .try_into()
+            .unwrap()
+    }
+}
+
+/// Bounding volumes computed from the control points by the convex hull property of NURBS
+///
+/// The bounds are conservative for geometries with positive weights and cheap to compute compared to tessellation.
+pub trait ControlHullBounds {
+    /// Returns the axis-aligned bounding box of the dehomogenized control points
+    fn control_hull_aabb(&self) -> Aabb;
+
+    /// Returns the oriented bounding box of the dehomogenized control points along their principal axes
+    fn control_hull_obb(&self) -> OrientedBoundingBox;
+}
+
+impl<T: FloatingPoint> ControlHullBounds for NurbsCurve3D<T> {
+    fn control_hull_aabb(&self) -> Aabb {
+        aabb(&dehomogenized(self.control_points().iter()))
+    }
+
+    fn control_hull_obb(&self) -> OrientedBoundingBox {
+        obb(&dehomogenized(self.control_points().iter()))
+    }
+}
+
+impl<T: FloatingPoint> ControlHullBounds for ControlNet<T, Const<4>> {
+    fn control_hull_aabb(&self) -> Aabb {
+        aabb(&dehomogenized(self.control_points().iter().flatten()))
+    }
+
+    fn control_hull_obb(&self) -> OrientedBoundingBox {
+        obb(&dehomogenized(self.control_points().iter().flatten()))
+    }
+}
+
+fn dehomogenized<'a, T: FloatingPoint>(points: impl Iterator<Item = &'a Point4<T>>) -> Vec<Vec3> {
+    points
+        .map(|p| {
+            let w = p.w;
+            Vec3::new(
+                (p.x / w).to_f32().unwrap(),
+                (p.y / w).to_f32().unwrap(),
+                (p.z / w).to_f32().unwrap(),
+            )
+        })
+        .collect()
+}
+
+fn aabb(points: &[Vec3]) -> Aabb {
+    let (min, max) = points.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), p| (min.min(*p), max.max(*p)),
+    );
+    if points.is_empty() {
+        Aabb::from_min_max(Vec3::ZERO, Vec3::ZERO)
+    } else {
+        Aabb::from_min_max(min, max)
+    }
+}
+
+fn obb(points: &[Vec3]) -> OrientedBoundingBox {
+    if points.is_empty() {
+        return OrientedBoundingBox {
+            center: Vec3::ZERO,
+            half_extents: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+        };
+    }
+
+    let mean = points.iter().sum::<Vec3>() / points.len() as f32;
+    let covariance = points.iter().fold(Matrix3::<f32>::zeros(), |acc, p| {
+        let d = Vector3::new(p.x - mean.x, p.y - mean.y, p.z - mean.z);
+        acc + d * d.transpose()
+    });
+    let eigen = SymmetricEigen::new(covariance);
+    let axis = |i: usize| {
+        let c = eigen.eigenvectors.column(i);
+        Vec3::new(c[0], c[1], c[2]).normalize_or_zero()
+    };
+    let (x, y) = (axis(0), axis(1));
+    let z = x.cross(y).normalize_or_zero();
+    let rotation = if z == Vec3::ZERO {
+        Quat::IDENTITY
+    } else {
+        Quat::from_mat3(&Mat3::from_cols(x, z.cross(x), z))
+    };
+
+    let inverse = rotation.inverse();
+    let (min, max) = points.iter().fold(
+        (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+        |(min, max), p| {
+            let local = inverse * *p;
+            (min.min(local), max.max(local))
+        },
+    );
+    OrientedBoundingBox {
+        center: rotation * ((min + max) * 0.5),
+        half_extents: (max - min) * 0.5,
+        rotation,
+    }
+}
+
+/// Node of the bounding volume hierarchy
+#[derive(Debug, Clone)]
+enum BvhNode {
+    Leaf {
+        min: Vec3,
+        max: Vec3,
+        entity: Entity,
+    },
+    Branch {
+        min: Vec3,
+        max: Vec3,
+        children: Box<[BvhNode; 2]>,
+    },
+}
+
+impl BvhNode {
+    fn bounds(&self) -> (Vec3, Vec3) {
+        match self {
+            BvhNode::Leaf { min, max, .. } | BvhNode::Branch { min, max, .. } => (*min, *max),
+        }
+    }
+
+    fn build(mut items: Vec<(Entity, Vec3, Vec3)>) -> Option<Self> {
+        match items.len() {
+            0 => None,
+            1 => {
+                let (entity, min, max) = items[0];
+                Some(BvhNode::Leaf { min, max, entity })
+            }
+            _ => {
+                let (min, max) = items.iter().fold(
+                    (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                    |(min, max), (_, a, b)| (min.min(*a), max.max(*b)),
+                );
+
+                // split at the median of the centers along the longest axis
+                let size = max - min;
+                let axis = if size.x >= size.y && size.x >= size.z {
+                    0
+                } else if size.y >= size.z {
+                    1
+                } else {
+                    2
+                };
+                items.sort_by(|a, b| (a.1 + a.2)[axis].total_cmp(&(b.1 + b.2)[axis]));
+                let right = items.split_off(items.len() / 2);
+                let (l, r) = (Self::build(items)?, Self::build(right)?);
+                Some(BvhNode::Branch {
+                    min,
+                    max,
+                    children: Box::new([l, r]),
+                })
+            }
+        }
+    }
+
+    /// Refits the bounds to the new bounds of the leaves, keeping the structure
+    ///
+    /// Returns false if the bounds of a leaf are missing.
+    fn refit(&mut self, bounds: &HashMap<Entity, (Vec3, Vec3)>) -> bool {
+        match self {
+            BvhNode::Leaf { min, max, entity } => match bounds.get(entity) {
+                Some(b) => {
+                    (*min, *max) = *b;
+                    true
+                }
+                None => false,
+            },
+            BvhNode::Branch { min, max, children } => {
+                if !children.iter_mut().all(|c| c.refit(bounds)) {
+                    return false;
+                }
+                let ((a0, a1), (b0, b1)) = (children[0].bounds(), children[1].bounds());
+                (*min, *max) = (a0.min(b0), a1.max(b1));
+                true
+            }
+        }
+    }
+
+    fn leaf_count(&self) -> usize {
+        match self {
+            BvhNode::Leaf { .. } => 1,
+            BvhNode::Branch { children, .. } => children.iter().map(|c| c.leaf_count()).sum(),
+        }
+    }
+
+    fn collect(&self, test: &impl Fn(Vec3, Vec3) -> bool, out: &mut Vec<Entity>) {
+        let (min, max) = self.bounds();
+        if !test(min, max) {
+            return;
+        }
+        match self {
+            BvhNode::Leaf { entity, .. } => out.push(*entity),
+            BvhNode::Branch { children, .. } => {
+                children.iter().for_each(|c| c.collect(test, out));
+            }
+        }
+    }
+}
+
+/// Bounding volume hierarchy over the world space control hull bounds of NURBS geometry components
+///
+/// When it is present, `pick_curves` and `raycast_surfaces` only test the entities whose bounds the cursor ray reaches.
+#[derive(Resource, Debug, Clone, Default)]
+pub struct NurbsBvh {
+    root: Option<BvhNode>,
+}
+
+impl NurbsBvh {
+    /// Builds the hierarchy from entities and their world space bounds
+    pub fn new(items: Vec<(Entity, Vec3, Vec3)>) -> Self {
+        Self {
+            root: BvhNode::build(items),
+        }
+    }
+
+    /// Returns the entities whose bounds pass the test on the minimum and maximum corners
+    pub fn query(&self, test: impl Fn(Vec3, Vec3) -> bool) -> Vec<Entity> {
+        let mut out = vec![];
+        if let Some(root) = &self.root {
+            root.collect(&test, &mut out);
+        }
+        out
+    }
+
+    /// Refits the bounds of the entities in place, keeping the structure of the hierarchy
+    ///
+    /// Returns false and leaves the hierarchy unusable for queries until it is rebuilt
+    /// if the entities differ from the ones it was built from.
+    pub fn refit(&mut self, items: Vec<(Entity, Vec3, Vec3)>) -> bool {
+        let count = items.len();
+        let bounds: HashMap<_, _> = items.into_iter().map(|(e, a, b)| (e, (a, b))).collect();
+        match &mut self.root {
+            Some(root) => root.leaf_count() == count && root.refit(&bounds),
+            None => count == 0,
+        }
+    }
+
+    /// Returns the entities whose bounds intersect the ray
+    pub fn ray_candidates(&self, ray: Ray3d) -> Vec<Entity> {
+        self.query(|min, max| intersect_ray_aabb(ray, min, max))
+    }
+
+    /// Returns the entities whose bounds, inflated by the slack at their distance from the ray origin, intersect the ray
+    ///
+    /// Used to find geometry within a screen space radius of the cursor, whose world space size grows with the distance
+    /// for perspective cameras.
+    pub fn ray_candidates_with_slack(&self, ray: Ray3d, slack: impl Fn(f32) -> f32) -> Vec<Entity> {
+        self.query(|min, max| {
+            let distance = ((min + max) * 0.5).distance(ray.origin) + (max - min).length() * 0.5;
+            let slack = Vec3::splat(slack(distance));
+            intersect_ray_aabb(ray, min - slack, max + slack)
+        })
+    }
+
+    /// Returns the entities whose bounds intersect the frustum
+    pub fn frustum_candidates(&self, frustum: &Frustum) -> Vec<Entity> {
+        self.query(|min, max| {
+            frustum.intersects_obb(
+                &Aabb::from_min_max(min, max),
+                &Affine3A::IDENTITY,
+                true,
+                true,
+            )
+        })
+    }
+
+    /// Returns the entities whose bounds overlap the box
+    pub fn aabb_candidates(&self, min: Vec3, max: Vec3) -> Vec<Entity> {
+        self.query(|a, b| a.cmple(max).all() && b.cmpge(min).all())
+    }
+}
+
+/// Slab test of a ray against an axis-aligned box
+///
+/// Axes the ray is parallel to only check that the origin lies between the slabs,
+/// since their distances would be 0 * inf = NaN for an origin on a slab.
+fn intersect_ray_aabb(ray: Ray3d, min: Vec3, max: Vec3) -> bool {
+    let (mut near, mut far) = (0f32, f32::INFINITY);
+    for axis in 0..3 {
+        let (origin, direction) = (ray.origin[axis], ray.direction[axis]);
+        if direction == 0. {
+            if origin < min[axis] || origin > max[axis] {
+                return false;
+            }
+            continue;
+        }
+        let t0 = (min[axis] - origin) / direction;
+        let t1 = (max[axis] - origin) / direction;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+    far >= near
+}
+
+/// Returns the world space axis-aligned bounds of the local bounding box
+fn world_bounds(aabb: &Aabb, transform: &GlobalTransform) -> (Vec3, Vec3) {
+    let affine = transform.affine();
+    let center = affine.transform_point3a(aabb.center);
+    let matrix = affine.matrix3;
+    let extents = Vec3A::new(
+        matrix.row(0).abs().dot(aabb.half_extents),
+        matrix.row(1).abs().dot(aabb.half_extents),
+        matrix.row(2).abs().dot(aabb.half_extents),
+    );
+    ((center - extents).into(), (center + extents).into())
+}
+
+/// System set in which the bounds of NURBS geometry components are updated
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NurbsBoundsSet;
+
+/// Plugin to attach control hull bounds to NURBS geometry components and maintain a hierarchy over them
+pub struct NurbsBoundsPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for NurbsBoundsPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for NurbsBoundsPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NurbsBvh>().add_systems(
+            PostUpdate,
+            (
+                (update_curve_bounds::<T>, update_surface_bounds::<T>),
+                update_nurbs_bvh::<T>,
+            )
+                .chain()
+                .in_set(NurbsBoundsSet)
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Attaches the control hull Aabb to changed curve geometries
+pub fn update_curve_bounds<T: FloatingPoint>(
+    mut commands: Commands,
+    curves: Query<(Entity, &NurbsCurveGeometry<T>), Changed<NurbsCurveGeometry<T>>>,
+) {
+    curves.iter().for_each(|(e, curve)| {
+        commands.entity(e).insert(curve.control_hull_aabb());
+    });
+}
+
+/// Attaches the control hull Aabb to changed surface geometries
+pub fn update_surface_bounds<T: FloatingPoint>(
+    mut commands: Commands,
+    surfaces: Query<(Entity, &NurbsSurfaceGeometry<T>), Changed<NurbsSurfaceGeometry<T>>>,
+) {
+    surfaces.iter().for_each(|(e, surface)| {
+        commands.entity(e).insert(surface.control_hull_aabb());
+    });
+}
+
+/// Refits the hierarchy when bounds or transforms of NURBS geometry components change,
+/// rebuilding it when geometries are added or removed
+#[allow(clippy::type_complexity)]
+pub fn update_nurbs_bvh<T: FloatingPoint>(
+    mut bvh: ResMut<NurbsBvh>,
+    added: Query<
+        (),
+        (
+            Or<(With<NurbsCurveGeometry<T>>, With<NurbsSurfaceGeometry<T>>)>,
+            Added<Aabb>,
+        ),
+    >,
+    changed: Query<
+        (),
+        (
+            Or<(With<NurbsCurveGeometry<T>>, With<NurbsSurfaceGeometry<T>>)>,
+            Or<(Changed<Aabb>, Changed<GlobalTransform>)>,
+        ),
+    >,
+    bounded: Query<
+        (Entity, &Aabb, &GlobalTransform),
+        Or<(With<NurbsCurveGeometry<T>>, With<NurbsSurfaceGeometry<T>>)>,
+    >,
+    mut removed_curves: RemovedComponents<NurbsCurveGeometry<T>>,
+    mut removed_surfaces: RemovedComponents<NurbsSurfaceGeometry<T>>,
+) {
+    let removed = removed_curves.read().count() + removed_surfaces.read().count();
+    if changed.is_empty() && removed == 0 {
+        return;
+    }
+    let items: Vec<_> = bounded
+        .iter()
+        .map(|(e, aabb, transform)| {
+            let (min, max) = world_bounds(aabb, transform);
+            (e, min, max)
+        })
+        .collect();
+    if removed > 0 || !added.is_empty() || !bvh.refit(items.clone()) {
+        *bvh = NurbsBvh::new(items);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::primitives::Direction3d;
+
+    use super::*;
+
+    #[test]
+    fn intersects_rays_parallel_to_a_face() {
+        let (min, max) = (Vec3::ZERO, Vec3::ONE);
+        let along = |origin: Vec3| Ray3d {
+            origin,
+            direction: Direction3d::X,
+        };
+        assert!(intersect_ray_aabb(along(Vec3::new(-1., 0., 0.5)), min, max));
+        assert!(intersect_ray_aabb(
+            along(Vec3::new(-1., 0.5, 0.5)),
+            min,
+            max
+        ));
+        assert!(!intersect_ray_aabb(
+            along(Vec3::new(-1., 1.5, 0.5)),
+            min,
+            max
+        ));
+        assert!(!intersect_ray_aabb(
+            along(Vec3::new(2., 0.5, 0.5)),
+            min,
+            max
+        ));
+    }
+
+    #[test]
+    fn refits_moved_bounds_and_rejects_other_entities() {
+        let (a, b) = (Entity::from_raw(0), Entity::from_raw(1));
+        let mut bvh = NurbsBvh::new(vec![
+            (a, Vec3::ZERO, Vec3::ONE),
+            (b, Vec3::ONE, Vec3::ONE * 2.),
+        ]);
+        assert!(bvh.refit(vec![
+            (a, Vec3::ONE * 4., Vec3::ONE * 5.),
+            (b, Vec3::ONE, Vec3::ONE * 2.)
+        ]));
+        assert_eq!(
+            bvh.aabb_candidates(Vec3::ONE * 4.5, Vec3::ONE * 4.6),
+            vec![a]
+        );
+        assert!(!bvh.refit(vec![(a, Vec3::ZERO, Vec3::ONE)]));
+    }
+}
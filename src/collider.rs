@@ -0,0 +1,334 @@
+use std::marker::PhantomData;
+
+#[cfg(any(feature = "rapier", feature = "xpbd"))]
+use bevy::ecs::schedule::IntoSystemConfigs;
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, Or},
+        schedule::{IntoSystemSetConfigs, SystemSet},
+        system::{Commands, Query},
+    },
+    log::warn,
+    math::Vec3,
+};
+use curvo::prelude::{FloatingPoint, NurbsCurve3D, SurfaceTessellation};
+use nalgebra::Const;
+
+use crate::{
+    geometry::to_vec3,
+    prelude::{
+        NurbsCurveGeometry, NurbsMeshSet, NurbsMorphSet, NurbsSurfaceGeometry, NurbsTessellation,
+    },
+};
+
+/// Kind of collider generated from a NURBS geometry component
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NurbsColliderKind {
+    /// Triangle mesh of the surface tessellation
+    TriMesh,
+    /// Convex decomposition of the surface tessellation for closed bodies
+    ConvexDecomposition,
+    /// Polyline of the curve tessellation
+    Polyline,
+    /// Chain of capsules along the curve tessellation
+    CapsuleChain {
+        /// The radius of the capsules
+        radius: f32,
+    },
+}
+
+impl NurbsColliderKind {
+    /// Returns true if the kind is generated from curves
+    pub fn is_curve_kind(&self) -> bool {
+        matches!(self, Self::Polyline | Self::CapsuleChain { .. })
+    }
+
+    /// Returns true if the kind is generated from surfaces
+    pub fn is_surface_kind(&self) -> bool {
+        !self.is_curve_kind()
+    }
+}
+
+/// Component to generate a physics collider from the NURBS geometry component of the entity
+///
+/// The collider is regenerated whenever the geometry or this component changes.
+/// If the kind does not match the geometry, a warning is logged and the previous collider is removed.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct NurbsCollider {
+    /// The kind of the collider
+    pub kind: NurbsColliderKind,
+    /// The tolerance of the curve tessellation. If None, curvo's default is used
+    pub curve_tolerance: Option<f32>,
+}
+
+impl NurbsCollider {
+    pub fn new(kind: NurbsColliderKind) -> Self {
+        Self {
+            kind,
+            curve_tolerance: None,
+        }
+    }
+}
+
+/// Engine agnostic shape extracted from a NURBS geometry
+#[derive(Debug, Clone, PartialEq)]
+pub enum NurbsColliderShape {
+    /// Triangle mesh with the vertices and triangles
+    TriMesh {
+        vertices: Vec<Vec3>,
+        indices: Vec<[u32; 3]>,
+    },
+    /// Convex decomposition of the triangle mesh with the vertices and triangles
+    ConvexDecomposition {
+        vertices: Vec<Vec3>,
+        indices: Vec<[u32; 3]>,
+    },
+    /// Polyline through the vertices
+    Polyline(Vec<Vec3>),
+    /// Capsules between the consecutive vertices
+    CapsuleChain { vertices: Vec<Vec3>, radius: f32 },
+}
+
+/// Returns the collider shape of the curve, or None if the kind is not a curve kind
+pub fn curve_collider_shape<T: FloatingPoint>(
+    curve: &NurbsCurve3D<T>,
+    collider: &NurbsCollider,
+) -> Option<NurbsColliderShape> {
+    if !collider.kind.is_curve_kind() {
+        return None;
+    }
+    let vertices = curve
+        .tessellate(collider.curve_tolerance.and_then(T::from_f32))
+        .iter()
+        .map(|p| to_vec3(p.coords.as_slice()))
+        .collect();
+    match collider.kind {
+        NurbsColliderKind::CapsuleChain { radius } => {
+            Some(NurbsColliderShape::CapsuleChain { vertices, radius })
+        }
+        _ => Some(NurbsColliderShape::Polyline(vertices)),
+    }
+}
+
+/// Returns the collider shape of the surface tessellation, or None if the kind is not a surface kind
+pub fn surface_collider_shape<T: FloatingPoint>(
+    tessellation: &SurfaceTessellation<T, Const<4>>,
+    kind: NurbsColliderKind,
+) -> Option<NurbsColliderShape> {
+    if !kind.is_surface_kind() {
+        return None;
+    }
+    let vertices = tessellation
+        .points()
+        .iter()
+        .map(|p| to_vec3(p.coords.as_slice()))
+        .collect();
+    let indices = tessellation
+        .faces()
+        .iter()
+        .map(|f| [f[0] as u32, f[1] as u32, f[2] as u32])
+        .collect();
+    match kind {
+        NurbsColliderKind::ConvexDecomposition => {
+            Some(NurbsColliderShape::ConvexDecomposition { vertices, indices })
+        }
+        _ => Some(NurbsColliderShape::TriMesh { vertices, indices }),
+    }
+}
+
+/// Physics engine which builds its collider component from the extracted shapes
+pub trait NurbsColliderBackend: Send + Sync + 'static {
+    /// The collider component of the engine
+    type Collider: Component;
+
+    /// Builds the collider from the shape
+    fn build(shape: NurbsColliderShape) -> Self::Collider;
+}
+
+fn apply_collider<B: NurbsColliderBackend>(
+    commands: &mut Commands,
+    entity: Entity,
+    kind: NurbsColliderKind,
+    shape: Option<NurbsColliderShape>,
+) {
+    match shape {
+        Some(shape) => {
+            commands.entity(entity).insert(B::build(shape));
+        }
+        None => {
+            warn!(
+                "NurbsCollider kind {:?} does not match the geometry of {:?}",
+                kind, entity
+            );
+            commands.entity(entity).remove::<B::Collider>();
+        }
+    }
+}
+
+/// Regenerates the colliders of changed curve geometries with the backend
+#[allow(clippy::type_complexity)]
+pub fn update_curve_colliders<T: FloatingPoint, B: NurbsColliderBackend>(
+    mut commands: Commands,
+    curves: Query<
+        (Entity, &NurbsCurveGeometry<T>, &NurbsCollider),
+        Or<(Changed<NurbsCurveGeometry<T>>, Changed<NurbsCollider>)>,
+    >,
+) {
+    curves.iter().for_each(|(e, curve, collider)| {
+        let shape = curve_collider_shape(curve, collider);
+        apply_collider::<B>(&mut commands, e, collider.kind, shape);
+    });
+}
+
+/// Regenerates the colliders of changed surface geometries with the backend
+#[allow(clippy::type_complexity)]
+pub fn update_surface_colliders<T: FloatingPoint, B: NurbsColliderBackend>(
+    mut commands: Commands,
+    surfaces: Query<
+        (
+            Entity,
+            &NurbsSurfaceGeometry<T>,
+            &NurbsCollider,
+            Option<&NurbsTessellation<T>>,
+        ),
+        Or<(Changed<NurbsSurfaceGeometry<T>>, Changed<NurbsCollider>)>,
+    >,
+) {
+    surfaces
+        .iter()
+        .for_each(|(e, surface, collider, tessellation)| {
+            let shape = collider
+                .kind
+                .is_surface_kind()
+                .then(|| surface.tessellate(tessellation.and_then(|t| t.surface_options())));
+            let shape = shape.and_then(|t| surface_collider_shape(&t, collider.kind));
+            apply_collider::<B>(&mut commands, e, collider.kind, shape);
+        });
+}
+
+/// System set in which colliders are regenerated, after the meshes and morphs of the geometry are updated
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NurbsColliderSet;
+
+/// Plugin to regenerate colliders of NURBS geometry components for the enabled physics engines
+///
+/// Enable the `rapier` feature for bevy_rapier3d and the `xpbd` feature for bevy_xpbd_3d.
+pub struct NurbsColliderPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for NurbsColliderPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for NurbsColliderPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            PostUpdate,
+            NurbsColliderSet.after(NurbsMeshSet).after(NurbsMorphSet),
+        );
+
+        #[cfg(feature = "rapier")]
+        app.add_systems(
+            PostUpdate,
+            (
+                update_curve_colliders::<T, rapier::Rapier>,
+                update_surface_colliders::<T, rapier::Rapier>,
+            )
+                .in_set(NurbsColliderSet),
+        );
+
+        #[cfg(feature = "xpbd")]
+        app.add_systems(
+            PostUpdate,
+            (
+                update_curve_colliders::<T, xpbd::Xpbd>,
+                update_surface_colliders::<T, xpbd::Xpbd>,
+            )
+                .in_set(NurbsColliderSet),
+        );
+    }
+}
+
+/// Collider generation for bevy_rapier3d
+#[cfg(feature = "rapier")]
+pub mod rapier {
+    use bevy::math::{Quat, Vec3};
+    use bevy_rapier3d::prelude::Collider;
+
+    use super::{NurbsColliderBackend, NurbsColliderShape};
+
+    /// Backend building bevy_rapier3d colliders
+    pub struct Rapier;
+
+    impl NurbsColliderBackend for Rapier {
+        type Collider = Collider;
+
+        fn build(shape: NurbsColliderShape) -> Collider {
+            match shape {
+                NurbsColliderShape::TriMesh { vertices, indices } => {
+                    Collider::trimesh(vertices, indices)
+                }
+                NurbsColliderShape::ConvexDecomposition { vertices, indices } => {
+                    Collider::convex_decomposition(&vertices, &indices)
+                }
+                NurbsColliderShape::Polyline(vertices) => Collider::polyline(vertices, None),
+                NurbsColliderShape::CapsuleChain { vertices, radius } => Collider::compound(
+                    vertices
+                        .windows(2)
+                        .map(|w| {
+                            (
+                                Vec3::ZERO,
+                                Quat::IDENTITY,
+                                Collider::capsule(w[0], w[1], radius),
+                            )
+                        })
+                        .collect(),
+                ),
+            }
+        }
+    }
+}
+
+/// Collider generation for bevy_xpbd_3d
+#[cfg(feature = "xpbd")]
+pub mod xpbd {
+    use bevy::math::{Quat, Vec3};
+    use bevy_xpbd_3d::prelude::Collider;
+
+    use super::{NurbsColliderBackend, NurbsColliderShape};
+
+    /// Backend building bevy_xpbd_3d colliders
+    pub struct Xpbd;
+
+    impl NurbsColliderBackend for Xpbd {
+        type Collider = Collider;
+
+        fn build(shape: NurbsColliderShape) -> Collider {
+            match shape {
+                NurbsColliderShape::TriMesh { vertices, indices } => {
+                    Collider::trimesh(vertices, indices)
+                }
+                NurbsColliderShape::ConvexDecomposition { vertices, indices } => {
+                    Collider::convex_decomposition(vertices, indices)
+                }
+                NurbsColliderShape::Polyline(vertices) => Collider::polyline(vertices, None),
+                NurbsColliderShape::CapsuleChain { vertices, radius } => Collider::compound(
+                    vertices
+                        .windows(2)
+                        .map(|w| {
+                            (
+                                Vec3::ZERO,
+                                Quat::IDENTITY,
+                                Collider::capsule_endpoints(w[0], w[1], radius),
+                            )
+                        })
+                        .collect(),
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,290 @@
+use curvo::prelude::{FloatingPoint, NurbsCurve};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, OPoint, U1,
+};
+
+use crate::prelude::ControlNet;
+
+/// Degree, knot vector and homogeneous control points of a B-spline in one direction
+#[derive(Debug, Clone)]
+struct BSpline<T: FloatingPoint, D: DimName>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    degree: usize,
+    knots: Vec<T>,
+    points: Vec<OPoint<T, D>>,
+}
+
+impl<T: FloatingPoint, D: DimName> BSpline<T, D>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Maps the knot vector onto [0, 1]
+    fn normalize_knots(&mut self) {
+        let (a, b) = (self.knots[0], self.knots[self.knots.len() - 1]);
+        let span = b - a;
+        if span > T::zero() {
+            self.knots.iter_mut().for_each(|k| *k = (*k - a) / span);
+        }
+    }
+
+    /// Inserts the knot once by Boehm's algorithm
+    fn insert_knot(&mut self, u: T) {
+        let p = self.degree;
+        let n = self.points.len() - 1;
+        let k = self
+            .knots
+            .partition_point(|v| *v <= u)
+            .saturating_sub(1)
+            .clamp(p, n);
+
+        let mut points = Vec::with_capacity(self.points.len() + 1);
+        points.extend(self.points[..=k - p].iter().cloned());
+        (k - p + 1..=k).for_each(|i| {
+            let denom = self.knots[i + p] - self.knots[i];
+            let a = if denom > T::zero() {
+                (u - self.knots[i]) / denom
+            } else {
+                T::zero()
+            };
+            let coords = &self.points[i].coords * a + &self.points[i - 1].coords * (T::one() - a);
+            points.push(OPoint::from(coords));
+        });
+        points.extend(self.points[k..].iter().cloned());
+
+        self.knots.insert(k + 1, u);
+        self.points = points;
+    }
+
+    /// Inserts the knots by repeated knot insertion
+    fn refine_knots(&mut self, knots: &[T]) {
+        knots.iter().for_each(|u| self.insert_knot(*u));
+    }
+
+    /// Elevates the degree by one through the Bézier decomposition
+    ///
+    /// Interior knots are left with multiplicity equal to the new degree as knot removal is not performed.
+    fn elevate_degree(&mut self) {
+        let p = self.degree;
+        let interior = distinct_knots(&self.knots[p + 1..self.knots.len() - p - 1]);
+        interior.iter().for_each(|(u, m)| {
+            (*m..p).for_each(|_| self.insert_knot(*u));
+        });
+
+        let q = p + 1;
+        let segments = (self.points.len() - 1) / p.max(1);
+        let mut points = Vec::with_capacity(segments * q + 1);
+        (0..segments).for_each(|s| {
+            let bezier = &self.points[s * p..=s * p + p];
+            if s == 0 {
+                points.push(bezier[0].clone());
+            }
+            (1..q).for_each(|i| {
+                let a = T::from_usize(i).unwrap() / T::from_usize(q).unwrap();
+                let coords = &bezier[i - 1].coords * a + &bezier[i].coords * (T::one() - a);
+                points.push(OPoint::from(coords));
+            });
+            points.push(bezier[p].clone());
+        });
+
+        let (a, b) = (self.knots[0], self.knots[self.knots.len() - 1]);
+        let mut knots = vec![a; q + 1];
+        interior
+            .iter()
+            .for_each(|(u, _)| knots.extend(std::iter::repeat_n(*u, q)));
+        knots.extend(std::iter::repeat_n(b, q + 1));
+
+        self.degree = q;
+        self.knots = knots;
+        self.points = points;
+    }
+
+    /// Returns the knots to insert to contain every knot of the target with its multiplicity
+    fn missing_knots(&self, target: &[(T, usize)]) -> Vec<T> {
+        let own = distinct_knots(&self.knots);
+        target
+            .iter()
+            .flat_map(|(u, m)| {
+                let current = own
+                    .iter()
+                    .find(|(v, _)| approx_eq(*u, *v))
+                    .map(|(_, m)| *m)
+                    .unwrap_or(0);
+                std::iter::repeat_n(*u, m.saturating_sub(current))
+            })
+            .collect()
+    }
+}
+
+/// Makes the B-splines share the degree and knot vector
+fn make_compatible<T: FloatingPoint, D: DimName>(splines: &mut [BSpline<T, D>])
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    let degree = splines.iter().map(|s| s.degree).max().unwrap_or(0);
+    splines.iter_mut().for_each(|s| {
+        s.normalize_knots();
+        while s.degree < degree {
+            s.elevate_degree();
+        }
+    });
+
+    let mut union: Vec<(T, usize)> = vec![];
+    splines.iter().for_each(|s| {
+        distinct_knots(&s.knots).into_iter().for_each(|(u, m)| {
+            match union.iter_mut().find(|(v, _)| approx_eq(u, *v)) {
+                Some((_, n)) => *n = (*n).max(m),
+                None => union.push((u, m)),
+            }
+        });
+    });
+
+    splines.iter_mut().for_each(|s| {
+        let missing = s.missing_knots(&union);
+        s.refine_knots(&missing);
+    });
+}
+
+/// Makes the curves share the degree and knot vector without changing their shapes
+///
+/// The knot vectors are normalized onto [0, 1], lower degree curves are degree elevated and
+/// then every curve is refined by the union of the knots.
+pub fn try_make_curves_compatible<T: FloatingPoint, D>(
+    curves: &[NurbsCurve<T, D>],
+) -> anyhow::Result<Vec<NurbsCurve<T, D>>>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let mut splines = curves
+        .iter()
+        .map(|c| BSpline {
+            degree: c.degree(),
+            knots: c.knots().as_slice().to_vec(),
+            points: c.control_points().clone(),
+        })
+        .collect::<Vec<_>>();
+    make_compatible(&mut splines);
+    splines
+        .into_iter()
+        .map(|s| NurbsCurve::try_new(s.degree, s.points, s.knots))
+        .collect()
+}
+
+/// Makes the surfaces share the degrees and knot vectors in both directions without changing their shapes
+pub fn try_make_surfaces_compatible<T: FloatingPoint, D>(
+    surfaces: &[ControlNet<T, D>],
+) -> anyhow::Result<Vec<ControlNet<T, D>>>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    anyhow::ensure!(!surfaces.is_empty(), "Surfaces must not be empty");
+
+    // control points are indexed by [u][v]
+    let mut grids = surfaces
+        .iter()
+        .map(|s| s.control_points().clone())
+        .collect::<Vec<_>>();
+
+    let columns = |grid: &Vec<Vec<OPoint<T, D>>>, j: usize| {
+        grid.iter().map(|row| row[j].clone()).collect::<Vec<_>>()
+    };
+
+    // u direction: every column of a surface shares the u knots
+    let mut u_splines = surfaces
+        .iter()
+        .zip(grids.iter())
+        .map(|(s, grid)| {
+            (0..grid[0].len())
+                .map(|j| BSpline {
+                    degree: s.u_degree(),
+                    knots: s.u_knots().to_vec(),
+                    points: columns(grid, j),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut representatives = u_splines.iter().map(|c| c[0].clone()).collect::<Vec<_>>();
+    make_compatible(&mut representatives);
+    let (u_degree, u_knots) = (representatives[0].degree, representatives[0].knots.clone());
+    u_splines.iter_mut().for_each(|columns| {
+        columns.iter_mut().for_each(|c| {
+            c.normalize_knots();
+            while c.degree < u_degree {
+                c.elevate_degree();
+            }
+            let missing = c.missing_knots(&distinct_knots(&u_knots));
+            c.refine_knots(&missing);
+        });
+    });
+    grids = u_splines
+        .iter()
+        .map(|columns| {
+            (0..columns[0].points.len())
+                .map(|i| columns.iter().map(|c| c.points[i].clone()).collect())
+                .collect()
+        })
+        .collect();
+
+    // v direction: every row of a surface shares the v knots
+    let mut v_splines = surfaces
+        .iter()
+        .zip(grids.iter())
+        .map(|(s, grid)| {
+            grid.iter()
+                .map(|row| BSpline {
+                    degree: s.v_degree(),
+                    knots: s.v_knots().to_vec(),
+                    points: row.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let mut representatives = v_splines.iter().map(|r| r[0].clone()).collect::<Vec<_>>();
+    make_compatible(&mut representatives);
+    let (v_degree, v_knots) = (representatives[0].degree, representatives[0].knots.clone());
+    v_splines.iter_mut().for_each(|rows| {
+        rows.iter_mut().for_each(|r| {
+            r.normalize_knots();
+            while r.degree < v_degree {
+                r.elevate_degree();
+            }
+            let missing = r.missing_knots(&distinct_knots(&v_knots));
+            r.refine_knots(&missing);
+        });
+    });
+
+    v_splines
+        .into_iter()
+        .map(|rows| {
+            ControlNet::try_new(
+                u_degree,
+                v_degree,
+                u_knots.clone(),
+                v_knots.clone(),
+                rows.into_iter().map(|r| r.points).collect(),
+            )
+        })
+        .collect()
+}
+
+/// Returns the distinct knots with their multiplicities
+fn distinct_knots<T: FloatingPoint>(knots: &[T]) -> Vec<(T, usize)> {
+    knots.iter().fold(vec![], |mut acc: Vec<(T, usize)>, k| {
+        match acc.last_mut() {
+            Some((v, m)) if approx_eq(*v, *k) => *m += 1,
+            _ => acc.push((*k, 1)),
+        }
+        acc
+    })
+}
+
+fn approx_eq<T: FloatingPoint>(a: T, b: T) -> bool {
+    let eps = T::from_f64(1e-6).unwrap();
+    let d = a - b;
+    d > -eps && d < eps
+}
@@ -0,0 +1,412 @@
+use std::{collections::HashMap, marker::PhantomData};
+
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{Assets, Handle},
+    ecs::{
+        change_detection::{DetectChanges, Ref},
+        component::Component,
+        entity::Entity,
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, ResMut},
+    },
+    render::{
+        mesh::{Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    transform::{components::GlobalTransform, TransformSystem},
+};
+use curvo::prelude::{
+    AdaptiveTessellationOptions, FloatingPoint, NurbsCurve3D, NurbsSurface3D, SurfaceTessellation,
+};
+use nalgebra::{Const, Point3, Vector2, Vector3};
+
+use crate::{
+    intersection::{to_matrix, transform_surface},
+    mesh_buffer::write_attribute,
+    prelude::NurbsSurfaceGeometry,
+};
+
+/// Family of parallel planes `normal · p = offset` used to slice surfaces
+#[derive(Debug, Clone, Copy)]
+pub struct ContourPlanes<T: FloatingPoint = f32> {
+    /// The normal of the planes
+    pub normal: Vector3<T>,
+    /// The distance between neighboring planes
+    pub spacing: T,
+    /// The range of the plane offsets along the normal
+    pub range: (T, T),
+}
+
+impl<T: FloatingPoint> ContourPlanes<T> {
+    pub fn new(normal: Vector3<T>, spacing: T, range: (T, T)) -> Self {
+        Self {
+            normal,
+            spacing,
+            range,
+        }
+    }
+
+    /// Returns the offsets of the planes along the unit normal
+    pub fn offsets(&self) -> Vec<T> {
+        if self.spacing <= T::zero() {
+            return vec![];
+        }
+        let (start, end) = self.range;
+        std::iter::successors(Some(start), |o| Some(*o + self.spacing))
+            .take_while(|o| *o <= end)
+            .collect()
+    }
+}
+
+/// Options for the refinement of contour points onto the exact surface
+#[derive(Debug, Clone, Copy)]
+pub struct ContourRefinement<T: FloatingPoint = f32> {
+    /// The distance to the plane below which the newton iteration stops
+    pub tolerance: T,
+    /// The maximum number of newton iterations
+    pub max_iterations: usize,
+}
+
+impl<T: FloatingPoint> Default for ContourRefinement<T> {
+    fn default() -> Self {
+        Self {
+            tolerance: T::from_f64(1e-6).unwrap(),
+            max_iterations: 8,
+        }
+    }
+}
+
+/// Polyline of a surface cut by a plane
+#[derive(Debug, Clone)]
+pub struct ContourPolyline<T: FloatingPoint = f32> {
+    /// The offset of the cutting plane
+    pub offset: T,
+    /// The points of the polyline
+    pub points: Vec<Point3<T>>,
+    /// The (u, v) parameters of the surface at the points
+    pub uvs: Vec<Vector2<T>>,
+    /// Whether the polyline forms a closed loop
+    pub closed: bool,
+}
+
+impl<T: FloatingPoint> ContourPolyline<T> {
+    /// Fits a NURBS curve interpolating the polyline
+    /// * `degree` - The degree of the curve, lowered if the polyline has too few points
+    pub fn try_fit(&self, degree: usize) -> anyhow::Result<NurbsCurve3D<T>> {
+        let mut points = self.points.clone();
+        if self.closed {
+            points.extend(points.first().copied());
+        }
+        if points.len() < 2 {
+            anyhow::bail!("contour has less than 2 points");
+        }
+        NurbsCurve3D::try_interpolate(&points, degree.min(points.len() - 1), None, None)
+    }
+}
+
+/// Slices the tessellation of a surface with the family of planes
+/// * `surface` - The surface and the options to refine the contour points onto it. If None, the points stay on the tessellation
+pub fn surface_contours<T: FloatingPoint>(
+    tessellation: &SurfaceTessellation<T, Const<4>>,
+    planes: &ContourPlanes<T>,
+    surface: Option<(&NurbsSurface3D<T>, &ContourRefinement<T>)>,
+) -> Vec<ContourPolyline<T>> {
+    let Some(normal) = planes.normal.try_normalize(T::default_epsilon()) else {
+        return vec![];
+    };
+    planes
+        .offsets()
+        .into_iter()
+        .flat_map(|offset| {
+            let mut contours = slice_tessellation(tessellation, &normal, offset);
+            if let Some((surface, refinement)) = surface {
+                contours
+                    .iter_mut()
+                    .for_each(|c| refine_contour(surface, &normal, c, refinement));
+            }
+            contours
+        })
+        .collect()
+}
+
+/// Slices the tessellation with the plane `normal · p = offset`, chaining the segments through shared triangle edges
+pub fn slice_tessellation<T: FloatingPoint>(
+    tessellation: &SurfaceTessellation<T, Const<4>>,
+    normal: &Vector3<T>,
+    offset: T,
+) -> Vec<ContourPolyline<T>> {
+    let points = tessellation.points();
+    let uvs = tessellation.uvs();
+    let distances: Vec<_> = points
+        .iter()
+        .map(|p| normal.dot(&p.coords) - offset)
+        .collect();
+
+    // vertices on the plane are treated as above it so that every crossing lies strictly inside an edge
+    let above = |i: usize| distances[i] >= T::zero();
+
+    let mut crossing_points = vec![];
+    let mut crossing_uvs = vec![];
+    let mut crossings: HashMap<(usize, usize), usize> = HashMap::new();
+    let mut segments = vec![];
+
+    for face in tessellation.faces() {
+        let ends: Vec<_> = (0..3)
+            .filter_map(|i| {
+                let (a, b) = (face[i], face[(i + 1) % 3]);
+                if above(a) == above(b) {
+                    return None;
+                }
+                let key = (a.min(b), a.max(b));
+                let index = *crossings.entry(key).or_insert_with(|| {
+                    let t = distances[a] / (distances[a] - distances[b]);
+                    crossing_points.push(points[a] + (points[b] - points[a]) * t);
+                    crossing_uvs.push(uvs[a] + (uvs[b] - uvs[a]) * t);
+                    crossing_points.len() - 1
+                });
+                Some(index)
+            })
+            .collect();
+        if let [a, b] = ends[..] {
+            segments.push((a, b));
+        }
+    }
+
+    let mut adjacency = vec![vec![]; crossing_points.len()];
+    segments.iter().enumerate().for_each(|(i, (a, b))| {
+        adjacency[*a].push(i);
+        adjacency[*b].push(i);
+    });
+
+    let mut used = vec![false; segments.len()];
+    let walk = |chain: &mut Vec<usize>, used: &mut Vec<bool>| {
+        while let Some(s) = adjacency[*chain.last().unwrap()]
+            .iter()
+            .copied()
+            .find(|s| !used[*s])
+        {
+            used[s] = true;
+            let (a, b) = segments[s];
+            chain.push(if a == *chain.last().unwrap() { b } else { a });
+        }
+    };
+
+    let mut contours = vec![];
+    for (s, &(a, b)) in segments.iter().enumerate() {
+        if used[s] {
+            continue;
+        }
+        used[s] = true;
+        let mut chain = vec![a, b];
+        walk(&mut chain, &mut used);
+
+        let closed = chain.len() > 3 && chain.first() == chain.last();
+        if closed {
+            chain.pop();
+        } else {
+            let mut backward = vec![a];
+            walk(&mut backward, &mut used);
+            backward.reverse();
+            backward.pop();
+            backward.extend(chain);
+            chain = backward;
+        }
+
+        contours.push(ContourPolyline {
+            offset,
+            points: chain.iter().map(|i| crossing_points[*i]).collect(),
+            uvs: chain.iter().map(|i| crossing_uvs[*i]).collect(),
+            closed,
+        });
+    }
+    contours
+}
+
+/// Moves the contour points onto the exact intersection of the surface and the plane
+///
+/// Each newton step moves the (u, v) parameter along the gradient of the distance to the plane.
+pub fn refine_contour<T: FloatingPoint>(
+    surface: &NurbsSurface3D<T>,
+    normal: &Vector3<T>,
+    contour: &mut ContourPolyline<T>,
+    refinement: &ContourRefinement<T>,
+) {
+    let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+    let tolerance = refinement.tolerance * refinement.tolerance;
+    let eps = T::default_epsilon();
+
+    contour
+        .points
+        .iter_mut()
+        .zip(contour.uvs.iter_mut())
+        .for_each(|(point, uv)| {
+            for _ in 0..refinement.max_iterations {
+                let derivs = surface.rational_derivatives(uv.x, uv.y, 1);
+                let g = normal.dot(&derivs[0][0]) - contour.offset;
+                if g * g < tolerance {
+                    break;
+                }
+                let gradient = Vector2::new(normal.dot(&derivs[1][0]), normal.dot(&derivs[0][1]));
+                let denominator = gradient.norm_squared();
+                if denominator < eps {
+                    break;
+                }
+                *uv -= gradient * (g / denominator);
+                uv.x = nalgebra::clamp(uv.x, u0, u1);
+                uv.y = nalgebra::clamp(uv.y, v0, v1);
+            }
+            *point = surface.point_at(uv.x, uv.y);
+        });
+}
+
+/// Builds a line list mesh from the contour polylines
+/// * `asset_usage` - The asset usage for the mesh. If None, default usage is used
+pub fn build_contour_line_list<T: FloatingPoint>(
+    contours: &[ContourPolyline<T>],
+    asset_usage: Option<RenderAssetUsages>,
+) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList, asset_usage.unwrap_or_default());
+    write_contour_line_list(contours, &mut mesh);
+    mesh
+}
+
+/// Writes the contour polylines into an existing line list mesh
+pub fn write_contour_line_list<T: FloatingPoint>(contours: &[ContourPolyline<T>], mesh: &mut Mesh) {
+    let to_array = |p: &Point3<T>| {
+        [
+            p.x.to_f32().unwrap(),
+            p.y.to_f32().unwrap(),
+            p.z.to_f32().unwrap(),
+        ]
+    };
+    write_attribute(
+        mesh,
+        Mesh::ATTRIBUTE_POSITION,
+        contours.iter().flat_map(|c| {
+            let n = c.points.len();
+            let segments = if c.closed { n } else { n.saturating_sub(1) };
+            (0..segments)
+                .flat_map(move |i| [to_array(&c.points[i]), to_array(&c.points[(i + 1) % n])])
+        }),
+    );
+}
+
+/// Component slicing a surface geometry entity with parallel planes in world space
+///
+/// The contours are written as a line list mesh in world space, so the entity holding this component should keep the identity transform.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct NurbsContourDisplay<T: FloatingPoint = f32> {
+    /// The entity holding the surface geometry
+    pub surface: Entity,
+    /// The cutting planes in world space
+    pub planes: ContourPlanes<T>,
+    /// The normal tolerance of the tessellation to slice. If None, curvo's default is used
+    pub norm_tolerance: Option<T>,
+    /// The refinement onto the exact surface. If None, the contours stay on the tessellation
+    pub refinement: Option<ContourRefinement<T>>,
+    /// The degree of the curves fitted to the contours
+    pub fit_degree: usize,
+}
+
+impl<T: FloatingPoint> NurbsContourDisplay<T> {
+    pub fn new(surface: Entity, planes: ContourPlanes<T>) -> Self {
+        Self {
+            surface,
+            planes,
+            norm_tolerance: None,
+            refinement: Some(Default::default()),
+            fit_degree: 3,
+        }
+    }
+}
+
+/// Contours computed for a display in world space
+#[derive(Component, Debug, Clone)]
+pub struct NurbsContourCurves<T: FloatingPoint = f32> {
+    /// The contour polylines
+    pub polylines: Vec<ContourPolyline<T>>,
+    /// The curves fitted to the polylines for export
+    pub curves: Vec<NurbsCurve3D<T>>,
+}
+
+/// System set in which contour displays are updated
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NurbsContourSet;
+
+/// Plugin to keep contour displays up to date with their surface entities
+pub struct NurbsContourPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for NurbsContourPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for NurbsContourPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            update_contour_displays::<T>
+                .in_set(NurbsContourSet)
+                .after(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Recomputes the contours of displays whose surface changed or moved
+#[allow(clippy::type_complexity)]
+pub fn update_contour_displays<T: FloatingPoint>(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    displays: Query<(Entity, Ref<NurbsContourDisplay<T>>, Option<&Handle<Mesh>>)>,
+    surfaces: Query<(Ref<NurbsSurfaceGeometry<T>>, Ref<GlobalTransform>)>,
+) {
+    displays.iter().for_each(|(e, display, handle)| {
+        let Ok((surface, transform)) = surfaces.get(display.surface) else {
+            return;
+        };
+        if !(display.is_changed() || surface.is_changed() || transform.is_changed()) {
+            return;
+        }
+
+        let Some(surface) = transform_surface(&surface, &to_matrix(&transform)) else {
+            return;
+        };
+        let tessellation = surface.tessellate(display.norm_tolerance.map(|norm_tolerance| {
+            AdaptiveTessellationOptions {
+                norm_tolerance,
+                ..Default::default()
+            }
+        }));
+        let polylines = surface_contours(
+            &tessellation,
+            &display.planes,
+            display.refinement.as_ref().map(|r| (surface.surface(), r)),
+        );
+        let curves = polylines
+            .iter()
+            .filter_map(|c| c.try_fit(display.fit_degree).ok())
+            .collect();
+
+        match handle.and_then(|h| meshes.get_mut(h)) {
+            Some(mesh) if mesh.primitive_topology() == PrimitiveTopology::LineList => {
+                write_contour_line_list(&polylines, mesh);
+            }
+            _ => {
+                let mesh = build_contour_line_list(&polylines, None);
+                match handle {
+                    Some(handle) => {
+                        meshes.insert(handle, mesh);
+                    }
+                    None => {
+                        commands.entity(e).insert(meshes.add(mesh));
+                    }
+                }
+            }
+        }
+        commands
+            .entity(e)
+            .insert(NurbsContourCurves { polylines, curves });
+    });
+}
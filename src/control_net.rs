@@ -0,0 +1,347 @@
+use std::ops::Deref;
+
+use curvo::prelude::{dehomogenize, FloatingPoint, NurbsCurve, NurbsSurface};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, OPoint, OVector, U1,
+};
+
+/// NURBS surface kept together with its control net, knot vectors and degrees
+///
+/// curvo's `NurbsSurface` does not expose its definition,
+/// so the tools that edit or analyze the control net work with surfaces built through this type.
+/// It dereferences to the curvo surface for evaluation and tessellation.
+#[derive(Debug, Clone)]
+pub struct ControlNet<T: FloatingPoint, D: DimName>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    surface: NurbsSurface<T, D>,
+    control_points: Vec<Vec<OPoint<T, D>>>,
+    u_degree: usize,
+    v_degree: usize,
+    u_knots: Vec<T>,
+    v_knots: Vec<T>,
+}
+
+impl<T: FloatingPoint, D: DimName> ControlNet<T, D>
+where
+    D: DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    /// Creates a surface from the homogeneous control points in rows along u
+    /// # Failures
+    /// - if the control net is not rectangular or has fewer rows or columns than the degree + 1
+    /// - if the number of knots is not equal to the number of control points + the degree + 1
+    /// - if a knot vector is decreasing or a control point is not finite
+    pub fn try_new(
+        u_degree: usize,
+        v_degree: usize,
+        u_knots: Vec<T>,
+        v_knots: Vec<T>,
+        control_points: Vec<Vec<OPoint<T, D>>>,
+    ) -> anyhow::Result<Self> {
+        let rows = control_points.len();
+        let columns = control_points.first().map_or(0, |row| row.len());
+        anyhow::ensure!(
+            control_points.iter().all(|row| row.len() == columns),
+            "control net rows have different lengths"
+        );
+        anyhow::ensure!(
+            rows > u_degree && columns > v_degree,
+            "control net of {}x{} is too small for degrees ({}, {})",
+            rows,
+            columns,
+            u_degree,
+            v_degree
+        );
+        anyhow::ensure!(
+            u_knots.len() == rows + u_degree + 1,
+            "expected {} u knots, got {}",
+            rows + u_degree + 1,
+            u_knots.len()
+        );
+        anyhow::ensure!(
+            v_knots.len() == columns + v_degree + 1,
+            "expected {} v knots, got {}",
+            columns + v_degree + 1,
+            v_knots.len()
+        );
+        anyhow::ensure!(
+            u_knots.windows(2).all(|w| w[0] <= w[1]) && v_knots.windows(2).all(|w| w[0] <= w[1]),
+            "knot vectors must be non-decreasing"
+        );
+        anyhow::ensure!(
+            control_points
+                .iter()
+                .flatten()
+                .all(|p| p.iter().all(|c| c.is_finite())),
+            "control points must be finite"
+        );
+
+        Ok(Self {
+            surface: NurbsSurface::new(
+                u_degree,
+                v_degree,
+                u_knots.clone(),
+                v_knots.clone(),
+                control_points.clone(),
+            ),
+            control_points,
+            u_degree,
+            v_degree,
+            u_knots,
+            v_knots,
+        })
+    }
+
+    /// Extrudes a curve along the axis, with the same definition as `NurbsSurface::extrude`
+    pub fn extrude(profile: &NurbsCurve<T, D>, axis: OVector<T, DimNameDiff<D, U1>>) -> Self {
+        let half = &axis * T::from_f64(0.5).unwrap();
+        let row = |offset: &OVector<T, DimNameDiff<D, U1>>| {
+            profile
+                .control_points()
+                .iter()
+                .map(|p| {
+                    let w = p[D::dim() - 1];
+                    let mut p = p.clone();
+                    (0..D::dim() - 1).for_each(|i| p[i] += offset[i] * w);
+                    p
+                })
+                .collect::<Vec<_>>()
+        };
+        let control_points = vec![
+            row(&axis),
+            row(&half),
+            row(&OVector::<T, DimNameDiff<D, U1>>::zeros()),
+        ];
+        let (zero, one) = (T::zero(), T::one());
+        Self::try_new(
+            2,
+            profile.degree(),
+            vec![zero, zero, zero, one, one, one],
+            profile.knots().as_slice().to_vec(),
+            control_points,
+        )
+        .expect("extruded control net is valid")
+    }
+
+    /// Lofts the curves, with the same definition as `NurbsSurface::try_loft`
+    pub fn try_loft(curves: &[NurbsCurve<T, D>], degree_v: Option<usize>) -> anyhow::Result<Self> {
+        anyhow::ensure!(curves.len() >= 2, "at least two curves are needed to loft");
+        let curves = unify_curves(curves)?;
+
+        let degree_u = curves[0].degree();
+        let degree_v = degree_v.unwrap_or(degree_u).min(curves.len() - 1);
+
+        let v_curves = (0..curves[0].control_points().len())
+            .map(|i| {
+                let points = curves
+                    .iter()
+                    .map(|c| {
+                        dehomogenize(&c.control_points()[i])
+                            .ok_or_else(|| anyhow::anyhow!("control point has zero weight"))
+                    })
+                    .collect::<anyhow::Result<Vec<_>>>()?;
+                NurbsCurve::try_interpolate(&points, degree_v, None, None)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let v_knots = v_curves[0].knots().as_slice().to_vec();
+
+        Self::try_new(
+            degree_u,
+            degree_v,
+            curves[0].knots().as_slice().to_vec(),
+            v_knots,
+            v_curves
+                .iter()
+                .map(|c| c.control_points().clone())
+                .collect(),
+        )
+    }
+
+    /// Returns the curvo surface
+    pub fn surface(&self) -> &NurbsSurface<T, D> {
+        &self.surface
+    }
+
+    /// Returns the homogeneous control points in rows along u
+    pub fn control_points(&self) -> &Vec<Vec<OPoint<T, D>>> {
+        &self.control_points
+    }
+
+    pub fn u_degree(&self) -> usize {
+        self.u_degree
+    }
+
+    pub fn v_degree(&self) -> usize {
+        self.v_degree
+    }
+
+    pub fn u_knots(&self) -> &[T] {
+        &self.u_knots
+    }
+
+    pub fn v_knots(&self) -> &[T] {
+        &self.v_knots
+    }
+
+    /// Returns the u and v domains of the knot vectors
+    pub fn knots_domain(&self) -> ((T, T), (T, T)) {
+        (self.u_knots_domain(), self.v_knots_domain())
+    }
+}
+
+impl<T: FloatingPoint, D: DimName> Deref for ControlNet<T, D>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    type Target = NurbsSurface<T, D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.surface
+    }
+}
+
+impl<T: FloatingPoint, D: DimName> From<ControlNet<T, D>> for NurbsSurface<T, D>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn from(value: ControlNet<T, D>) -> Self {
+        value.surface
+    }
+}
+
+/// Elevates the curves to the same degree and refines them to the same knot vector over a common domain
+fn unify_curves<T: FloatingPoint, D: DimName>(
+    curves: &[NurbsCurve<T, D>],
+) -> anyhow::Result<Vec<NurbsCurve<T, D>>>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    let degree = curves.iter().map(|c| c.degree()).max().unwrap_or(0);
+    let mut curves = curves
+        .iter()
+        .map(|c| {
+            if c.degree() < degree {
+                c.try_elevate_degree(degree)
+            } else {
+                Ok(c.clone())
+            }
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let width = curves
+        .iter()
+        .map(|c| c.knots().last() - c.knots().first())
+        .fold(T::zero(), |a, b| if b > a { b } else { a });
+    curves.iter_mut().for_each(|c| {
+        let (first, last) = (c.knots().first(), c.knots().last());
+        let scale = if last > first {
+            width / (last - first)
+        } else {
+            T::one()
+        };
+        c.knots_mut()
+            .iter_mut()
+            .for_each(|k| *k = (*k - first) * scale);
+    });
+
+    let merged = curves.iter().fold(vec![], |merged: Vec<T>, c| {
+        merge_knots(&merged, c.knots().as_slice())
+    });
+    curves.iter_mut().for_each(|c| {
+        let missing = missing_knots(&merged, c.knots().as_slice());
+        if !missing.is_empty() {
+            c.knot_refine(missing);
+        }
+    });
+    Ok(curves)
+}
+
+/// Merges two sorted knot vectors, keeping the highest multiplicity of each knot
+fn merge_knots<T: FloatingPoint>(a: &[T], b: &[T]) -> Vec<T> {
+    let eps = T::default_epsilon();
+    let (mut i, mut j) = (0, 0);
+    let mut merged = vec![];
+    while i < a.len() || j < b.len() {
+        if j >= b.len() || (i < a.len() && a[i] < b[j] - eps) {
+            merged.push(a[i]);
+            i += 1;
+        } else if i >= a.len() || b[j] < a[i] - eps {
+            merged.push(b[j]);
+            j += 1;
+        } else {
+            merged.push(a[i]);
+            i += 1;
+            j += 1;
+        }
+    }
+    merged
+}
+
+/// Returns the knots of the sorted `all` that are missing from the sorted `subset`
+fn missing_knots<T: FloatingPoint>(all: &[T], subset: &[T]) -> Vec<T> {
+    let eps = T::default_epsilon();
+    let mut j = 0;
+    all.iter()
+        .filter(|k| {
+            let found = j < subset.len() && (**k - subset[j]) <= eps && (subset[j] - **k) <= eps;
+            if found {
+                j += 1;
+            }
+            !found
+        })
+        .copied()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use curvo::prelude::{NurbsCurve3D, NurbsSurface};
+    use nalgebra::{Point3, Vector3};
+
+    use super::*;
+
+    fn profile(z: f64) -> NurbsCurve3D<f64> {
+        let points = [(0., 0.), (1., 0.5), (2., -0.5), (3., 0.)].map(|(x, y)| Point3::new(x, y, z));
+        NurbsCurve3D::try_interpolate(&points, 3, None, None).unwrap()
+    }
+
+    #[test]
+    fn matches_curvo_surfaces() {
+        let extruded = ControlNet::extrude(&profile(0.), Vector3::z() * 2.);
+        let expected = NurbsSurface::extrude(&profile(0.), Vector3::z() * 2.);
+        let lofted = ControlNet::try_loft(&[profile(0.), profile(1.), profile(3.)], None).unwrap();
+        let expected_loft =
+            NurbsSurface::try_loft(&[profile(0.), profile(1.), profile(3.)], None).unwrap();
+
+        for (u, v) in [(0., 0.), (0.3, 0.6), (1., 0.25), (0.5, 1.)] {
+            assert!((extruded.point_at(u, v) - expected.point_at(u, v)).norm() < 1e-9);
+            assert!((lofted.point_at(u, v) - expected_loft.point_at(u, v)).norm() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_nets() {
+        let net = ControlNet::extrude(&profile(0.), Vector3::z());
+        let mut points = net.control_points().clone();
+        points[1].pop();
+        assert!(ControlNet::try_new(
+            net.u_degree(),
+            net.v_degree(),
+            net.u_knots().to_vec(),
+            net.v_knots().to_vec(),
+            points
+        )
+        .is_err());
+        assert!(ControlNet::try_new(
+            net.u_degree(),
+            net.v_degree(),
+            net.u_knots()[1..].to_vec(),
+            net.v_knots().to_vec(),
+            net.control_points().clone()
+        )
+        .is_err());
+    }
+}
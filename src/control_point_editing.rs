@@ -0,0 +1,473 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    asset::{Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, Or, With, Without},
+        removal_detection::RemovedComponents,
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Local, Query, Res, ResMut, Resource},
+    },
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    input::{keyboard::KeyCode, mouse::MouseButton, ButtonInput},
+    math::{
+        primitives::{Direction3d, Plane3d, Sphere},
+        Vec2, Vec3,
+    },
+    prelude::{Deref, SpatialBundle},
+    render::{camera::Camera, mesh::Mesh},
+    transform::components::{GlobalTransform, Transform},
+    window::{PrimaryWindow, Window},
+};
+use curvo::prelude::FloatingPoint;
+use nalgebra::Point3;
+
+use crate::{
+    geometry::to_vec3,
+    prelude::{homogenize, NurbsCurveGeometry, NurbsGeometryPlugin, NurbsSurfaceGeometry},
+};
+
+/// Marker component to edit the control points of a NURBS geometry component on the entity
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct EditableNurbs;
+
+/// Index of a control point in a curve or a surface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ControlPointIndex {
+    Curve(usize),
+    Surface(usize, usize),
+}
+
+/// Handle entity spawned as a child of an editable geometry for each control point
+///
+/// Handles are spawned with a sphere mesh of `ControlPointEditing::handle_radius`;
+/// insert a material on `Added<ControlPointHandle>` to render them.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ControlPointHandle {
+    /// The entity holding the edited geometry
+    pub owner: Entity,
+    /// The index of the control point in the geometry
+    pub index: ControlPointIndex,
+}
+
+/// Handle entities spawned for an editable geometry
+#[derive(Component, Debug, Default, Clone, Deref)]
+pub struct ControlPointHandles(Vec<Entity>);
+
+/// Plane on which a control point handle is dragged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DragPlane {
+    /// The plane facing the camera through the dragged control point
+    ViewAligned,
+    /// The plane with the normal in world space through the dragged control point
+    Constrained(Direction3d),
+}
+
+/// Settings for the control point editing
+#[derive(Resource, Debug, Clone)]
+pub struct ControlPointEditing {
+    /// The plane on which handles are dragged
+    pub drag_plane: DragPlane,
+    /// The distance in logical pixels within which a handle is picked by the cursor
+    pub pick_radius: f32,
+    /// The radius of the handle mesh in the local space of the geometry
+    pub handle_radius: f32,
+    /// The mouse button to drag handles
+    pub drag_button: MouseButton,
+    /// The key to hold for editing the weight instead of the position
+    pub weight_modifier: KeyCode,
+    /// The exponential rate at which the weight changes per logical pixel of vertical cursor motion
+    pub weight_sensitivity: f32,
+}
+
+impl Default for ControlPointEditing {
+    fn default() -> Self {
+        Self {
+            drag_plane: DragPlane::ViewAligned,
+            pick_radius: 12.,
+            handle_radius: 0.05,
+            drag_button: MouseButton::Left,
+            weight_modifier: KeyCode::ShiftLeft,
+            weight_sensitivity: 1e-2,
+        }
+    }
+}
+
+/// The control point handle being dragged
+#[derive(Resource, Debug, Default, Clone)]
+pub struct ControlPointDrag {
+    active: Option<ActiveDrag>,
+}
+
+impl ControlPointDrag {
+    /// Returns the handle entity being dragged
+    pub fn handle(&self) -> Option<Entity> {
+        self.active.as_ref().map(|a| a.handle)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct ActiveDrag {
+    handle: Entity,
+    plane_origin: Vec3,
+    plane_normal: Direction3d,
+    start_cursor: Vec2,
+    start_weight: f32,
+}
+
+/// System set in which control point handles are picked, dragged and synchronized
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ControlPointEditingSet;
+
+/// Plugin to edit control points of NURBS geometry components by dragging handles in the viewport
+pub struct ControlPointEditingPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for ControlPointEditingPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for ControlPointEditingPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<NurbsGeometryPlugin<T>>() {
+            app.add_plugins(NurbsGeometryPlugin::<T>::default());
+        }
+        app.init_resource::<ControlPointEditing>()
+            .init_resource::<ControlPointDrag>()
+            .add_systems(
+                Update,
+                (
+                    despawn_control_point_handles,
+                    spawn_control_point_handles::<T>,
+                    drag_control_point_handles::<T>,
+                    sync_control_point_handles::<T>,
+                )
+                    .chain()
+                    .in_set(ControlPointEditingSet),
+            );
+    }
+}
+
+/// Spawns handle entities for the control points of editable geometries without handles
+///
+/// Handles of control points with a zero weight are spawned at the origin.
+/// Handles are respawned after `sync_control_point_handles` removes them for a changed number of control points.
+#[allow(clippy::type_complexity)]
+pub fn spawn_control_point_handles<T: FloatingPoint>(
+    mut commands: Commands,
+    settings: Res<ControlPointEditing>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut handle_mesh: Local<Option<(f32, Handle<Mesh>)>>,
+    curves: Query<
+        (Entity, &NurbsCurveGeometry<T>),
+        (With<EditableNurbs>, Without<ControlPointHandles>),
+    >,
+    surfaces: Query<
+        (Entity, &NurbsSurfaceGeometry<T>),
+        (With<EditableNurbs>, Without<ControlPointHandles>),
+    >,
+) {
+    if curves.is_empty() && surfaces.is_empty() {
+        return;
+    }
+    let radius = settings.handle_radius;
+    let mesh = match handle_mesh.as_ref() {
+        Some((r, mesh)) if *r == radius => mesh.clone(),
+        _ => {
+            let mesh = meshes.add(Sphere::new(radius));
+            *handle_mesh = Some((radius, mesh.clone()));
+            mesh
+        }
+    };
+
+    let mut spawn = |owner: Entity, indices: Vec<(ControlPointIndex, Point3<T>)>| {
+        let handles = indices
+            .into_iter()
+            .map(|(index, p)| {
+                commands
+                    .spawn((
+                        ControlPointHandle { owner, index },
+                        mesh.clone(),
+                        SpatialBundle::from_transform(Transform::from_translation(to_vec3(
+                            p.coords.as_slice(),
+                        ))),
+                    ))
+                    .set_parent(owner)
+                    .id()
+            })
+            .collect();
+        commands.entity(owner).insert(ControlPointHandles(handles));
+    };
+
+    curves.iter().for_each(|(e, curve)| {
+        let indices = (0..curve.control_points().len())
+            .map(|i| {
+                let p = curve.control_point(i).unwrap_or_else(Point3::origin);
+                (ControlPointIndex::Curve(i), p)
+            })
+            .collect();
+        spawn(e, indices);
+    });
+
+    surfaces.iter().for_each(|(e, surface)| {
+        let indices = surface
+            .control_points()
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| (0..row.len()).map(move |j| (i, j)))
+            .map(|(i, j)| {
+                let p = surface.control_point((i, j)).unwrap_or_else(Point3::origin);
+                (ControlPointIndex::Surface(i, j), p)
+            })
+            .collect();
+        spawn(e, indices);
+    });
+}
+
+/// Despawns the handle entities of geometries no longer marked as editable
+pub fn despawn_control_point_handles(
+    mut commands: Commands,
+    mut removed: RemovedComponents<EditableNurbs>,
+    owners: Query<&ControlPointHandles>,
+    mut drag: ResMut<ControlPointDrag>,
+) {
+    removed.read().for_each(|e| {
+        if let Ok(handles) = owners.get(e) {
+            handles.iter().for_each(|h| {
+                if drag.handle() == Some(*h) {
+                    drag.active = None;
+                }
+                commands.entity(*h).despawn_recursive();
+            });
+            commands.entity(e).remove::<ControlPointHandles>();
+        }
+    });
+}
+
+/// Picks a handle under the cursor and drags it, writing the result back into the geometry
+#[allow(clippy::too_many_arguments)]
+pub fn drag_control_point_handles<T: FloatingPoint>(
+    settings: Res<ControlPointEditing>,
+    mut drag: ResMut<ControlPointDrag>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    key_button_input: Res<ButtonInput<KeyCode>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    handles: Query<(Entity, &ControlPointHandle, &GlobalTransform)>,
+    owners: Query<&GlobalTransform, With<EditableNurbs>>,
+    mut curves: Query<&mut NurbsCurveGeometry<T>, With<EditableNurbs>>,
+    mut surfaces: Query<&mut NurbsSurfaceGeometry<T>, With<EditableNurbs>>,
+) {
+    let Some(cursor) = windows.get_single().ok().and_then(|w| w.cursor_position()) else {
+        return;
+    };
+    let Some((camera, camera_transform)) = cameras.iter().find(|(c, _)| c.is_active) else {
+        return;
+    };
+
+    if mouse_button_input.just_released(settings.drag_button) {
+        drag.active = None;
+        return;
+    }
+
+    if mouse_button_input.just_pressed(settings.drag_button) {
+        let picked = handles
+            .iter()
+            .filter_map(|(e, handle, tr)| {
+                camera
+                    .world_to_viewport(camera_transform, tr.translation())
+                    .map(|p| (e, handle, tr, p.distance(cursor)))
+            })
+            .filter(|(.., d)| *d <= settings.pick_radius)
+            .min_by(|a, b| a.3.total_cmp(&b.3));
+        drag.active = picked.map(|(e, handle, tr, _)| {
+            let weight = match handle.index {
+                ControlPointIndex::Curve(i) => {
+                    curves.get(handle.owner).ok().and_then(|c| c.weight(i))
+                }
+                ControlPointIndex::Surface(i, j) => surfaces
+                    .get(handle.owner)
+                    .ok()
+                    .and_then(|s| s.weight((i, j))),
+            };
+            let plane_normal = match settings.drag_plane {
+                DragPlane::ViewAligned => {
+                    Direction3d::new(camera_transform.back()).unwrap_or(Direction3d::Z)
+                }
+                DragPlane::Constrained(normal) => normal,
+            };
+            ActiveDrag {
+                handle: e,
+                plane_origin: tr.translation(),
+                plane_normal,
+                start_cursor: cursor,
+                start_weight: weight.and_then(|w| w.to_f32()).unwrap_or(1.),
+            }
+        });
+        return;
+    }
+
+    let Some(active) = drag.active.as_ref() else {
+        return;
+    };
+    if !mouse_button_input.pressed(settings.drag_button) {
+        drag.active = None;
+        return;
+    }
+    let Ok((_, handle, _)) = handles.get(active.handle) else {
+        drag.active = None;
+        return;
+    };
+    let Ok(owner_transform) = owners.get(handle.owner) else {
+        return;
+    };
+
+    let weight = if key_button_input.pressed(settings.weight_modifier) {
+        let dy = active.start_cursor.y - cursor.y;
+        Some(active.start_weight * (dy * settings.weight_sensitivity).exp())
+    } else {
+        None
+    };
+    let position = if weight.is_none() {
+        let Some(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+            return;
+        };
+        let Some(d) = ray.intersect_plane(active.plane_origin, Plane3d::new(*active.plane_normal))
+        else {
+            return;
+        };
+        let local = owner_transform
+            .affine()
+            .inverse()
+            .transform_point3(ray.get_point(d));
+        Some(Point3::new(
+            T::from_f32(local.x).unwrap(),
+            T::from_f32(local.y).unwrap(),
+            T::from_f32(local.z).unwrap(),
+        ))
+    } else {
+        None
+    };
+    let weight = weight.map(|w| T::from_f32(w.max(f32::EPSILON)).unwrap());
+
+    match handle.index {
+        ControlPointIndex::Curve(i) => {
+            if let Ok(mut curve) = curves.get_mut(handle.owner) {
+                let p = position.or_else(|| curve.control_point(i));
+                let w = weight.or_else(|| curve.weight(i));
+                if let (Some(p), Some(w)) = (p, w) {
+                    curve.set_control_point(i, homogenize(&p, w));
+                }
+            }
+        }
+        ControlPointIndex::Surface(i, j) => {
+            if let Ok(mut surface) = surfaces.get_mut(handle.owner) {
+                let p = position.or_else(|| surface.control_point((i, j)));
+                let w = weight.or_else(|| surface.weight((i, j)));
+                if let (Some(p), Some(w)) = (p, w) {
+                    surface.set_control_point((i, j), homogenize(&p, w));
+                }
+            }
+        }
+    }
+}
+
+/// Moves the handle entities to the control points of changed geometries
+///
+/// Handles of geometries whose number of control points changed, e.g. by a knot refinement, are despawned
+/// so that `spawn_control_point_handles` respawns them with valid indices.
+#[allow(clippy::type_complexity)]
+pub fn sync_control_point_handles<T: FloatingPoint>(
+    mut commands: Commands,
+    mut drag: ResMut<ControlPointDrag>,
+    owners: Query<
+        (
+            Entity,
+            &ControlPointHandles,
+            Option<&NurbsCurveGeometry<T>>,
+            Option<&NurbsSurfaceGeometry<T>>,
+        ),
+        Or<(
+            Changed<NurbsCurveGeometry<T>>,
+            Changed<NurbsSurfaceGeometry<T>>,
+        )>,
+    >,
+    mut handles: Query<(&ControlPointHandle, &mut Transform)>,
+) {
+    owners.iter().for_each(|(e, owned, curve, surface)| {
+        let count = curve
+            .map(|c| c.control_points().len())
+            .or(surface.map(|s| s.control_point_count()))
+            .unwrap_or_default();
+        if count != owned.len() {
+            owned.iter().for_each(|h| {
+                if drag.handle() == Some(*h) {
+                    drag.active = None;
+                }
+                commands.entity(*h).despawn_recursive();
+            });
+            commands.entity(e).remove::<ControlPointHandles>();
+            return;
+        }
+
+        let mut iter = handles.iter_many_mut(owned.iter());
+        while let Some((handle, mut tr)) = iter.fetch_next() {
+            let p = match (handle.index, curve, surface) {
+                (ControlPointIndex::Curve(i), Some(curve), _) => curve.control_point(i),
+                (ControlPointIndex::Surface(i, j), _, Some(surface)) => {
+                    surface.control_point((i, j))
+                }
+                _ => None,
+            };
+            if let Some(p) = p {
+                tr.translation = to_vec3(p.coords.as_slice());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::app::App;
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::Point4;
+
+    use super::*;
+
+    fn curve(count: usize) -> NurbsCurveGeometry<f64> {
+        let points = (0..count)
+            .map(|i| Point4::new(i as f64, 0., 0., 1.))
+            .collect();
+        let knots = [
+            vec![0.; 2],
+            (1..count - 1).map(|i| i as f64).collect(),
+            vec![(count - 1) as f64; 2],
+        ]
+        .concat();
+        NurbsCurveGeometry(NurbsCurve3D::try_new(1, points, knots).unwrap())
+    }
+
+    #[test]
+    fn respawns_handles_when_control_point_count_changes() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Mesh>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .add_plugins(ControlPointEditingPlugin::<f64>::default());
+        let e = app.world.spawn((curve(5), EditableNurbs)).id();
+        app.update();
+        let handles = |app: &App| app.world.get::<ControlPointHandles>(e).map(|h| h.len());
+        assert_eq!(handles(&app), Some(5));
+
+        *app.world.get_mut::<NurbsCurveGeometry<f64>>(e).unwrap() = curve(3);
+        app.update();
+        app.update();
+        assert_eq!(handles(&app), Some(3));
+        let mut query = app.world.query::<&ControlPointHandle>();
+        assert_eq!(query.iter(&app.world).count(), 3);
+    }
+}
@@ -0,0 +1,218 @@
+use bevy::{
+    asset::{Assets, Handle},
+    math::Vec3,
+    render::{
+        mesh::{Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+};
+use curvo::prelude::{FloatingPoint, NurbsCurve};
+use nalgebra::{allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, U1};
+
+use crate::{derivatives::curve_derivatives, geometry::to_vec3, mesh_buffer::write_attribute};
+
+/// Curvature of a curve at a parameter
+#[derive(Debug, Clone, Copy)]
+pub struct CurvatureSample<T: FloatingPoint = f32> {
+    /// The parameter of the curve
+    pub parameter: T,
+    /// The point on the curve
+    pub point: Vec3,
+    /// The unit tangent
+    pub tangent: Vec3,
+    /// The curvature vector towards the center of curvature, whose length is the curvature
+    pub vector: Vec3,
+    /// The curvature, signed by the turning direction in the XY plane for 2D curves
+    pub curvature: f32,
+}
+
+/// Samples the curvature at uniform parameters of a 2D or 3D curve
+/// * `count` - The number of samples, at least 2
+pub fn sample_curvature<T: FloatingPoint, D>(
+    curve: &NurbsCurve<T, D>,
+    count: usize,
+) -> Vec<CurvatureSample<T>>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let planar = DimNameDiff::<D, U1>::dim() == 2;
+    let count = count.max(2);
+    let (t0, t1) = curve.knots_domain();
+    (0..count)
+        .map(|i| {
+            let parameter =
+                t0 + (t1 - t0) * T::from_usize(i).unwrap() / T::from_usize(count - 1).unwrap();
+            let derivs = curve_derivatives(curve, parameter, 2);
+            let d1 = to_vec3(derivs[1].as_slice());
+            let d2 = to_vec3(derivs[2].as_slice());
+            let speed = d1.length_squared();
+            let tangent = d1.normalize_or_zero();
+            let vector = if speed > f32::EPSILON {
+                (d2 - tangent * tangent.dot(d2)) / speed
+            } else {
+                Vec3::ZERO
+            };
+            let curvature = if planar && tangent.cross(vector).z < 0. {
+                -vector.length()
+            } else {
+                vector.length()
+            };
+            CurvatureSample {
+                parameter,
+                point: to_vec3(derivs[0].as_slice()),
+                tangent,
+                vector,
+                curvature,
+            }
+        })
+        .collect()
+}
+
+/// Options for curvature combs
+#[derive(Debug, Clone, Copy)]
+pub struct CurvatureCombOptions {
+    /// The number of spikes along the curve
+    pub samples: usize,
+    /// The length of a spike per unit curvature
+    pub scale: f32,
+}
+
+impl Default for CurvatureCombOptions {
+    fn default() -> Self {
+        Self {
+            samples: 128,
+            scale: 1.,
+        }
+    }
+}
+
+/// Curvature comb of a curve for fairing
+///
+/// Spikes point away from the center of curvature with lengths proportional to the curvature,
+/// and the envelope connects their tips.
+#[derive(Debug, Clone)]
+pub struct CurvatureComb<T: FloatingPoint = f32> {
+    samples: Vec<CurvatureSample<T>>,
+    scale: f32,
+}
+
+impl<T: FloatingPoint> CurvatureComb<T> {
+    /// Samples the curvature comb of a 2D or 3D curve
+    pub fn new<D>(curve: &NurbsCurve<T, D>, options: &CurvatureCombOptions) -> Self
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        Self {
+            samples: sample_curvature(curve, options.samples),
+            scale: options.scale,
+        }
+    }
+
+    /// Returns the curvature samples
+    pub fn samples(&self) -> &[CurvatureSample<T>] {
+        &self.samples
+    }
+
+    /// Returns the tips of the spikes
+    pub fn tips(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.samples.iter().map(|s| s.point - s.vector * self.scale)
+    }
+
+    /// Returns the sample with the smallest curvature magnitude
+    pub fn min_curvature(&self) -> Option<&CurvatureSample<T>> {
+        self.samples
+            .iter()
+            .min_by(|a, b| a.vector.length().total_cmp(&b.vector.length()))
+    }
+
+    /// Returns the sample with the largest curvature magnitude
+    pub fn max_curvature(&self) -> Option<&CurvatureSample<T>> {
+        self.samples
+            .iter()
+            .max_by(|a, b| a.vector.length().total_cmp(&b.vector.length()))
+    }
+
+    /// Returns the sample with the smallest signed curvature, turning the most clockwise for 2D curves
+    pub fn min_signed_curvature(&self) -> Option<&CurvatureSample<T>> {
+        self.samples
+            .iter()
+            .min_by(|a, b| a.curvature.total_cmp(&b.curvature))
+    }
+
+    /// Returns the sample with the largest signed curvature, turning the most counterclockwise for 2D curves
+    pub fn max_signed_curvature(&self) -> Option<&CurvatureSample<T>> {
+        self.samples
+            .iter()
+            .max_by(|a, b| a.curvature.total_cmp(&b.curvature))
+    }
+
+    /// Builds a line list mesh of the spikes followed by the envelope
+    /// * `asset_usage` - The asset usage for the mesh. If None, default usage is used
+    pub fn build_line_list(&self, asset_usage: Option<RenderAssetUsages>) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList, asset_usage.unwrap_or_default());
+        self.write_line_list(&mut mesh);
+        mesh
+    }
+
+    /// Writes the spikes and the envelope into an existing line list mesh
+    pub fn write_line_list(&self, mesh: &mut Mesh) {
+        let tips: Vec<_> = self.tips().collect();
+        let spikes = self
+            .samples
+            .iter()
+            .zip(tips.iter())
+            .flat_map(|(s, tip)| [s.point, *tip]);
+        let envelope = tips.windows(2).flat_map(|w| [w[0], w[1]]);
+        write_attribute(
+            mesh,
+            Mesh::ATTRIBUTE_POSITION,
+            spikes.chain(envelope).map(|v| v.to_array()),
+        );
+    }
+
+    /// Updates the mesh asset of the handle with the comb
+    ///
+    /// The mesh is written in place if it exists with the line list topology, otherwise a new mesh is inserted at the handle.
+    pub fn update_line_list(&self, meshes: &mut Assets<Mesh>, handle: &Handle<Mesh>) {
+        match meshes.get_mut(handle) {
+            Some(mesh) if mesh.primitive_topology() == PrimitiveTopology::LineList => {
+                self.write_line_list(mesh);
+            }
+            _ => {
+                meshes.insert(handle, self.build_line_list(None));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curvo::prelude::NurbsCurve2D;
+    use nalgebra::Point2;
+
+    use super::*;
+
+    #[test]
+    fn compares_curvature_magnitudes_of_2d_curves() {
+        // turns clockwise sharply, then counterclockwise gently
+        let points =
+            [(0., 0.), (1., -1.), (2., 0.), (4., 1.), (6., 0.)].map(|(x, y)| Point2::new(x, y));
+        let curve = NurbsCurve2D::try_interpolate(&points, 3, None, None).unwrap();
+        let comb = CurvatureComb::new(&curve, &CurvatureCombOptions::default());
+        let lengths: Vec<_> = comb.samples().iter().map(|s| s.vector.length()).collect();
+        let max = lengths.iter().copied().fold(0f32, f32::max);
+        let min = lengths.iter().copied().fold(f32::MAX, f32::min);
+
+        assert_eq!(comb.max_curvature().unwrap().vector.length(), max);
+        assert_eq!(comb.min_curvature().unwrap().vector.length(), min);
+        assert!(comb.min_signed_curvature().unwrap().curvature < 0.);
+        assert!(
+            comb.min_curvature().unwrap().curvature.abs()
+                < comb.min_signed_curvature().unwrap().curvature.abs()
+        );
+    }
+}
@@ -0,0 +1,469 @@
+use std::{cmp::Ordering, marker::PhantomData};
+
+use bevy::{
+    app::{App, Plugin, PreUpdate},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        query::{Changed, Or, With},
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    input::{mouse::MouseButton, ButtonInput},
+    math::{Vec2, Vec3},
+    render::camera::Camera,
+    transform::components::GlobalTransform,
+    utils::HashSet,
+    window::{PrimaryWindow, Window},
+};
+use curvo::prelude::{dehomogenize, FloatingPoint, NurbsCurve3D};
+
+use crate::{
+    geometry::to_vec3,
+    prelude::{NurbsBvh, NurbsCurveGeometry},
+};
+
+/// Marker component to make a NURBS curve geometry pickable by the cursor
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PickableCurve;
+
+/// Sampled polyline and control point bounds of a pickable curve in local space
+#[derive(Component, Debug, Clone)]
+pub struct CurvePickCache<T: FloatingPoint = f32> {
+    parameters: Vec<T>,
+    points: Vec<Vec3>,
+    min: Vec3,
+    max: Vec3,
+}
+
+impl<T: FloatingPoint> CurvePickCache<T> {
+    /// Samples the curve with the number of samples per control point
+    pub fn new(curve: &NurbsCurve3D<T>, samples_per_control_point: usize) -> Self {
+        let (start, end) = curve.knots_domain();
+        let n = (curve.control_points().len() * samples_per_control_point).max(2);
+        let parameters: Vec<T> = (0..n)
+            .map(|i| {
+                start + (end - start) * T::from_usize(i).unwrap() / T::from_usize(n - 1).unwrap()
+            })
+            .collect();
+        let points = parameters
+            .iter()
+            .map(|t| to_vec3(curve.point_at(*t).coords.as_slice()))
+            .collect();
+
+        // the curve lies in the convex hull of its control points
+        let (min, max) = curve.control_points().iter().filter_map(dehomogenize).fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), p| {
+                let p = to_vec3(p.coords.as_slice());
+                (min.min(p), max.max(p))
+            },
+        );
+
+        Self {
+            parameters,
+            points,
+            min,
+            max,
+        }
+    }
+
+    /// Returns the corners of the local bounding box
+    pub fn corners(&self) -> [Vec3; 8] {
+        let (a, b) = (self.min, self.max);
+        [
+            Vec3::new(a.x, a.y, a.z),
+            Vec3::new(b.x, a.y, a.z),
+            Vec3::new(a.x, b.y, a.z),
+            Vec3::new(b.x, b.y, a.z),
+            Vec3::new(a.x, a.y, b.z),
+            Vec3::new(b.x, a.y, b.z),
+            Vec3::new(a.x, b.y, b.z),
+            Vec3::new(b.x, b.y, b.z),
+        ]
+    }
+}
+
+/// Hit of the cursor on a curve
+#[derive(Debug, Clone, Copy)]
+pub struct CurveHit<T: FloatingPoint = f32> {
+    /// The entity holding the curve
+    pub entity: Entity,
+    /// The curve parameter at the hit
+    pub parameter: T,
+    /// The point on the curve at the hit in world space
+    pub position: Vec3,
+    /// The distance between the cursor and the curve in logical pixels
+    pub screen_distance: f32,
+    /// The distance from the camera to the hit along the view direction
+    pub depth: f32,
+}
+
+/// Settings for the curve picking
+#[derive(Resource, Debug, Clone)]
+pub struct CurvePickingSettings {
+    /// The distance in logical pixels within which a curve is hit by the cursor
+    pub radius: f32,
+    /// The number of samples per control point used for the coarse search
+    pub samples_per_control_point: usize,
+    /// The number of iterations to refine the parameter on the closest sampled segment
+    pub refinement_iterations: usize,
+    /// The mouse button emitting click events
+    pub click_button: MouseButton,
+}
+
+impl Default for CurvePickingSettings {
+    fn default() -> Self {
+        Self {
+            radius: 8.,
+            samples_per_control_point: 8,
+            refinement_iterations: 16,
+            click_button: MouseButton::Left,
+        }
+    }
+}
+
+/// The curve hovered by the cursor
+#[derive(Resource, Debug, Clone)]
+pub struct HoveredCurve<T: FloatingPoint = f32>(pub Option<CurveHit<T>>);
+
+impl<T: FloatingPoint> Default for HoveredCurve<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+/// Event sent when the cursor starts hovering a curve
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CurveOver<T: FloatingPoint = f32>(pub CurveHit<T>);
+
+/// Event sent when the cursor stops hovering a curve
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CurveOut(pub Entity);
+
+/// Event sent when a hovered curve is clicked
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CurveClick<T: FloatingPoint = f32>(pub CurveHit<T>);
+
+/// System set in which curves are picked by the cursor
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CurvePickingSet;
+
+/// Plugin to pick NURBS curve geometries by the cursor in screen space
+pub struct CurvePickingPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for CurvePickingPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for CurvePickingPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CurvePickingSettings>()
+            .init_resource::<HoveredCurve<T>>()
+            .add_event::<CurveOver<T>>()
+            .add_event::<CurveOut>()
+            .add_event::<CurveClick<T>>()
+            .add_systems(
+                PreUpdate,
+                (update_curve_pick_caches::<T>, pick_curves::<T>)
+                    .chain()
+                    .in_set(CurvePickingSet),
+            );
+
+        #[cfg(feature = "picking")]
+        app.add_systems(
+            PreUpdate,
+            backend::curve_picking_backend::<T>
+                .after(update_curve_pick_caches::<T>)
+                .in_set(bevy_mod_picking::backend::prelude::PickSet::Backend),
+        );
+    }
+}
+
+/// Rebuilds the pick caches of pickable curves when the geometry changes
+#[allow(clippy::type_complexity)]
+pub fn update_curve_pick_caches<T: FloatingPoint>(
+    mut commands: Commands,
+    settings: Res<CurvePickingSettings>,
+    curves: Query<
+        (Entity, &NurbsCurveGeometry<T>),
+        (
+            With<PickableCurve>,
+            Or<(Changed<NurbsCurveGeometry<T>>, Changed<PickableCurve>)>,
+        ),
+    >,
+) {
+    curves.iter().for_each(|(e, curve)| {
+        commands.entity(e).insert(CurvePickCache::new(
+            curve,
+            settings.samples_per_control_point,
+        ));
+    });
+}
+
+/// Finds the curve under the cursor and emits hover and click events
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn pick_curves<T: FloatingPoint>(
+    settings: Res<CurvePickingSettings>,
+    bvh: Option<Res<NurbsBvh>>,
+    mut hovered: ResMut<HoveredCurve<T>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    curves: Query<
+        (
+            Entity,
+            &NurbsCurveGeometry<T>,
+            &CurvePickCache<T>,
+            &GlobalTransform,
+        ),
+        With<PickableCurve>,
+    >,
+    mut over: EventWriter<CurveOver<T>>,
+    mut out: EventWriter<CurveOut>,
+    mut click: EventWriter<CurveClick<T>>,
+) {
+    let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+    let camera = cameras.iter().find(|(c, _)| c.is_active);
+    let hit = cursor
+        .zip(camera)
+        .and_then(|(cursor, (camera, camera_transform))| {
+            let candidates = bvh.and_then(|bvh| {
+                pick_candidates(&bvh, camera, camera_transform, cursor, settings.radius)
+            });
+            curves
+                .iter()
+                .filter(|(e, ..)| candidates.as_ref().is_none_or(|c| c.contains(e)))
+                .filter_map(|(e, curve, cache, transform)| {
+                    pick_curve(
+                        camera,
+                        camera_transform,
+                        transform,
+                        curve,
+                        cache,
+                        cursor,
+                        &settings,
+                    )
+                    .map(|hit| CurveHit { entity: e, ..hit })
+                })
+                .min_by(compare_hits)
+        });
+
+    let previous = hovered.0.map(|h| h.entity);
+    let current = hit.map(|h| h.entity);
+    if previous != current {
+        if let Some(previous) = previous {
+            out.send(CurveOut(previous));
+        }
+        if let Some(hit) = hit {
+            over.send(CurveOver(hit));
+        }
+    }
+    if let Some(hit) = hit {
+        if mouse_button_input.just_pressed(settings.click_button) {
+            click.send(CurveClick(hit));
+        }
+    }
+    hovered.0 = hit;
+}
+
+/// Returns the entities whose bounds may lie within the picking radius of the cursor
+///
+/// The bounds are inflated by the distance between the ray through the cursor and the ray through a point
+/// on the picking radius, which grows with the distance for perspective cameras.
+fn pick_candidates(
+    bvh: &NurbsBvh,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    cursor: Vec2,
+    radius: f32,
+) -> Option<HashSet<Entity>> {
+    let ray = camera.viewport_to_world(camera_transform, cursor)?;
+    let edge = camera.viewport_to_world(camera_transform, cursor + Vec2::new(radius, 0.))?;
+    let offset = ray.origin.distance(edge.origin);
+    let spread = ray.direction.distance(*edge.direction);
+    Some(
+        bvh.ray_candidates_with_slack(ray, |distance| offset + spread * distance)
+            .into_iter()
+            .collect(),
+    )
+}
+
+/// Computes the hit of the cursor on the curve in screen space
+/// * `cursor` - The cursor position in logical pixels
+///
+/// Returns None if the curve is farther than the picking radius from the cursor.
+/// The `entity` of the returned hit is a placeholder to be filled by the caller.
+pub fn pick_curve<T: FloatingPoint>(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    transform: &GlobalTransform,
+    curve: &NurbsCurve3D<T>,
+    cache: &CurvePickCache<T>,
+    cursor: Vec2,
+    settings: &CurvePickingSettings,
+) -> Option<CurveHit<T>> {
+    let project =
+        |p: Vec3| camera.world_to_viewport(camera_transform, transform.transform_point(p));
+
+    // cull by the screen space rectangle of the control point bounds,
+    // unless the bounds reach behind the camera where the rectangle is undefined
+    let corners = cache
+        .corners()
+        .into_iter()
+        .map(project)
+        .collect::<Option<Vec<_>>>();
+    if let Some(corners) = corners {
+        let (min, max) = corners.iter().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        );
+        let radius = Vec2::splat(settings.radius);
+        if cursor.cmplt(min - radius).any() || cursor.cmpgt(max + radius).any() {
+            return None;
+        }
+    }
+
+    let screen = cache.points.iter().map(|p| project(*p)).collect::<Vec<_>>();
+    let (segment, _) = screen
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, w)| match (w[0], w[1]) {
+            (Some(a), Some(b)) => Some((i, distance_to_segment(cursor, a, b))),
+            _ => None,
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    // refine the parameter by golden section search over both segments adjacent to
+    // the closest one, since the true closest point may lie just past either end
+    let screen_distance = |t: T| {
+        project(to_vec3(curve.point_at(t).coords.as_slice()))
+            .map(|p| p.distance(cursor))
+            .unwrap_or(f32::MAX)
+    };
+    let ratio = T::from_f64((5f64.sqrt() - 1.) * 0.5).unwrap();
+    let last = cache.parameters.len() - 1;
+    let (mut a, mut b) = (
+        cache.parameters[segment.saturating_sub(1)],
+        cache.parameters[(segment + 2).min(last)],
+    );
+    for _ in 0..settings.refinement_iterations {
+        let c = b - (b - a) * ratio;
+        let d = a + (b - a) * ratio;
+        if screen_distance(c) < screen_distance(d) {
+            b = d;
+        } else {
+            a = c;
+        }
+    }
+    let parameter = (a + b) * T::from_f64(0.5).unwrap();
+    let distance = screen_distance(parameter);
+    if distance > settings.radius {
+        return None;
+    }
+
+    let position = transform.transform_point(to_vec3(curve.point_at(parameter).coords.as_slice()));
+    let depth = (position - camera_transform.translation()).dot(camera_transform.forward());
+    Some(CurveHit {
+        entity: Entity::PLACEHOLDER,
+        parameter,
+        position,
+        screen_distance: distance,
+        depth,
+    })
+}
+
+fn compare_hits<T: FloatingPoint>(a: &CurveHit<T>, b: &CurveHit<T>) -> Ordering {
+    a.screen_distance
+        .total_cmp(&b.screen_distance)
+        .then(a.depth.total_cmp(&b.depth))
+}
+
+fn distance_to_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+    let ab = b - a;
+    let l = ab.length_squared();
+    if l <= f32::EPSILON {
+        return p.distance(a);
+    }
+    let t = ((p - a).dot(ab) / l).clamp(0., 1.);
+    p.distance(a + ab * t)
+}
+
+/// Picking backend for bevy_mod_picking
+#[cfg(feature = "picking")]
+pub mod backend {
+    use bevy::{
+        ecs::{
+            entity::Entity,
+            event::EventWriter,
+            query::With,
+            system::{Query, Res},
+        },
+        render::camera::Camera,
+        transform::components::GlobalTransform,
+        window::PrimaryWindow,
+    };
+    use bevy_mod_picking::backend::prelude::{HitData, PointerHits, PointerId, PointerLocation};
+    use curvo::prelude::FloatingPoint;
+
+    use super::{pick_curve, CurvePickCache, CurvePickingSettings, PickableCurve};
+    use crate::prelude::NurbsCurveGeometry;
+
+    /// Reports hits on pickable curves for each pointer to bevy_mod_picking
+    #[allow(clippy::type_complexity)]
+    pub fn curve_picking_backend<T: FloatingPoint>(
+        settings: Res<CurvePickingSettings>,
+        pointers: Query<(&PointerId, &PointerLocation)>,
+        cameras: Query<(Entity, &Camera, &GlobalTransform)>,
+        primary_window: Query<Entity, With<PrimaryWindow>>,
+        curves: Query<
+            (
+                Entity,
+                &NurbsCurveGeometry<T>,
+                &CurvePickCache<T>,
+                &GlobalTransform,
+            ),
+            With<PickableCurve>,
+        >,
+        mut output: EventWriter<PointerHits>,
+    ) {
+        pointers.iter().for_each(|(pointer_id, pointer_location)| {
+            let Some(location) = pointer_location.location() else {
+                return;
+            };
+            let Some((camera_entity, camera, camera_transform)) = cameras
+                .iter()
+                .filter(|(_, camera, _)| camera.is_active)
+                .find(|(_, camera, _)| location.is_in_viewport(camera, &primary_window))
+            else {
+                return;
+            };
+            let picks = curves
+                .iter()
+                .filter_map(|(e, curve, cache, transform)| {
+                    pick_curve(
+                        camera,
+                        camera_transform,
+                        transform,
+                        curve,
+                        cache,
+                        location.position,
+                        &settings,
+                    )
+                    .map(|hit| {
+                        (
+                            e,
+                            HitData::new(camera_entity, hit.depth, Some(hit.position), None),
+                        )
+                    })
+                })
+                .collect::<Vec<_>>();
+            if !picks.is_empty() {
+                output.send(PointerHits::new(*pointer_id, picks, camera.order as f32));
+            }
+        });
+    }
+}
@@ -0,0 +1,80 @@
+use curvo::prelude::{FloatingPoint, NurbsCurve};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, OVector, U1,
+};
+
+/// Evaluates the derivatives of the curve from the 0th (the point) up to the order
+///
+/// curvo only exposes the first derivative through `tangent_at`, so the rational derivatives are evaluated here
+/// from the homogeneous control points in the same way.
+pub(crate) fn curve_derivatives<T: FloatingPoint, D>(
+    curve: &NurbsCurve<T, D>,
+    t: T,
+    order: usize,
+) -> Vec<OVector<T, DimNameDiff<D, U1>>>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let degree = curve.degree();
+    let knots = curve.knots();
+    let control_points = curve.control_points();
+    let n = knots.len() - degree - 2;
+    let span = knots.find_knot_span_index(n, degree, t);
+    let basis = knots.derivative_basis_functions(span, t, degree, order.min(degree));
+
+    // derivatives of the homogeneous curve
+    let homogeneous: Vec<OVector<T, D>> = (0..=order)
+        .map(|k| {
+            basis.get(k).map_or_else(OVector::<T, D>::zeros, |row| {
+                row.iter()
+                    .enumerate()
+                    .fold(OVector::<T, D>::zeros(), |acc, (j, b)| {
+                        acc + &control_points[span - degree + j].coords * *b
+                    })
+            })
+        })
+        .collect();
+
+    let w = D::dim() - 1;
+    let mut derivatives: Vec<OVector<T, DimNameDiff<D, U1>>> = Vec::with_capacity(order + 1);
+    for k in 0..=order {
+        let mut v =
+            OVector::<T, DimNameDiff<D, U1>>::from_iterator(homogeneous[k].iter().take(w).copied());
+        for i in 1..=k {
+            v -= &derivatives[k - i] * (binomial::<T>(k, i) * homogeneous[i][w]);
+        }
+        derivatives.push(v / homogeneous[0][w]);
+    }
+    derivatives
+}
+
+fn binomial<T: FloatingPoint>(n: usize, k: usize) -> T {
+    (0..k).fold(T::one(), |acc, i| {
+        acc * T::from_usize(n - i).unwrap() / T::from_usize(i + 1).unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::Point3;
+
+    use super::*;
+
+    #[test]
+    fn matches_curvo_and_finite_differences() {
+        let points = [(0., 0.), (1., 2.), (3., -1.), (4., 1.), (6., 0.)]
+            .map(|(x, y)| Point3::new(x, y, 0.5 * x));
+        let curve = NurbsCurve3D::try_interpolate(&points, 3, None, None).unwrap();
+        let h = 1e-5;
+        for t in [0.1, 0.35, 0.5, 0.8] {
+            let d = curve_derivatives(&curve, t, 2);
+            assert!((d[0] - curve.point_at(t).coords).norm() < 1e-9);
+            assert!((d[1] - curve.tangent_at(t)).norm() < 1e-9);
+            let second = (curve.tangent_at(t + h) - curve.tangent_at(t - h)) / (2. * h);
+            assert!((d[2] - second).norm() < 1e-4);
+        }
+    }
+}
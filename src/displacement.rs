@@ -0,0 +1,338 @@
+use std::sync::Arc;
+
+use bevy::{
+    ecs::component::Component,
+    prelude::{Deref, DerefMut},
+    render::{render_resource::TextureFormat, texture::Image},
+};
+use curvo::prelude::FloatingPoint;
+use nalgebra::{Vector2, Vector3};
+
+use crate::{
+    mesh_optimization::{cluster, TriangleBuffers},
+    mesh_projection::vertex_normals,
+};
+
+/// Grid of heights sampled bilinearly over `[0, 1]` in both directions
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeightMap {
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl HeightMap {
+    /// Creates a height map from the values in rows
+    pub fn new(width: usize, height: usize, values: Vec<f32>) -> anyhow::Result<Self> {
+        anyhow::ensure!(
+            width > 0 && height > 0,
+            "height map of {}x{} is empty",
+            width,
+            height
+        );
+        anyhow::ensure!(
+            values.len() == width * height,
+            "height map of {}x{} has {} values",
+            width,
+            height,
+            values.len()
+        );
+        Ok(Self {
+            width,
+            height,
+            values,
+        })
+    }
+
+    /// Reads the first channel of the image as heights in `[0, 1]` for normalized formats
+    ///
+    /// 8-bit channels are read as raw values, without the sRGB transfer function.
+    pub fn from_image(image: &Image) -> anyhow::Result<Self> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let data = &image.data;
+        let values: Vec<f32> = match image.texture_descriptor.format {
+            TextureFormat::R8Unorm => data.iter().map(|v| *v as f32 / 255.).collect(),
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {
+                data.chunks_exact(4).map(|v| v[0] as f32 / 255.).collect()
+            }
+            TextureFormat::R16Unorm => data
+                .chunks_exact(2)
+                .map(|v| u16::from_le_bytes([v[0], v[1]]) as f32 / 65535.)
+                .collect(),
+            TextureFormat::R32Float => data
+                .chunks_exact(4)
+                .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .collect(),
+            TextureFormat::Rgba32Float => data
+                .chunks_exact(16)
+                .map(|v| f32::from_le_bytes([v[0], v[1], v[2], v[3]]))
+                .collect(),
+            format => anyhow::bail!("image format {:?} is not supported as a height map", format),
+        };
+        Self::new(width, height, values)
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Samples the height bilinearly, clamping to the edges
+    /// * `x` - The position from 0 at the first column to 1 at the last
+    /// * `y` - The position from 0 at the first row to 1 at the last
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let coordinate = |t: f32, size: usize| {
+            let t = if t.is_nan() { 0. } else { t.clamp(0., 1.) } * (size - 1) as f32;
+            let i = (t.floor() as usize).min(size.saturating_sub(2));
+            (i, (i + 1).min(size - 1), t - i as f32)
+        };
+        let (x0, x1, s) = coordinate(x, self.width);
+        let (y0, y1, t) = coordinate(y, self.height);
+        let value = |x: usize, y: usize| self.values[y * self.width + x];
+        let top = value(x0, y0) + (value(x1, y0) - value(x0, y0)) * s;
+        let bottom = value(x0, y1) + (value(x1, y1) - value(x0, y1)) * s;
+        top + (bottom - top) * t
+    }
+}
+
+/// Source of the heights of a displacement
+#[derive(Clone)]
+pub enum HeightSource<T: FloatingPoint = f32> {
+    /// Height evaluated at the uv parameters of the surface
+    Function(Arc<dyn Fn(Vector2<T>) -> T + Send + Sync>),
+    /// Height map stretched over the knot domain of the surface, with u along the columns and v along the rows
+    Map(Arc<HeightMap>),
+}
+
+/// Displacement of the vertices along their normals
+#[derive(Clone)]
+pub struct Displacement<T: FloatingPoint = f32> {
+    pub source: HeightSource<T>,
+    /// The factor applied to the heights
+    pub scale: T,
+    /// The height left in place, e.g. 0.5 for maps displacing both inwards and outwards
+    pub midlevel: T,
+}
+
+impl<T: FloatingPoint> Displacement<T> {
+    /// Creates a displacement by the height evaluated at the uv parameters of the surface
+    pub fn function(f: impl Fn(Vector2<T>) -> T + Send + Sync + 'static) -> Self {
+        Self {
+            source: HeightSource::Function(Arc::new(f)),
+            scale: T::one(),
+            midlevel: T::zero(),
+        }
+    }
+
+    /// Creates a displacement by the heights of a map stretched over the knot domain of the surface
+    pub fn map(map: HeightMap) -> Self {
+        Self {
+            source: HeightSource::Map(Arc::new(map)),
+            scale: T::one(),
+            midlevel: T::zero(),
+        }
+    }
+
+    /// Creates a displacement by the first channel of an image
+    pub fn image(image: &Image) -> anyhow::Result<Self> {
+        Ok(Self::map(HeightMap::from_image(image)?))
+    }
+
+    pub fn with_scale(mut self, scale: T) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_midlevel(mut self, midlevel: T) -> Self {
+        self.midlevel = midlevel;
+        self
+    }
+
+    /// Moves the vertices along their normals and recomputes the normals from the displaced triangles
+    ///
+    /// Vertices at the same position move along their averaged normal, so that split vertices stay together,
+    /// and the recomputed normals are averaged over the vertices which shared their normal before,
+    /// so that seams stay smooth while creases stay sharp.
+    /// * `domain` - The u and v knot domains of the surface, which the height map covers
+    pub(crate) fn apply(&self, buffers: &mut TriangleBuffers<T>, domain: ((T, T), (T, T))) {
+        let ((u0, u1), (v0, v1)) = domain;
+        let normalize = |t: T, min: T, max: T| {
+            let width = max - min;
+            if width > T::zero() {
+                ((t - min) / width).to_f32().unwrap()
+            } else {
+                0.
+            }
+        };
+
+        let scale = buffers
+            .positions
+            .iter()
+            .fold(T::one(), |scale, p| scale.max(p.amax()));
+        let tolerance = T::from_f64(SHARED_TOLERANCE).unwrap() * scale;
+        let cos = T::from_f64(SHARED_NORMAL_ANGLE.cos()).unwrap();
+        let normals = &buffers.normals;
+        let (shared, _) = cluster(&buffers.positions, tolerance, |_, _| true);
+        let (smooth, _) = cluster(&buffers.positions, tolerance, |i, j| {
+            normals[i].dot(&normals[j]) >= cos * normals[i].norm() * normals[j].norm()
+        });
+        let directions = average(normals, &shared);
+
+        let TriangleBuffers { positions, uvs, .. } = buffers;
+        positions
+            .iter_mut()
+            .zip(uvs.iter())
+            .zip(shared.iter())
+            .for_each(|((p, uv), group)| {
+                let height = match &self.source {
+                    HeightSource::Function(f) => f(*uv),
+                    HeightSource::Map(map) => {
+                        T::from_f32(map.sample(normalize(uv.x, u0, u1), normalize(uv.y, v0, v1)))
+                            .unwrap()
+                    }
+                };
+                *p += directions[*group] * ((height - self.midlevel) * self.scale);
+            });
+
+        let normals = average(&vertex_normals(&buffers.positions, &buffers.faces), &smooth);
+        buffers.normals = smooth.iter().map(|group| normals[*group]).collect();
+    }
+}
+
+/// Relative distance, scaled by the largest coordinate, within which vertices are at the same position
+const SHARED_TOLERANCE: f64 = 1e-6;
+
+/// Largest angle in radians between the normals of vertices at the same position sharing their normal
+const SHARED_NORMAL_ANGLE: f64 = 1e-3;
+
+/// Returns the normalized sum of the unit normals in every group
+fn average<T: FloatingPoint>(normals: &[Vector3<T>], groups: &[usize]) -> Vec<Vector3<T>> {
+    let count = groups.iter().max().map_or(0, |g| g + 1);
+    let mut sums = vec![Vector3::zeros(); count];
+    normals.iter().zip(groups.iter()).for_each(|(n, group)| {
+        sums[*group] += n.try_normalize(T::default_epsilon()).unwrap_or(*n);
+    });
+    sums.into_iter()
+        .map(|n| n.try_normalize(T::default_epsilon()).unwrap_or(n))
+        .collect()
+}
+
+/// Component holding the displacement applied to the mesh of a `NurbsSurfaceGeometry`
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct NurbsDisplacement<T: FloatingPoint = f32>(pub Displacement<T>);
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::{Vec2, Vec3},
+        render::mesh::{Mesh, VertexAttributeValues},
+    };
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::{Const, Point4};
+
+    use super::*;
+    use crate::prelude::{ControlNet, NurbsSphere, NurbsSurfaceMesh};
+
+    fn extrusion(points: Vec<Point4<f64>>, knots: Vec<f64>) -> ControlNet<f64, Const<4>> {
+        let degree = knots.len() - points.len() - 1;
+        let profile = NurbsCurve3D::try_new(degree, points, knots).unwrap();
+        ControlNet::extrude(&profile, Vector3::z())
+    }
+
+    fn vertices(mesh: &Mesh) -> Vec<(Vec3, Vec2)> {
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expected positions");
+        };
+        let Some(VertexAttributeValues::Float32x2(uvs)) = mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+        else {
+            panic!("expected uvs");
+        };
+        positions
+            .iter()
+            .zip(uvs.iter())
+            .map(|(p, uv)| (Vec3::from_array(*p), Vec2::from_array(*uv)))
+            .collect()
+    }
+
+    #[test]
+    fn keeps_split_vertices_together() {
+        // folded quadratic profile whose middle knot makes it only C0
+        let surface = extrusion(
+            vec![
+                Point4::new(0., 0., 0., 1.),
+                Point4::new(0.5, 0., 0., 1.),
+                Point4::new(1., 0., 0., 1.),
+                Point4::new(1., 0.5, 0., 1.),
+                Point4::new(1., 1., 0., 1.),
+            ],
+            vec![0., 0., 0., 0.5, 0.5, 1., 1., 1.],
+        );
+        let mesh = NurbsSurfaceMesh::from(surface.tessellate(None))
+            .with_creases(&surface)
+            .with_knot_spans(&surface)
+            .with_flat_shading(true)
+            .with_displacement(
+                &surface,
+                Displacement::function(|uv: Vector2<f64>| uv.x + uv.y).with_scale(0.1),
+            )
+            .build_surface_triangle_list(None);
+        let vertices = vertices(&mesh);
+        assert!(vertices.iter().all(|(p, uv)| vertices
+            .iter()
+            .filter(|(_, other)| other == uv)
+            .all(|(q, _)| p.distance(*q) < 1e-6)));
+    }
+
+    #[test]
+    fn shares_normals_of_seam_vertices() {
+        let sphere = NurbsSphere::<f64>::new(1.).surface();
+        let mesh = NurbsSurfaceMesh::from(sphere.tessellate(None))
+            .with_seams(&sphere, 1e-6)
+            .with_displacement(
+                &sphere,
+                Displacement::function(|uv: Vector2<f64>| (uv.x * 3.).sin() * (uv.y * 2.).cos())
+                    .with_scale(0.1),
+            )
+            .build_surface_triangle_list(None);
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("expected normals");
+        };
+        let vertices = vertices(&mesh);
+        vertices.iter().zip(normals.iter()).for_each(|((p, _), n)| {
+            vertices
+                .iter()
+                .zip(normals.iter())
+                .filter(|((q, _), _)| p.distance(*q) < 1e-5)
+                .for_each(|(_, m)| {
+                    assert!(Vec3::from_array(*n).distance(Vec3::from_array(*m)) < 1e-4);
+                });
+        });
+    }
+
+    #[test]
+    fn samples_height_map_over_knot_domain() {
+        let surface = extrusion(
+            vec![Point4::new(0., 0., 0., 1.), Point4::new(2., 0., 0., 1.)],
+            vec![0., 0., 2., 2.],
+        );
+        let map = HeightMap::new(2, 2, vec![0., 1., 0., 1.]).unwrap();
+        let mesh = NurbsSurfaceMesh::from(surface.tessellate(None))
+            .with_displacement(&surface, Displacement::map(map.clone()))
+            .build_surface_triangle_list(None);
+        let ((u0, u1), (v0, v1)) = surface.knots_domain();
+        vertices(&mesh).iter().for_each(|(p, uv)| {
+            let height = map.sample(
+                (uv.x - u0 as f32) / (u1 - u0) as f32,
+                (uv.y - v0 as f32) / (v1 - v0) as f32,
+            );
+            assert!((p.y.abs() - height).abs() < 1e-5);
+        });
+    }
+}
@@ -0,0 +1,278 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::Handle,
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, Or},
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query},
+    },
+    hierarchy::{BuildChildren, DespawnRecursiveExt},
+    math::{Quat, Vec3},
+    prelude::{Deref, SpatialBundle},
+    render::mesh::Mesh,
+    transform::components::Transform,
+};
+use curvo::prelude::{FloatingPoint, NurbsCurve3D, SurfaceTessellation};
+use nalgebra::Const;
+
+use crate::{
+    geometry::to_vec3,
+    path_following::look_to,
+    prelude::{ArcLengthTable, NurbsCurveGeometry, NurbsSurfaceGeometry, NurbsTessellation},
+};
+
+/// How instances are spaced along a curve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveSpacing {
+    /// Places instances at the arc length interval from the start of the curve
+    Distance(f32),
+    /// Places the number of instances at equal arc length intervals including both ends
+    Count(usize),
+}
+
+/// Returns transforms at equal arc length intervals along the curve
+/// * `align` - If Some, the instances look along the tangent with the up vector, otherwise they keep the identity rotation
+pub fn distribute_along_curve<T: FloatingPoint>(
+    curve: &NurbsCurve3D<T>,
+    spacing: CurveSpacing,
+    align: Option<Vec3>,
+) -> Vec<Transform> {
+    let table = ArcLengthTable::new(curve, curve.control_points().len() * 16);
+    let length = table.length().to_f32().unwrap();
+    let distances: Vec<f32> = match spacing {
+        CurveSpacing::Distance(d) if d > 0. => {
+            let n = (length / d).floor() as usize;
+            (0..=n).map(|i| i as f32 * d).collect()
+        }
+        CurveSpacing::Count(1) => vec![0.],
+        CurveSpacing::Count(n) => (0..n).map(|i| length * i as f32 / (n - 1) as f32).collect(),
+        _ => vec![],
+    };
+
+    distances
+        .into_iter()
+        .map(|d| {
+            let t = table.parameter_at_length(curve, T::from_f32(d).unwrap());
+            let transform =
+                Transform::from_translation(to_vec3(curve.point_at(t).coords.as_slice()));
+            match align.and_then(|up| look_to(to_vec3(curve.tangent_at(t).as_slice()), up)) {
+                Some(rotation) => transform.with_rotation(rotation),
+                None => transform,
+            }
+        })
+        .collect()
+}
+
+/// Returns transforms sampled uniformly by area over the tessellated surface
+/// * `count` - The number of samples
+/// * `seed` - The seed of the deterministic random sequence
+/// * `align` - If true, the local Y axis of the instances is aligned to the interpolated surface normal
+pub fn distribute_on_surface<T: FloatingPoint>(
+    tessellation: &SurfaceTessellation<T, Const<4>>,
+    count: usize,
+    seed: u64,
+    align: bool,
+) -> Vec<Transform> {
+    let points = tessellation
+        .points()
+        .iter()
+        .map(|p| to_vec3(p.coords.as_slice()))
+        .collect::<Vec<_>>();
+    let normals = tessellation
+        .normals()
+        .iter()
+        .map(|n| to_vec3(n.as_slice()))
+        .collect::<Vec<_>>();
+    let faces = tessellation.faces();
+
+    let cumulative = faces
+        .iter()
+        .scan(0., |acc, f| {
+            let (a, b, c) = (points[f[0]], points[f[1]], points[f[2]]);
+            *acc += (b - a).cross(c - a).length() * 0.5;
+            Some(*acc)
+        })
+        .collect::<Vec<f32>>();
+    let Some(total) = cumulative.last().copied().filter(|t| *t > 0.) else {
+        return vec![];
+    };
+
+    let mut random = SplitMix64(seed);
+    (0..count)
+        .map(|_| {
+            let target = random.next_f32() * total;
+            let i = cumulative
+                .partition_point(|c| *c < target)
+                .min(faces.len() - 1);
+            let f = faces[i];
+
+            // uniform barycentric coordinates by the square root mapping
+            let r1 = random.next_f32().sqrt();
+            let r2 = random.next_f32();
+            let (w0, w1, w2) = (1. - r1, r1 * (1. - r2), r1 * r2);
+            let p = points[f[0]] * w0 + points[f[1]] * w1 + points[f[2]] * w2;
+            let transform = Transform::from_translation(p);
+            let n =
+                (normals[f[0]] * w0 + normals[f[1]] * w1 + normals[f[2]] * w2).normalize_or_zero();
+            if align && n != Vec3::ZERO {
+                transform.with_rotation(Quat::from_rotation_arc(Vec3::Y, n))
+            } else {
+                transform
+            }
+        })
+        .collect()
+}
+
+/// Deterministic random number generator for the distributions
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Component to spawn child instances along the curve geometry of the entity
+#[derive(Component, Debug, Clone)]
+pub struct ScatterAlongCurve {
+    /// The spacing of the instances
+    pub spacing: CurveSpacing,
+    /// If Some, the instances look along the tangent with the up vector in the local space of the curve
+    pub align: Option<Vec3>,
+    /// The mesh inserted to each instance
+    pub mesh: Option<Handle<Mesh>>,
+}
+
+/// Component to spawn child instances over the surface geometry of the entity
+#[derive(Component, Debug, Clone)]
+pub struct ScatterOnSurface {
+    /// The number of instances
+    pub count: usize,
+    /// The seed of the deterministic random sequence
+    pub seed: u64,
+    /// Whether the local Y axis of the instances is aligned to the surface normal
+    pub align: bool,
+    /// The mesh inserted to each instance
+    pub mesh: Option<Handle<Mesh>>,
+}
+
+/// Instance spawned by a scatter component
+///
+/// Query `Added<ScatterInstance>` to attach materials or other components to new instances.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ScatterInstance {
+    /// The entity holding the source geometry
+    pub source: Entity,
+    /// The index of the instance in the distribution
+    pub index: usize,
+}
+
+/// Instance entities spawned for a source geometry
+#[derive(Component, Debug, Default, Clone, Deref)]
+pub struct ScatterInstances(Vec<Entity>);
+
+/// System set in which scattered instances are respawned
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScatterSet;
+
+/// Plugin to scatter instances along curve geometries and over surface geometries
+pub struct ScatterPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for ScatterPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for ScatterPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (scatter_along_curves::<T>, scatter_on_surfaces::<T>).in_set(ScatterSet),
+        );
+    }
+}
+
+fn respawn_instances(
+    commands: &mut Commands,
+    source: Entity,
+    previous: Option<&ScatterInstances>,
+    transforms: Vec<Transform>,
+    mesh: &Option<Handle<Mesh>>,
+) {
+    previous.iter().flat_map(|p| p.iter()).for_each(|e| {
+        commands.entity(*e).despawn_recursive();
+    });
+    let instances = transforms
+        .into_iter()
+        .enumerate()
+        .map(|(index, transform)| {
+            let mut instance = commands.spawn((
+                ScatterInstance { source, index },
+                SpatialBundle::from_transform(transform),
+            ));
+            if let Some(mesh) = mesh {
+                instance.insert(mesh.clone());
+            }
+            instance.set_parent(source).id()
+        })
+        .collect();
+    commands.entity(source).insert(ScatterInstances(instances));
+}
+
+/// Respawns the instances of curves when the geometry or the scatter settings change
+#[allow(clippy::type_complexity)]
+pub fn scatter_along_curves<T: FloatingPoint>(
+    mut commands: Commands,
+    sources: Query<
+        (
+            Entity,
+            &NurbsCurveGeometry<T>,
+            &ScatterAlongCurve,
+            Option<&ScatterInstances>,
+        ),
+        Or<(Changed<NurbsCurveGeometry<T>>, Changed<ScatterAlongCurve>)>,
+    >,
+) {
+    sources.iter().for_each(|(e, curve, scatter, previous)| {
+        let transforms = distribute_along_curve(curve, scatter.spacing, scatter.align);
+        respawn_instances(&mut commands, e, previous, transforms, &scatter.mesh);
+    });
+}
+
+/// Respawns the instances of surfaces when the geometry or the scatter settings change
+#[allow(clippy::type_complexity)]
+pub fn scatter_on_surfaces<T: FloatingPoint>(
+    mut commands: Commands,
+    sources: Query<
+        (
+            Entity,
+            &NurbsSurfaceGeometry<T>,
+            &ScatterOnSurface,
+            Option<&NurbsTessellation<T>>,
+            Option<&ScatterInstances>,
+        ),
+        Or<(Changed<NurbsSurfaceGeometry<T>>, Changed<ScatterOnSurface>)>,
+    >,
+) {
+    sources
+        .iter()
+        .for_each(|(e, surface, scatter, tessellation, previous)| {
+            let tessellation = surface.tessellate(tessellation.and_then(|t| t.surface_options()));
+            let transforms =
+                distribute_on_surface(&tessellation, scatter.count, scatter.seed, scatter.align);
+            respawn_instances(&mut commands, e, previous, transforms, &scatter.mesh);
+        });
+}
@@ -0,0 +1,278 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    ecs::{
+        change_detection::{DetectChanges, Mut},
+        component::Component,
+        entity::Entity,
+        query::{Added, Or, With},
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, Res, Resource},
+        world::Ref,
+    },
+    math::Mat3,
+    prelude::{Deref, DerefMut},
+    transform::{components::Transform, TransformBundle, TransformSystem},
+};
+use curvo::prelude::FloatingPoint;
+use nalgebra::{Matrix3, Vector3};
+
+use crate::{
+    geometry::to_vec3,
+    prelude::{
+        NurbsCurveGeometry, NurbsCurveMorph, NurbsGeometryPlugin, NurbsMeshSet,
+        NurbsSurfaceGeometry, NurbsSurfaceMorph,
+    },
+};
+
+/// Origin subtracted from the positions of a mesh before they are cast to f32
+///
+/// Rebasing keeps the precision of geometries far from the world origin,
+/// and the subtracted offset is returned as the translation of a `Transform`.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum MeshOrigin<T: FloatingPoint = f32> {
+    /// Positions are cast as they are
+    #[default]
+    Zero,
+    /// Positions are relative to the point
+    Point(Vector3<T>),
+    /// Positions are relative to the center of the bounding box of the geometry
+    BoundingBoxCenter,
+}
+
+impl<T: FloatingPoint> MeshOrigin<T> {
+    /// Resolves the origin for the points of a geometry
+    pub fn resolve(&self, points: impl IntoIterator<Item = Vector3<T>>) -> Vector3<T> {
+        match self {
+            MeshOrigin::Zero => Vector3::zeros(),
+            MeshOrigin::Point(p) => *p,
+            MeshOrigin::BoundingBoxCenter => {
+                let mut points = points.into_iter();
+                let Some(first) = points.next() else {
+                    return Vector3::zeros();
+                };
+                let (min, max) =
+                    points.fold((first, first), |(min, max), p| (min.inf(&p), max.sup(&p)));
+                (min + max) / T::from_f64(2.).unwrap()
+            }
+        }
+    }
+}
+
+/// Returns the transform translating a rebased mesh back to its origin
+pub(crate) fn origin_transform<T: FloatingPoint>(origin: &Vector3<T>) -> Transform {
+    Transform::from_translation(to_vec3(origin.as_slice()))
+}
+
+/// Resource holding the world position that is rendered at the origin
+#[derive(Resource, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct FloatingOrigin<T: FloatingPoint = f32>(pub Vector3<T>);
+
+impl<T: FloatingPoint> Default for FloatingOrigin<T> {
+    fn default() -> Self {
+        Self(Vector3::zeros())
+    }
+}
+
+/// Component holding the world position of an entity in full precision
+///
+/// The translation of the `Transform` is kept relative to the `FloatingOrigin`.
+#[derive(Component, Debug, Clone, Copy, Deref, DerefMut)]
+pub struct NurbsWorldPosition<T: FloatingPoint = f32>(pub Vector3<T>);
+
+/// Marker component to move the local origin of NURBS geometry components to their control hull center
+///
+/// When the marker or the geometry is added, the geometry is translated so that its control hull is centered
+/// at the local origin, and the center is moved into `NurbsWorldPosition` and the `Transform`.
+/// The geometry, its mesh, its bounds and the keyframes of its morph stay in the same local space, so picking, raycasts,
+/// colliders and other consumers need no adjustment. Reinsert the marker to rebase again after large edits.
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct NurbsOriginRebase;
+
+/// System set in which transforms are rebased to the floating origin
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FloatingOriginSet;
+
+/// Plugin that keeps entities with `NurbsWorldPosition` relative to the `FloatingOrigin`
+pub struct FloatingOriginPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for FloatingOriginPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for FloatingOriginPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<NurbsGeometryPlugin<T>>() {
+            app.add_plugins(NurbsGeometryPlugin::<T>::default());
+        }
+        app.init_resource::<FloatingOrigin<T>>().add_systems(
+            PostUpdate,
+            (
+                (rebase_curve_origins::<T>, rebase_surface_origins::<T>).before(NurbsMeshSet),
+                apply_floating_origin::<T>
+                    .after(NurbsMeshSet)
+                    .before(TransformSystem::TransformPropagate),
+            )
+                .in_set(FloatingOriginSet),
+        );
+    }
+}
+
+/// Sets the translation of entities to their world position relative to the floating origin
+pub fn apply_floating_origin<T: FloatingPoint>(
+    origin: Res<FloatingOrigin<T>>,
+    mut query: Query<(Ref<NurbsWorldPosition<T>>, &mut Transform)>,
+) {
+    let moved = origin.is_changed();
+    query.iter_mut().for_each(|(position, mut transform)| {
+        if moved || position.is_changed() {
+            transform.translation = to_vec3((position.0 - origin.0).as_slice());
+        }
+    });
+}
+
+/// Moves the origin of newly rebased curves to their control hull center
+#[allow(clippy::type_complexity)]
+pub fn rebase_curve_origins<T: FloatingPoint>(
+    mut commands: Commands,
+    mut curves: Query<
+        (
+            Entity,
+            &mut NurbsCurveGeometry<T>,
+            Option<&mut NurbsCurveMorph<T>>,
+            Option<&mut NurbsWorldPosition<T>>,
+            Option<&mut Transform>,
+        ),
+        (
+            With<NurbsOriginRebase>,
+            Or<(Added<NurbsOriginRebase>, Added<NurbsCurveGeometry<T>>)>,
+        ),
+    >,
+) {
+    curves
+        .iter_mut()
+        .for_each(|(e, mut curve, morph, position, transform)| {
+            let center = curve.control_hull_center();
+            if center != Vector3::zeros() {
+                curve.translate(&-center);
+                if let Some(mut morph) = morph {
+                    morph.translate(&-center);
+                }
+                shift_origin(&mut commands, e, &center, position, transform);
+            }
+        });
+}
+
+/// Moves the origin of newly rebased surfaces to their control hull center
+#[allow(clippy::type_complexity)]
+pub fn rebase_surface_origins<T: FloatingPoint>(
+    mut commands: Commands,
+    mut surfaces: Query<
+        (
+            Entity,
+            &mut NurbsSurfaceGeometry<T>,
+            Option<&mut NurbsSurfaceMorph<T>>,
+            Option<&mut NurbsWorldPosition<T>>,
+            Option<&mut Transform>,
+        ),
+        (
+            With<NurbsOriginRebase>,
+            Or<(Added<NurbsOriginRebase>, Added<NurbsSurfaceGeometry<T>>)>,
+        ),
+    >,
+) {
+    surfaces
+        .iter_mut()
+        .for_each(|(e, mut surface, morph, position, transform)| {
+            let center = surface.control_hull_center();
+            if center != Vector3::zeros() {
+                surface.translate(&-center);
+                if let Some(mut morph) = morph {
+                    morph.translate(&-center);
+                }
+                shift_origin(&mut commands, e, &center, position, transform);
+            }
+        });
+}
+
+/// Moves the translation of an entity by the local offset, keeping its geometry in place
+fn shift_origin<T: FloatingPoint>(
+    commands: &mut Commands,
+    entity: Entity,
+    local: &Vector3<T>,
+    position: Option<Mut<NurbsWorldPosition<T>>>,
+    transform: Option<Mut<Transform>>,
+) {
+    // without a transform the geometry is in world space, and the offset becomes its translation
+    let Some(mut transform) = transform else {
+        commands.entity(entity).insert((
+            NurbsWorldPosition(*local),
+            TransformBundle::from_transform(Transform::from_translation(to_vec3(local.as_slice()))),
+        ));
+        return;
+    };
+
+    // the offset in the parent space is the local offset scaled and rotated by the transform
+    let to_t = |v: f32| T::from_f32(v).unwrap();
+    let rotation = Mat3::from_quat(transform.rotation).to_cols_array();
+    let rotation = Matrix3::from_column_slice(&rotation.map(to_t));
+    let scale = Vector3::new(
+        to_t(transform.scale.x),
+        to_t(transform.scale.y),
+        to_t(transform.scale.z),
+    );
+    let offset = rotation * local.component_mul(&scale);
+
+    let translation = transform.translation;
+    transform.translation += to_vec3(offset.as_slice());
+    match position {
+        Some(mut position) => position.0 += offset,
+        None => {
+            let translation = Vector3::new(
+                to_t(translation.x),
+                to_t(translation.y),
+                to_t(translation.z),
+            );
+            commands
+                .entity(entity)
+                .insert(NurbsWorldPosition(translation + offset));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{app::App, asset::Assets, render::mesh::Mesh};
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::Point4;
+
+    use super::*;
+
+    #[test]
+    fn inserts_a_transform_when_rebasing_entities_without_one() {
+        let mut app = App::new();
+        app.init_resource::<Assets<Mesh>>()
+            .add_plugins(FloatingOriginPlugin::<f64>::default());
+        let curve = NurbsCurve3D::try_new(
+            1,
+            vec![Point4::new(100., 0., 0., 1.), Point4::new(102., 0., 0., 1.)],
+            vec![0., 0., 1., 1.],
+        )
+        .unwrap();
+        let e = app
+            .world
+            .spawn((NurbsCurveGeometry(curve), NurbsOriginRebase))
+            .id();
+        app.update();
+
+        let transform = app.world.get::<Transform>(e).unwrap();
+        assert_eq!(transform.translation.x, 101.);
+        let position = app.world.get::<NurbsWorldPosition<f64>>(e).unwrap();
+        assert_eq!(position.0.x, 101.);
+        let geometry = app.world.get::<NurbsCurveGeometry<f64>>(e).unwrap();
+        assert_eq!(geometry.control_hull_center(), Vector3::zeros());
+    }
+}
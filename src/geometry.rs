@@ -0,0 +1,403 @@
+use std::marker::PhantomData;
+
+use bevy::math::Vec3;
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{Assets, Handle},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, Has, Or},
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, ResMut},
+    },
+    log::warn,
+    prelude::{Deref, DerefMut},
+    render::mesh::{Mesh, PrimitiveTopology},
+};
+use curvo::prelude::{dehomogenize, AdaptiveTessellationOptions, FloatingPoint, NurbsCurve3D};
+use nalgebra::{Const, Point3, Point4, Vector3};
+
+use crate::prelude::{
+    ControlNet, GridTessellation, GridTessellationOptions, MeshOptimization, MeshOrigin,
+    MeshRepair, NurbsCurveMesh, NurbsCurveMorph, NurbsDisplacement, NurbsScalarLegend,
+    NurbsSurfaceMesh, NurbsSurfaceMorph, NurbsVertexColors, ScalarFieldLegend,
+};
+
+/// Component holding a NURBS curve that is meshed as a line strip
+#[derive(Component, Debug, Clone, Deref, DerefMut)]
+pub struct NurbsCurveGeometry<T: FloatingPoint = f32>(pub NurbsCurve3D<T>);
+
+/// Component holding a NURBS surface that is meshed as a triangle list
+///
+/// The surface is kept with its control net so that the control points can be edited.
+#[derive(Component, Debug, Clone, Deref, DerefMut)]
+pub struct NurbsSurfaceGeometry<T: FloatingPoint = f32>(pub ControlNet<T, Const<4>>);
+
+/// Tessellation settings used when meshing NURBS geometry components
+#[derive(Component, Debug, Clone, Copy)]
+pub struct NurbsTessellation<T: FloatingPoint = f32> {
+    /// Tolerance for the curve tessellation. If None, curvo's default is used
+    pub curve_tolerance: Option<T>,
+    /// Normal tolerance for the adaptive surface tessellation. If None, curvo's default is used
+    pub surface_norm_tolerance: Option<T>,
+    /// Grid tessellation of surfaces with a lattice of stable topology. If Some, it replaces the adaptive tessellation
+    pub surface_grid: Option<GridTessellationOptions>,
+    /// Repairs applied to degenerate triangles and zero normals of surface meshes
+    pub repair: MeshRepair,
+    /// Optimization applied to surface meshes. If None, the tessellation is written as it is
+    pub optimization: Option<MeshOptimization<T>>,
+    /// Tolerance to detect the seams and poles of closed surfaces. If None, seams are left as tessellated
+    pub seam_tolerance: Option<T>,
+    /// Splits surface meshes along the C0 knot lines so that creases render sharp
+    pub creases: bool,
+    /// Splits surface meshes along all knot lines and writes the knot span of each vertex to `ATTRIBUTE_KNOT_SPAN`
+    pub knot_spans: bool,
+    /// Gives every triangle of surface meshes its own vertices with the normal of its plane
+    pub flat_shading: bool,
+}
+
+impl<T: FloatingPoint> Default for NurbsTessellation<T> {
+    fn default() -> Self {
+        Self {
+            curve_tolerance: None,
+            surface_norm_tolerance: None,
+            surface_grid: None,
+            repair: MeshRepair::default(),
+            optimization: None,
+            seam_tolerance: None,
+            creases: false,
+            knot_spans: false,
+            flat_shading: false,
+        }
+    }
+}
+
+impl<T: FloatingPoint> NurbsTessellation<T> {
+    /// Returns the adaptive tessellation options for surfaces
+    pub fn surface_options(&self) -> Option<AdaptiveTessellationOptions<T>> {
+        self.surface_norm_tolerance
+            .map(|norm_tolerance| AdaptiveTessellationOptions {
+                norm_tolerance,
+                ..Default::default()
+            })
+    }
+}
+
+impl<T: FloatingPoint> NurbsCurveGeometry<T> {
+    /// Returns the center of the bounding box of the control points
+    pub fn control_hull_center(&self) -> Vector3<T> {
+        MeshOrigin::BoundingBoxCenter.resolve(
+            self.0
+                .control_points()
+                .iter()
+                .filter_map(|p| dehomogenize(p).map(|p| p.coords)),
+        )
+    }
+
+    /// Translates the control points by the offset
+    pub fn translate(&mut self, offset: &Vector3<T>) {
+        let control_points = self
+            .0
+            .control_points()
+            .iter()
+            .map(|p| translated(p, offset))
+            .collect();
+        if let Ok(curve) = NurbsCurve3D::try_new(
+            self.0.degree(),
+            control_points,
+            self.0.knots().as_slice().to_vec(),
+        ) {
+            self.0 = curve;
+        }
+    }
+
+    /// Returns the dehomogenized control point at the index, or None if it is out of range or has a zero weight
+    pub fn control_point(&self, index: usize) -> Option<Point3<T>> {
+        self.0.control_points().get(index).and_then(dehomogenize)
+    }
+
+    /// Returns the weight of the control point at the index, or None if it is out of range
+    pub fn weight(&self, index: usize) -> Option<T> {
+        self.0.control_points().get(index).map(|p| p.w)
+    }
+
+    /// Replaces the homogeneous control point at the index, keeping the curve if the index or the result is invalid
+    pub fn set_control_point(&mut self, index: usize, point: Point4<T>) {
+        let mut control_points = self.0.control_points().clone();
+        let Some(p) = control_points.get_mut(index) else {
+            return;
+        };
+        *p = point;
+        if let Ok(curve) = NurbsCurve3D::try_new(
+            self.0.degree(),
+            control_points,
+            self.0.knots().as_slice().to_vec(),
+        ) {
+            self.0 = curve;
+        }
+    }
+}
+
+impl<T: FloatingPoint> NurbsSurfaceGeometry<T> {
+    /// Returns the center of the bounding box of the control points
+    pub fn control_hull_center(&self) -> Vector3<T> {
+        MeshOrigin::BoundingBoxCenter.resolve(
+            self.0
+                .control_points()
+                .iter()
+                .flatten()
+                .filter_map(|p| dehomogenize(p).map(|p| p.coords)),
+        )
+    }
+
+    /// Translates the control points by the offset
+    pub fn translate(&mut self, offset: &Vector3<T>) {
+        let control_points = self
+            .0
+            .control_points()
+            .iter()
+            .map(|row| row.iter().map(|p| translated(p, offset)).collect())
+            .collect();
+        if let Ok(surface) = ControlNet::try_new(
+            self.0.u_degree(),
+            self.0.v_degree(),
+            self.0.u_knots().to_vec(),
+            self.0.v_knots().to_vec(),
+            control_points,
+        ) {
+            self.0 = surface;
+        }
+    }
+
+    /// Returns the dehomogenized control point at the (u, v) index, or None if it is out of range or has a zero weight
+    pub fn control_point(&self, index: (usize, usize)) -> Option<Point3<T>> {
+        self.homogeneous_control_point(index).and_then(dehomogenize)
+    }
+
+    /// Returns the weight of the control point at the (u, v) index, or None if it is out of range
+    pub fn weight(&self, index: (usize, usize)) -> Option<T> {
+        self.homogeneous_control_point(index).map(|p| p.w)
+    }
+
+    /// Returns the number of control points
+    pub fn control_point_count(&self) -> usize {
+        self.0.control_points().iter().map(|row| row.len()).sum()
+    }
+
+    fn homogeneous_control_point(&self, index: (usize, usize)) -> Option<&Point4<T>> {
+        self.0
+            .control_points()
+            .get(index.0)
+            .and_then(|row| row.get(index.1))
+    }
+
+    /// Replaces the homogeneous control point at the (u, v) index, keeping the surface if the index or the result is invalid
+    pub fn set_control_point(&mut self, index: (usize, usize), point: Point4<T>) {
+        let mut control_points = self.0.control_points().clone();
+        let Some(p) = control_points
+            .get_mut(index.0)
+            .and_then(|row| row.get_mut(index.1))
+        else {
+            return;
+        };
+        *p = point;
+        if let Ok(surface) = ControlNet::try_new(
+            self.0.u_degree(),
+            self.0.v_degree(),
+            self.0.u_knots().to_vec(),
+            self.0.v_knots().to_vec(),
+            control_points,
+        ) {
+            self.0 = surface;
+        }
+    }
+}
+
+/// Lifts a euclidean point to the homogeneous space with the weight
+pub fn homogenize<T: FloatingPoint>(point: &Point3<T>, weight: T) -> Point4<T> {
+    Point4::new(point.x * weight, point.y * weight, point.z * weight, weight)
+}
+
+/// Translates the homogeneous point by the offset, keeping its weight
+pub(crate) fn translated<T: FloatingPoint>(point: &Point4<T>, offset: &Vector3<T>) -> Point4<T> {
+    let w = point.w;
+    Point4::new(
+        point.x + offset.x * w,
+        point.y + offset.y * w,
+        point.z + offset.z * w,
+        w,
+    )
+}
+
+/// Converts the first three coordinates to a `Vec3`, filling missing ones with zero
+pub(crate) fn to_vec3<T: FloatingPoint>(coords: &[T]) -> Vec3 {
+    let c = |i: usize| coords.get(i).map_or(0., |v| v.to_f32().unwrap());
+    Vec3::new(c(0), c(1), c(2))
+}
+
+/// System set in which NURBS geometry components are meshed
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NurbsMeshSet;
+
+/// Plugin that keeps the meshes of NURBS geometry components up to date
+pub struct NurbsGeometryPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for NurbsGeometryPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for NurbsGeometryPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (update_curve_meshes::<T>, update_surface_meshes::<T>).in_set(NurbsMeshSet),
+        );
+    }
+}
+
+/// Rewrites the line strip mesh of changed curves in place, inserting a mesh handle if missing
+///
+/// Curves whose tessellation fails the validation keep their previous mesh and a warning is logged.
+///
+/// Curves animated by `NurbsCurveMorph` keep their mesh as the morph updates it in place.
+#[allow(clippy::type_complexity)]
+pub fn update_curve_meshes<T: FloatingPoint>(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    curves: Query<
+        (
+            Entity,
+            &NurbsCurveGeometry<T>,
+            Option<&NurbsTessellation<T>>,
+            Option<&Handle<Mesh>>,
+            Has<NurbsCurveMorph<T>>,
+        ),
+        Changed<NurbsCurveGeometry<T>>,
+    >,
+) {
+    curves
+        .iter()
+        .for_each(|(e, curve, tessellation, handle, morphed)| {
+            if morphed && handle.is_some() {
+                return;
+            }
+            let tolerance = tessellation.and_then(|t| t.curve_tolerance);
+            let mesh = NurbsCurveMesh::new(curve.tessellate(tolerance));
+            let result = match handle {
+                Some(handle) => mesh.try_update_line_strip(&mut meshes, handle),
+                None => mesh.try_build_line_strip(None).map(|m| {
+                    commands.entity(e).insert(meshes.add(m));
+                }),
+            };
+            if let Err(err) = result {
+                warn!("failed to mesh the curve of {:?}: {}", e, err);
+            }
+        });
+}
+
+/// Rewrites the triangle list mesh of changed surfaces in place, inserting a mesh handle if missing
+///
+/// Surfaces whose tessellation fails the validation keep their previous mesh and a warning is logged.
+///
+/// Surfaces animated by `NurbsSurfaceMorph` keep their mesh as the morph rewrites it every frame.
+///
+/// The legend of surfaces with scalar `NurbsVertexColors` is inserted as their `NurbsScalarLegend` component.
+#[allow(clippy::type_complexity)]
+pub fn update_surface_meshes<T: FloatingPoint>(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    surfaces: Query<
+        (
+            Entity,
+            &NurbsSurfaceGeometry<T>,
+            Option<&NurbsTessellation<T>>,
+            Option<&NurbsVertexColors<T>>,
+            Option<&NurbsDisplacement<T>>,
+            Option<&Handle<Mesh>>,
+            Has<NurbsSurfaceMorph<T>>,
+        ),
+        Or<(
+            Changed<NurbsSurfaceGeometry<T>>,
+            Changed<NurbsVertexColors<T>>,
+            Changed<NurbsDisplacement<T>>,
+        )>,
+    >,
+) {
+    surfaces.iter().for_each(
+        |(e, surface, tessellation, colors, displacement, handle, morphed)| {
+            if morphed && handle.is_some() {
+                return;
+            }
+            let mesh = match tessellation.and_then(|t| t.surface_grid) {
+                Some(grid) => NurbsSurfaceMesh::from_grid(GridTessellation::new(surface, &grid)),
+                None => {
+                    let options = tessellation.and_then(|t| t.surface_options());
+                    NurbsSurfaceMesh::from(surface.tessellate(options))
+                }
+            };
+            let mesh = with_surface_settings(mesh, surface, tessellation, colors, displacement);
+            let repair = tessellation.map(|t| t.repair).unwrap_or_default();
+            let result = match handle {
+                Some(handle) => mesh.try_update_surface_triangle_list(&mut meshes, handle, &repair),
+                None => {
+                    let mut m = Mesh::new(PrimitiveTopology::TriangleList, Default::default());
+                    mesh.try_write_surface_triangle_list(&mut m, &repair)
+                        .inspect(|_| {
+                            commands.entity(e).insert(meshes.add(m));
+                        })
+                }
+            };
+            match result {
+                Ok(validation) => update_scalar_legend(&mut commands, e, validation.legend),
+                Err(err) => warn!("failed to mesh the surface of {:?}: {}", e, err),
+            }
+        },
+    );
+}
+
+/// Inserts the legend of the scalar vertex colors written to the mesh of the entity, or removes a stale one
+pub(crate) fn update_scalar_legend(
+    commands: &mut Commands,
+    entity: Entity,
+    legend: Option<ScalarFieldLegend>,
+) {
+    match legend {
+        Some(legend) => commands.entity(entity).insert(NurbsScalarLegend(legend)),
+        None => commands.entity(entity).remove::<NurbsScalarLegend>(),
+    };
+}
+
+/// Applies the tessellation settings, vertex colors and displacement of an entity to the mesh generator of its surface
+pub(crate) fn with_surface_settings<T: FloatingPoint>(
+    mut mesh: NurbsSurfaceMesh<T, Const<4>>,
+    surface: &ControlNet<T, Const<4>>,
+    tessellation: Option<&NurbsTessellation<T>>,
+    colors: Option<&NurbsVertexColors<T>>,
+    displacement: Option<&NurbsDisplacement<T>>,
+) -> NurbsSurfaceMesh<T, Const<4>> {
+    if let Some(optimization) = tessellation.and_then(|t| t.optimization) {
+        mesh = mesh.with_optimization(optimization);
+    }
+    if let Some(tolerance) = tessellation.and_then(|t| t.seam_tolerance) {
+        mesh = mesh.with_seams(surface, tolerance);
+    }
+    if tessellation.is_some_and(|t| t.creases) {
+        mesh = mesh.with_creases(surface);
+    }
+    if tessellation.is_some_and(|t| t.knot_spans) {
+        mesh = mesh.with_knot_spans(surface);
+    }
+    if tessellation.is_some_and(|t| t.flat_shading) {
+        mesh = mesh.with_flat_shading(true);
+    }
+    if let Some(colors) = colors {
+        mesh = mesh.with_vertex_colors(colors.0.clone());
+    }
+    if let Some(displacement) = displacement {
+        mesh = mesh.with_displacement(surface, displacement.0.clone());
+    }
+    mesh
+}
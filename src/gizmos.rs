@@ -0,0 +1,309 @@
+use std::sync::{Mutex, OnceLock};
+
+use bevy::{
+    app::{App, Plugin},
+    gizmos::{config::GizmoConfigGroup, gizmos::Gizmos, AppGizmoBuilder},
+    math::Vec3,
+    reflect::Reflect,
+    render::color::Color,
+    utils::HashMap,
+};
+use curvo::prelude::{FloatingPoint, NurbsCurve, NurbsSurface};
+use nalgebra::{allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, U1};
+
+use crate::{
+    geometry::to_vec3,
+    prelude::{sample_curvature, CurvatureComb, CurvatureCombOptions},
+};
+
+/// Gizmo config group holding the settings used to draw NURBS geometries
+///
+/// Gizmos of other config groups draw NURBS geometries with the default settings.
+#[derive(Reflect, GizmoConfigGroup, Debug, Clone)]
+pub struct NurbsGizmoConfigGroup {
+    /// The tolerance of the curve tessellation
+    pub tolerance: f32,
+    /// The number of isocurves drawn in each direction of a surface
+    pub isocurves: usize,
+    /// The number of samples along each isocurve
+    pub isocurve_samples: usize,
+    /// The number of samples of a curvature comb
+    pub comb_samples: usize,
+    /// The length of a comb spike per unit curvature
+    pub comb_scale: f32,
+    /// The number of frames drawn along a curve
+    pub frame_count: usize,
+    /// The length of the frame axes
+    pub frame_size: f32,
+}
+
+impl Default for NurbsGizmoConfigGroup {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-3,
+            isocurves: 8,
+            isocurve_samples: 32,
+            comb_samples: 64,
+            comb_scale: 1.,
+            frame_count: 8,
+            frame_size: 0.1,
+        }
+    }
+}
+
+/// Plugin registering the NURBS gizmo config group
+#[derive(Default)]
+pub struct NurbsGizmosPlugin;
+
+impl Plugin for NurbsGizmosPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_gizmo_group::<NurbsGizmoConfigGroup>();
+    }
+}
+
+/// Extension of `Gizmos` of any config group to draw NURBS geometries
+///
+/// The settings are taken from `Gizmos<NurbsGizmoConfigGroup>`, and other config groups use the default settings.
+/// The methods accept 2D and 3D geometries in both f32 and f64.
+pub trait NurbsGizmosExt {
+    /// Draws the tessellated curve
+    ///
+    /// The tessellation is cached by the control points, knots and tolerance,
+    /// so a curve drawn every frame is only tessellated again after it changes.
+    fn nurbs_curve<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>, color: Color)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>;
+
+    /// Draws isocurves of the surface at uniform parameters in both directions
+    fn nurbs_surface_isocurves<T: FloatingPoint, D>(
+        &mut self,
+        surface: &NurbsSurface<T, D>,
+        color: Color,
+    ) where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>;
+
+    /// Draws the polygon connecting the dehomogenized control points of the curve
+    fn control_polygon<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>, color: Color)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>;
+
+    /// Draws spikes opposite to the curvature vector with the envelope connecting their tips
+    fn curvature_comb<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>, color: Color)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>;
+
+    /// Draws the tangent, normal and binormal of the frenet frames in red, green and blue
+    fn frames<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>;
+}
+
+impl<G: GizmoConfigGroup> NurbsGizmosExt for Gizmos<'_, '_, G> {
+    fn nurbs_curve<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>, color: Color)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let tolerance = nurbs_settings(self).tolerance;
+        let points = cached_tessellation(curve, tolerance);
+        self.linestrip(points, color);
+    }
+
+    fn nurbs_surface_isocurves<T: FloatingPoint, D>(
+        &mut self,
+        surface: &NurbsSurface<T, D>,
+        color: Color,
+    ) where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+        let settings = nurbs_settings(self);
+        let lines = settings.isocurves.max(2);
+        let samples = settings.isocurve_samples.max(2);
+        let at = |a: T, b: T, i: usize, n: usize| {
+            a + (b - a) * T::from_usize(i).unwrap() / T::from_usize(n - 1).unwrap()
+        };
+        for i in 0..lines {
+            let u = at(u0, u1, i, lines);
+            let points: Vec<_> = (0..samples)
+                .map(|j| {
+                    to_vec3(
+                        surface
+                            .point_at(u, at(v0, v1, j, samples))
+                            .coords
+                            .as_slice(),
+                    )
+                })
+                .collect();
+            self.linestrip(points, color);
+
+            let v = at(v0, v1, i, lines);
+            let points: Vec<_> = (0..samples)
+                .map(|j| {
+                    to_vec3(
+                        surface
+                            .point_at(at(u0, u1, j, samples), v)
+                            .coords
+                            .as_slice(),
+                    )
+                })
+                .collect();
+            self.linestrip(points, color);
+        }
+    }
+
+    fn control_polygon<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>, color: Color)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let points: Vec<_> = curve
+            .control_points()
+            .iter()
+            .map(|p| {
+                let coords = p.coords.as_slice();
+                let (w, xyz) = coords.split_last().unwrap();
+                to_vec3(&xyz.iter().map(|c| *c / *w).collect::<Vec<_>>())
+            })
+            .collect();
+        self.linestrip(points, color);
+    }
+
+    fn curvature_comb<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>, color: Color)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let settings = nurbs_settings(self);
+        let comb = CurvatureComb::new(
+            curve,
+            &CurvatureCombOptions {
+                samples: settings.comb_samples,
+                scale: settings.comb_scale,
+            },
+        );
+        comb.samples()
+            .iter()
+            .zip(comb.tips())
+            .for_each(|(s, tip)| self.line(s.point, tip, color));
+        self.linestrip(comb.tips(), color);
+    }
+
+    fn frames<T: FloatingPoint, D>(&mut self, curve: &NurbsCurve<T, D>)
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let settings = nurbs_settings(self);
+        let size = settings.frame_size;
+        let mut normal = None;
+        sample_curvature(curve, settings.frame_count)
+            .into_iter()
+            .for_each(|s| {
+                // keep the previous normal where the curvature vanishes
+                let n = s
+                    .vector
+                    .try_normalize()
+                    .or(normal)
+                    .unwrap_or_else(|| s.tangent.any_orthonormal_vector());
+                normal = Some(n);
+                self.ray(s.point, s.tangent * size, Color::RED);
+                self.ray(s.point, n * size, Color::GREEN);
+                self.ray(s.point, s.tangent.cross(n) * size, Color::BLUE);
+            });
+    }
+}
+
+/// Returns the settings of the config group if it is `NurbsGizmoConfigGroup`, otherwise the default settings
+fn nurbs_settings<G: GizmoConfigGroup>(gizmos: &Gizmos<'_, '_, G>) -> NurbsGizmoConfigGroup {
+    gizmos
+        .config_ext
+        .as_any()
+        .downcast_ref::<NurbsGizmoConfigGroup>()
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// The number of curve tessellations kept by `nurbs_curve`
+const TESSELLATION_CACHE_CAPACITY: usize = 256;
+
+/// Tessellations of recently drawn curves keyed by the bits of their degree, control points, knots and tolerance
+#[derive(Default)]
+struct TessellationCache {
+    /// The tessellated points and the time of their last use
+    entries: HashMap<Vec<u64>, (Vec<Vec3>, u64)>,
+    time: u64,
+}
+
+/// Returns the tessellation of the curve from the cache, tessellating it on a miss
+///
+/// The least recently used entry is evicted when the cache is full.
+fn cached_tessellation<T: FloatingPoint, D>(curve: &NurbsCurve<T, D>, tolerance: f32) -> Vec<Vec3>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    static CACHE: OnceLock<Mutex<TessellationCache>> = OnceLock::new();
+
+    let bits = |v: &T| v.to_f64().unwrap().to_bits();
+    let key: Vec<u64> = [
+        D::dim() as u64,
+        curve.degree() as u64,
+        tolerance.to_bits() as u64,
+    ]
+    .into_iter()
+    .chain(
+        curve
+            .control_points()
+            .iter()
+            .flat_map(|p| p.coords.iter().map(bits).collect::<Vec<_>>()),
+    )
+    .chain(curve.knots().as_slice().iter().map(bits))
+    .collect();
+
+    let mut cache = CACHE
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    cache.time += 1;
+    let time = cache.time;
+    if let Some((points, used)) = cache.entries.get_mut(&key) {
+        *used = time;
+        return points.clone();
+    }
+
+    let points: Vec<_> = curve
+        .tessellate(T::from_f32(tolerance))
+        .iter()
+        .map(|p| to_vec3(p.coords.as_slice()))
+        .collect();
+    if cache.entries.len() >= TESSELLATION_CACHE_CAPACITY {
+        let oldest = cache
+            .entries
+            .iter()
+            .min_by_key(|(_, (_, used))| *used)
+            .map(|(k, _)| k.clone());
+        if let Some(oldest) = oldest {
+            cache.entries.remove(&oldest);
+        }
+    }
+    cache.entries.insert(key, (points.clone(), time));
+    points
+}
@@ -0,0 +1,256 @@
+use curvo::prelude::FloatingPoint;
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, OPoint, OVector,
+    Vector2, U1,
+};
+
+use crate::{prelude::ControlNet, surface_seams::tessellation_normal};
+
+/// Parameter values of the grid lattice along a direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GridDivisions {
+    /// Divides the domain into the number of equal segments
+    Uniform(usize),
+    /// Divides each non-empty knot span into the number of equal segments, so that knots lie on the lattice
+    PerKnotSpan(usize),
+}
+
+impl Default for GridDivisions {
+    fn default() -> Self {
+        Self::Uniform(16)
+    }
+}
+
+impl GridDivisions {
+    /// Returns the increasing parameter values of the lattice over the knot vector
+    pub fn parameters<T: FloatingPoint>(&self, knots: &[T], degree: usize) -> Vec<T> {
+        let (start, end) = (knots[degree], knots[knots.len() - degree - 1]);
+        let spans = match self {
+            GridDivisions::Uniform(_) => vec![(start, end)],
+            GridDivisions::PerKnotSpan(_) => knots[degree..knots.len() - degree]
+                .windows(2)
+                .filter(|w| w[0] < w[1])
+                .map(|w| (w[0], w[1]))
+                .collect(),
+        };
+        let segments = match self {
+            GridDivisions::Uniform(n) | GridDivisions::PerKnotSpan(n) => (*n).max(1),
+        };
+        let n = T::from_usize(segments).unwrap();
+        spans
+            .iter()
+            .flat_map(|(a, b)| {
+                (0..segments).map(move |i| *a + (*b - *a) * T::from_usize(i).unwrap() / n)
+            })
+            .chain(std::iter::once(end))
+            .collect()
+    }
+}
+
+/// Diagonal splitting each quad of the lattice into two triangles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum GridDiagonal {
+    /// Every quad is split along the diagonal from its lowest to its highest parameters
+    #[default]
+    Forward,
+    /// The diagonals alternate in a checkerboard pattern
+    Alternating,
+    /// Each quad is split along its shorter diagonal, which makes the triangles depend on the shape
+    Shortest,
+}
+
+/// Options of the grid tessellation
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GridTessellationOptions {
+    pub u_divisions: GridDivisions,
+    pub v_divisions: GridDivisions,
+    pub diagonal: GridDiagonal,
+}
+
+/// Tessellation of a surface into a structured lattice of vertices
+///
+/// The vertex at row `i` and column `j` has the index `i * columns + j`, where rows follow u and columns follow v.
+/// The topology depends only on the options and the knot spans, not on the shape of the surface,
+/// unless the shortest diagonal is chosen.
+#[derive(Debug, Clone)]
+pub struct GridTessellation<T: FloatingPoint, D: DimName>
+where
+    D: DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    points: Vec<OPoint<T, DimNameDiff<D, U1>>>,
+    normals: Vec<OVector<T, DimNameDiff<D, U1>>>,
+    uvs: Vec<Vector2<T>>,
+    faces: Vec<[usize; 3]>,
+    rows: usize,
+    columns: usize,
+}
+
+impl<T: FloatingPoint, D: DimName> GridTessellation<T, D>
+where
+    D: DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    /// Evaluates the surface on the lattice of the options
+    ///
+    /// The normals are oriented like the normals of curvo's adaptive tessellation.
+    pub fn new(surface: &ControlNet<T, D>, options: &GridTessellationOptions) -> Self {
+        let us = options
+            .u_divisions
+            .parameters(surface.u_knots(), surface.u_degree());
+        let vs = options
+            .v_divisions
+            .parameters(surface.v_knots(), surface.v_degree());
+        let (rows, columns) = (us.len(), vs.len());
+
+        let uvs: Vec<_> = us
+            .iter()
+            .flat_map(|u| vs.iter().map(move |v| Vector2::new(*u, *v)))
+            .collect();
+        let points: Vec<_> = uvs.iter().map(|uv| surface.point_at(uv.x, uv.y)).collect();
+        let normals = uvs
+            .iter()
+            .map(|uv| {
+                let n = tessellation_normal(surface, uv.x, uv.y);
+                let n = n.try_normalize(T::default_epsilon()).unwrap_or(n);
+                OVector::<T, DimNameDiff<D, U1>>::from_fn(|i, _| {
+                    n.as_slice().get(i).copied().unwrap_or(T::zero())
+                })
+            })
+            .collect();
+
+        let index = |i: usize, j: usize| i * columns + j;
+        let faces = (0..rows - 1)
+            .flat_map(|i| (0..columns - 1).map(move |j| (i, j)))
+            .flat_map(|(i, j)| {
+                let (a, b, c, d) = (
+                    index(i, j),
+                    index(i + 1, j),
+                    index(i + 1, j + 1),
+                    index(i, j + 1),
+                );
+                let forward = match options.diagonal {
+                    GridDiagonal::Forward => true,
+                    GridDiagonal::Alternating => (i + j) % 2 == 0,
+                    GridDiagonal::Shortest => {
+                        (&points[c] - &points[a]).norm_squared()
+                            <= (&points[d] - &points[b]).norm_squared()
+                    }
+                };
+                if forward {
+                    [[a, b, c], [a, c, d]]
+                } else {
+                    [[a, b, d], [b, c, d]]
+                }
+            })
+            .collect();
+
+        Self {
+            points,
+            normals,
+            uvs,
+            faces,
+            rows,
+            columns,
+        }
+    }
+
+    pub fn points(&self) -> &Vec<OPoint<T, DimNameDiff<D, U1>>> {
+        &self.points
+    }
+
+    pub fn normals(&self) -> &Vec<OVector<T, DimNameDiff<D, U1>>> {
+        &self.normals
+    }
+
+    pub fn uvs(&self) -> &Vec<Vector2<T>> {
+        &self.uvs
+    }
+
+    pub fn faces(&self) -> &Vec<[usize; 3]> {
+        &self.faces
+    }
+
+    /// Returns the number of vertices along u
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the number of vertices along v
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Returns the index of the vertex at the row and column
+    pub fn index(&self, row: usize, column: usize) -> usize {
+        row * self.columns + column
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::{Const, Point4, Vector3};
+
+    use super::*;
+
+    /// Extrusion of a quadratic profile through the points with two knot spans
+    fn extrusion(points: [[f64; 2]; 4]) -> ControlNet<f64, Const<4>> {
+        let profile = NurbsCurve3D::try_new(
+            2,
+            points
+                .iter()
+                .map(|[x, y]| Point4::new(*x, *y, 0., 1.))
+                .collect(),
+            vec![0., 0., 0., 0.5, 1., 1., 1.],
+        )
+        .unwrap();
+        ControlNet::extrude(&profile, Vector3::z())
+    }
+
+    #[test]
+    fn keeps_lattice_of_different_shapes() {
+        let options = GridTessellationOptions {
+            u_divisions: GridDivisions::PerKnotSpan(3),
+            v_divisions: GridDivisions::Uniform(4),
+            diagonal: GridDiagonal::Alternating,
+        };
+        let a = GridTessellation::new(
+            &extrusion([[0., 0.], [1., 0.], [2., 0.], [3., 0.]]),
+            &options,
+        );
+        let b = GridTessellation::new(
+            &extrusion([[0., 0.], [1., 2.], [2., -1.], [3., 1.]]),
+            &options,
+        );
+        assert_eq!(a.faces(), b.faces());
+        assert_eq!(a.uvs(), b.uvs());
+        assert_eq!(a.points().len(), a.rows() * a.columns());
+        assert_eq!(a.uvs()[a.index(1, 2)], a.uvs()[a.columns() + 2]);
+        assert_ne!(a.points(), b.points());
+    }
+
+    #[test]
+    fn orients_normals_like_adaptive_tessellation() {
+        let surface = extrusion([[0., 0.], [1., 2.], [2., -1.], [3., 1.]]);
+        let grid = GridTessellation::new(&surface, &GridTessellationOptions::default());
+        let adaptive = surface.tessellate(None);
+        let corners = adaptive
+            .uvs()
+            .iter()
+            .zip(adaptive.normals())
+            .filter_map(|(uv, normal)| {
+                grid.uvs()
+                    .iter()
+                    .position(|g| (g - Vector2::new(uv[0], uv[1])).norm() < 1e-9)
+                    .map(|i| (i, normal))
+            })
+            .collect::<Vec<_>>();
+        assert!(!corners.is_empty());
+        assert!(corners
+            .iter()
+            .all(|(i, normal)| grid.normals()[*i].dot(normal) > 0.));
+    }
+}
@@ -0,0 +1,817 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{Assets, Handle},
+    ecs::{
+        change_detection::{DetectChanges, Ref},
+        component::Component,
+        entity::Entity,
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    math::Vec3,
+    render::{
+        mesh::{Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    transform::{components::GlobalTransform, TransformSystem},
+};
+use curvo::prelude::{
+    AdaptiveTessellationOptions, FloatingPoint, NurbsCurve3D, NurbsSurface3D, SurfaceTessellation,
+};
+use nalgebra::{Const, Matrix2, Matrix3, Matrix4, Point3, Point4, Vector2, Vector3};
+
+use crate::{
+    derivatives::curve_derivatives,
+    geometry::to_vec3,
+    mesh_buffer::write_attribute,
+    prelude::{ControlNet, NurbsCurveGeometry, NurbsSurfaceGeometry},
+    surface_raycast::intersect_ray_triangle,
+};
+
+/// Options for the intersections between NURBS geometries
+#[derive(Debug, Clone, Copy)]
+pub struct IntersectionOptions<T: FloatingPoint> {
+    /// The normal tolerance of the tessellations used to seed the intersections
+    pub norm_tolerance: T,
+    /// The distance between the geometries below which a point is considered on both,
+    /// relative to the magnitude of their coordinates and never tighter than the floating point precision at that magnitude.
+    /// It also bounds the parameter steps of the projections onto the surfaces
+    pub tolerance: T,
+    /// The length of the marching steps along surface-surface intersections
+    pub step: T,
+    /// The maximum number of newton iterations per point
+    pub max_iterations: usize,
+    /// The maximum number of marching steps in each direction of an intersection curve
+    pub max_steps: usize,
+    /// The number of curve segments sampled to seed curve-surface intersections
+    pub curve_samples: usize,
+}
+
+impl<T: FloatingPoint> IntersectionOptions<T> {
+    /// Returns the distance tolerance for geometries whose coordinates reach the magnitude
+    fn distance_tolerance(&self, magnitude: T) -> T {
+        let scale = magnitude.max(T::one());
+        let precision = T::default_epsilon() * T::from_f64(64.).unwrap() * scale;
+        (self.tolerance * scale).max(precision)
+    }
+}
+
+impl<T: FloatingPoint> Default for IntersectionOptions<T> {
+    fn default() -> Self {
+        Self {
+            norm_tolerance: T::from_f64(1e-1).unwrap(),
+            tolerance: T::from_f64(1e-6).unwrap(),
+            step: T::from_f64(5e-2).unwrap(),
+            max_iterations: 16,
+            max_steps: 4096,
+            curve_samples: 64,
+        }
+    }
+}
+
+/// Point on the intersection of two surfaces
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceIntersectionPoint<T: FloatingPoint> {
+    /// The intersection point
+    pub point: Point3<T>,
+    /// The (u, v) parameter of the first surface at the point
+    pub uv_a: Vector2<T>,
+    /// The (u, v) parameter of the second surface at the point
+    pub uv_b: Vector2<T>,
+}
+
+/// Polyline traced along the intersection of two surfaces
+#[derive(Debug, Clone)]
+pub struct SurfaceIntersection<T: FloatingPoint> {
+    /// The points of the polyline in marching order
+    pub points: Vec<SurfaceIntersectionPoint<T>>,
+    /// Whether the intersection forms a closed loop
+    pub closed: bool,
+}
+
+impl<T: FloatingPoint> SurfaceIntersection<T> {
+    /// Returns the positions of the polyline, repeating the first point if closed
+    pub fn polyline(&self) -> Vec<Point3<T>> {
+        let mut points: Vec<_> = self.points.iter().map(|p| p.point).collect();
+        if self.closed {
+            points.extend(points.first().copied());
+        }
+        points
+    }
+
+    /// Fits a NURBS curve interpolating the polyline
+    /// * `degree` - The degree of the curve, lowered if the polyline has too few points
+    pub fn try_fit(&self, degree: usize) -> anyhow::Result<NurbsCurve3D<T>> {
+        let points = self.polyline();
+        if points.len() < 2 {
+            anyhow::bail!("intersection has less than 2 points");
+        }
+        NurbsCurve3D::try_interpolate(&points, degree.min(points.len() - 1), None, None)
+    }
+}
+
+/// Intersection of a curve with a surface
+#[derive(Debug, Clone, Copy)]
+pub struct CurveSurfaceIntersection<T: FloatingPoint> {
+    /// The intersection point
+    pub point: Point3<T>,
+    /// The parameter of the curve at the point
+    pub parameter: T,
+    /// The (u, v) parameter of the surface at the point
+    pub uv: Vector2<T>,
+}
+
+/// Intersects two NURBS surfaces
+///
+/// Crossings of the tessellation triangles seed the intersection, which is refined onto both surfaces
+/// and marched in both directions along the cross product of their normals.
+pub fn intersect_surfaces<T: FloatingPoint>(
+    a: &NurbsSurface3D<T>,
+    b: &NurbsSurface3D<T>,
+    options: &IntersectionOptions<T>,
+) -> Vec<SurfaceIntersection<T>> {
+    let tessellation_options = || {
+        Some(AdaptiveTessellationOptions {
+            norm_tolerance: options.norm_tolerance,
+            ..Default::default()
+        })
+    };
+    let ta = a.tessellate(tessellation_options());
+    let tb = b.tessellate(tessellation_options());
+    let tolerance = options.distance_tolerance(magnitude(ta.points().iter().chain(tb.points())));
+
+    let mut seeds = triangle_seeds(&ta, &tb);
+    let mut intersections: Vec<SurfaceIntersection<T>> = vec![];
+    let prune = options.step + options.step;
+
+    while let Some(seed) = seeds.pop() {
+        let Some(start) =
+            refine_surface_surface(a, b, &seed.point, seed.uv_a, seed.uv_b, tolerance, options)
+        else {
+            continue;
+        };
+        let traced = intersections
+            .iter()
+            .flat_map(|i| i.points.iter())
+            .any(|p| (p.point - start.point).norm() < options.step);
+        if traced {
+            continue;
+        }
+
+        let (forward, closed) = march(a, b, &start, T::one(), tolerance, options);
+        let points: Vec<_> = if closed {
+            std::iter::once(start).chain(forward).collect()
+        } else {
+            let (backward, _) = march(a, b, &start, -T::one(), tolerance, options);
+            backward
+                .into_iter()
+                .rev()
+                .chain(std::iter::once(start))
+                .chain(forward)
+                .collect()
+        };
+
+        seeds.retain(|s| points.iter().all(|p| (p.point - s.point).norm() > prune));
+        if points.len() >= 2 {
+            intersections.push(SurfaceIntersection { points, closed });
+        }
+    }
+
+    intersections
+}
+
+/// Intersects a NURBS curve with a NURBS surface
+///
+/// Crossings of the sampled curve with the tessellation triangles are refined onto the exact geometries by newton iteration.
+/// The intersections are sorted by the curve parameter.
+pub fn intersect_curve_surface<T: FloatingPoint>(
+    curve: &NurbsCurve3D<T>,
+    surface: &NurbsSurface3D<T>,
+    options: &IntersectionOptions<T>,
+) -> Vec<CurveSurfaceIntersection<T>> {
+    let tessellation = surface.tessellate(Some(AdaptiveTessellationOptions {
+        norm_tolerance: options.norm_tolerance,
+        ..Default::default()
+    }));
+    let points = tessellation.points();
+    let uvs = tessellation.uvs();
+
+    let (t0, t1) = curve.knots_domain();
+    let n = options.curve_samples.max(1);
+    let parameters: Vec<_> = (0..=n)
+        .map(|i| t0 + (t1 - t0) * T::from_usize(i).unwrap() / T::from_usize(n).unwrap())
+        .collect();
+    let samples: Vec<_> = parameters.iter().map(|t| curve.point_at(*t)).collect();
+    let tolerance = options.distance_tolerance(magnitude(points.iter().chain(samples.iter())));
+    let duplicate = options.step * T::from_f64(1e-2).unwrap();
+
+    let mut hits: Vec<CurveSurfaceIntersection<T>> = vec![];
+    for i in 0..n {
+        let direction = samples[i + 1] - samples[i];
+        for f in tessellation.faces() {
+            let Some((s, b1, b2)) = intersect_ray_triangle(
+                &samples[i],
+                &direction,
+                &points[f[0]],
+                &points[f[1]],
+                &points[f[2]],
+            ) else {
+                continue;
+            };
+            if s > T::one() {
+                continue;
+            }
+            let parameter = parameters[i] + (parameters[i + 1] - parameters[i]) * s;
+            let uv = uvs[f[0]] * (T::one() - b1 - b2) + uvs[f[1]] * b1 + uvs[f[2]] * b2;
+            let Some(hit) = refine_curve_surface(curve, surface, parameter, uv, tolerance, options)
+            else {
+                continue;
+            };
+            if hits
+                .iter()
+                .all(|h| (h.point - hit.point).norm() > duplicate)
+            {
+                hits.push(hit);
+            }
+        }
+    }
+
+    hits.sort_by(|a, b| {
+        a.parameter
+            .partial_cmp(&b.parameter)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    hits
+}
+
+/// Finds points where the edges of each tessellation cross the triangles of the other
+fn triangle_seeds<T: FloatingPoint>(
+    a: &SurfaceTessellation<T, Const<4>>,
+    b: &SurfaceTessellation<T, Const<4>>,
+) -> Vec<SurfaceIntersectionPoint<T>> {
+    let (min_a, max_a) = bounds(a.points().iter());
+    let (min_b, max_b) = bounds(b.points().iter());
+    let candidates = |t: &SurfaceTessellation<T, Const<4>>, min: &Point3<T>, max: &Point3<T>| {
+        let points = t.points();
+        t.faces()
+            .iter()
+            .filter(|f| {
+                let (lo, hi) = bounds(f.iter().map(|i| &points[*i]));
+                overlaps(&lo, &hi, min, max)
+            })
+            .copied()
+            .collect::<Vec<_>>()
+    };
+    let faces_a = candidates(a, &min_b, &max_b);
+    let faces_b = candidates(b, &min_a, &max_a);
+
+    let mut seeds = vec![];
+    for fa in faces_a.iter() {
+        let (lo_a, hi_a) = bounds(fa.iter().map(|i| &a.points()[*i]));
+        for fb in faces_b.iter() {
+            let (lo_b, hi_b) = bounds(fb.iter().map(|i| &b.points()[*i]));
+            if !overlaps(&lo_a, &hi_a, &lo_b, &hi_b) {
+                continue;
+            }
+            seeds.extend(
+                edge_crossings(a, fa, b, fb)
+                    .into_iter()
+                    .map(|(point, uv_a, uv_b)| SurfaceIntersectionPoint { point, uv_a, uv_b }),
+            );
+            seeds.extend(
+                edge_crossings(b, fb, a, fa)
+                    .into_iter()
+                    .map(|(point, uv_b, uv_a)| SurfaceIntersectionPoint { point, uv_a, uv_b }),
+            );
+        }
+    }
+    seeds
+}
+
+/// Crossings of the edges of the triangle `fa` with the triangle `fb`
+fn edge_crossings<T: FloatingPoint>(
+    a: &SurfaceTessellation<T, Const<4>>,
+    fa: &[usize; 3],
+    b: &SurfaceTessellation<T, Const<4>>,
+    fb: &[usize; 3],
+) -> Vec<(Point3<T>, Vector2<T>, Vector2<T>)> {
+    let (pa, ua) = (a.points(), a.uvs());
+    let (pb, ub) = (b.points(), b.uvs());
+    (0..3)
+        .filter_map(|i| {
+            let (i0, i1) = (fa[i], fa[(i + 1) % 3]);
+            let direction = pa[i1] - pa[i0];
+            let (s, b1, b2) =
+                intersect_ray_triangle(&pa[i0], &direction, &pb[fb[0]], &pb[fb[1]], &pb[fb[2]])?;
+            (s <= T::one()).then(|| {
+                (
+                    pa[i0] + direction * s,
+                    ua[i0] + (ua[i1] - ua[i0]) * s,
+                    ub[fb[0]] * (T::one() - b1 - b2) + ub[fb[1]] * b1 + ub[fb[2]] * b2,
+                )
+            })
+        })
+        .collect()
+}
+
+fn bounds<'a, T: FloatingPoint>(
+    points: impl Iterator<Item = &'a Point3<T>>,
+) -> (Point3<T>, Point3<T>) {
+    points.fold(
+        (
+            Point3::from(Vector3::repeat(T::from_f32(f32::MAX).unwrap())),
+            Point3::from(Vector3::repeat(T::from_f32(f32::MIN).unwrap())),
+        ),
+        |(lo, hi), p| (lo.inf(p), hi.sup(p)),
+    )
+}
+
+/// Returns the largest absolute coordinate of the points
+fn magnitude<'a, T: FloatingPoint>(points: impl Iterator<Item = &'a Point3<T>>) -> T {
+    points.fold(T::zero(), |m, p| m.max(p.coords.amax()))
+}
+
+fn overlaps<T: FloatingPoint>(
+    lo_a: &Point3<T>,
+    hi_a: &Point3<T>,
+    lo_b: &Point3<T>,
+    hi_b: &Point3<T>,
+) -> bool {
+    (0..3).all(|i| lo_a[i] <= hi_b[i] && lo_b[i] <= hi_a[i])
+}
+
+/// Finds the (u, v) parameter of the closest point on the surface by gauss-newton iteration
+fn project_onto_surface<T: FloatingPoint>(
+    surface: &NurbsSurface3D<T>,
+    point: &Point3<T>,
+    uv: Vector2<T>,
+    options: &IntersectionOptions<T>,
+) -> Vector2<T> {
+    let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+    let (mut u, mut v) = (uv.x, uv.y);
+    for _ in 0..options.max_iterations {
+        let derivs = surface.rational_derivatives(u, v, 1);
+        let f = derivs[0][0] - point.coords;
+        let (su, sv) = (derivs[1][0], derivs[0][1]);
+        let m = Matrix2::new(su.dot(&su), su.dot(&sv), su.dot(&sv), sv.dot(&sv));
+        let Some(delta) = m.lu().solve(&Vector2::new(-su.dot(&f), -sv.dot(&f))) else {
+            break;
+        };
+        u = nalgebra::clamp(u + delta.x, u0, u1);
+        v = nalgebra::clamp(v + delta.y, v0, v1);
+        if delta.norm() < options.tolerance {
+            break;
+        }
+    }
+    Vector2::new(u, v)
+}
+
+/// Moves the point onto the intersection of two surfaces
+///
+/// The point is projected onto both surfaces and moved to the intersection of their tangent planes
+/// and the plane normal to the intersection through the point.
+/// * `tolerance` - The distance between the surfaces below which the point is accepted
+fn refine_surface_surface<T: FloatingPoint>(
+    a: &NurbsSurface3D<T>,
+    b: &NurbsSurface3D<T>,
+    point: &Point3<T>,
+    uv_a: Vector2<T>,
+    uv_b: Vector2<T>,
+    tolerance: T,
+    options: &IntersectionOptions<T>,
+) -> Option<SurfaceIntersectionPoint<T>> {
+    let eps = T::default_epsilon();
+    let (mut p, mut uv_a, mut uv_b) = (*point, uv_a, uv_b);
+    for _ in 0..options.max_iterations {
+        uv_a = project_onto_surface(a, &p, uv_a, options);
+        uv_b = project_onto_surface(b, &p, uv_b, options);
+        let (pa, pb) = (a.point_at(uv_a.x, uv_a.y), b.point_at(uv_b.x, uv_b.y));
+        if (pa - pb).norm() < tolerance {
+            return Some(SurfaceIntersectionPoint {
+                point: nalgebra::center(&pa, &pb),
+                uv_a,
+                uv_b,
+            });
+        }
+        let na = a.normal_at(uv_a.x, uv_a.y).try_normalize(eps)?;
+        let nb = b.normal_at(uv_b.x, uv_b.y).try_normalize(eps)?;
+        let tangent = na.cross(&nb);
+        let m = Matrix3::from_rows(&[na.transpose(), nb.transpose(), tangent.transpose()]);
+        let rhs = Vector3::new(
+            na.dot(&pa.coords),
+            nb.dot(&pb.coords),
+            tangent.dot(&p.coords),
+        );
+        p = Point3::from(m.lu().solve(&rhs)?);
+    }
+    None
+}
+
+/// Marches along the intersection of two surfaces from the start point
+/// * `sign` - The orientation of the marching relative to the cross product of the normals
+///
+/// Returns the traced points excluding the start point and whether the marching returned to the start.
+fn march<T: FloatingPoint>(
+    a: &NurbsSurface3D<T>,
+    b: &NurbsSurface3D<T>,
+    start: &SurfaceIntersectionPoint<T>,
+    sign: T,
+    tolerance: T,
+    options: &IntersectionOptions<T>,
+) -> (Vec<SurfaceIntersectionPoint<T>>, bool) {
+    let eps = T::default_epsilon();
+    let stall = options.step * T::from_f64(1e-1).unwrap();
+    let mut points = vec![];
+    let mut current = *start;
+    let mut direction: Option<Vector3<T>> = None;
+
+    for i in 0..options.max_steps {
+        let na = a.normal_at(current.uv_a.x, current.uv_a.y);
+        let nb = b.normal_at(current.uv_b.x, current.uv_b.y);
+        let Some(mut tangent) = na.cross(&nb).try_normalize(eps) else {
+            break;
+        };
+        tangent = match direction {
+            Some(d) if d.dot(&tangent) < T::zero() => -tangent,
+            Some(_) => tangent,
+            None => tangent * sign,
+        };
+        direction = Some(tangent);
+
+        let predicted = current.point + tangent * options.step;
+        let Some(next) = refine_surface_surface(
+            a,
+            b,
+            &predicted,
+            current.uv_a,
+            current.uv_b,
+            tolerance,
+            options,
+        ) else {
+            break;
+        };
+        if (next.point - current.point).norm() < stall {
+            break;
+        }
+        if i > 1 && (next.point - start.point).norm() < options.step {
+            return (points, true);
+        }
+        points.push(next);
+        current = next;
+        if on_boundary(a, &next.uv_a) || on_boundary(b, &next.uv_b) {
+            break;
+        }
+    }
+    (points, false)
+}
+
+fn on_boundary<T: FloatingPoint>(surface: &NurbsSurface3D<T>, uv: &Vector2<T>) -> bool {
+    let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+    uv.x <= u0 || uv.x >= u1 || uv.y <= v0 || uv.y >= v1
+}
+
+/// Moves the curve parameter and the surface parameter onto the intersection by newton iteration
+/// * `tolerance` - The distance between the curve and the surface below which the point is accepted
+fn refine_curve_surface<T: FloatingPoint>(
+    curve: &NurbsCurve3D<T>,
+    surface: &NurbsSurface3D<T>,
+    parameter: T,
+    uv: Vector2<T>,
+    tolerance: T,
+    options: &IntersectionOptions<T>,
+) -> Option<CurveSurfaceIntersection<T>> {
+    let (t0, t1) = curve.knots_domain();
+    let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+    let (mut t, mut u, mut v) = (parameter, uv.x, uv.y);
+    for _ in 0..options.max_iterations {
+        let c = curve_derivatives(curve, t, 1);
+        let s = surface.rational_derivatives(u, v, 1);
+        let f = c[0] - s[0][0];
+        if f.norm() < tolerance {
+            return Some(CurveSurfaceIntersection {
+                point: surface.point_at(u, v),
+                parameter: t,
+                uv: Vector2::new(u, v),
+            });
+        }
+        let jacobian = Matrix3::from_columns(&[c[1], -s[1][0], -s[0][1]]);
+        let delta = jacobian.lu().solve(&(-f))?;
+        t = nalgebra::clamp(t + delta.x, t0, t1);
+        u = nalgebra::clamp(u + delta.y, u0, u1);
+        v = nalgebra::clamp(v + delta.z, v0, v1);
+    }
+    None
+}
+
+/// Component displaying the intersection of two geometry entities as a line list mesh
+///
+/// Either entity may hold a curve or a surface geometry. Curve-surface intersections are drawn as crosses.
+/// The lines are written in world space, so the entity holding this component should keep the identity transform.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct NurbsIntersectionDisplay {
+    /// The first geometry entity
+    pub a: Entity,
+    /// The second geometry entity
+    pub b: Entity,
+    /// The size of the crosses drawn at curve-surface intersections
+    pub marker_size: f32,
+}
+
+impl NurbsIntersectionDisplay {
+    pub fn new(a: Entity, b: Entity) -> Self {
+        Self {
+            a,
+            b,
+            marker_size: 0.05,
+        }
+    }
+}
+
+/// Intersections computed for a display in world space
+#[derive(Component, Debug, Clone)]
+pub struct NurbsIntersectionCurves<T: FloatingPoint = f32> {
+    /// The polylines of surface-surface intersections
+    pub polylines: Vec<SurfaceIntersection<T>>,
+    /// The curves fitted to the polylines
+    pub curves: Vec<NurbsCurve3D<T>>,
+    /// The points of curve-surface intersections
+    pub points: Vec<CurveSurfaceIntersection<T>>,
+}
+
+/// Settings for the intersection displays
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct NurbsIntersectionSettings<T: FloatingPoint = f32> {
+    /// The options of the intersection routines
+    pub options: IntersectionOptions<T>,
+    /// The degree of the curves fitted to intersection polylines
+    pub fit_degree: usize,
+}
+
+impl<T: FloatingPoint> Default for NurbsIntersectionSettings<T> {
+    fn default() -> Self {
+        Self {
+            options: Default::default(),
+            fit_degree: 3,
+        }
+    }
+}
+
+/// System set in which intersection displays are updated
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NurbsIntersectionSet;
+
+/// Plugin to keep intersection displays up to date with their geometry entities
+pub struct NurbsIntersectionPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for NurbsIntersectionPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for NurbsIntersectionPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NurbsIntersectionSettings<T>>()
+            .add_systems(
+                PostUpdate,
+                update_intersection_displays::<T>
+                    .in_set(NurbsIntersectionSet)
+                    .after(TransformSystem::TransformPropagate),
+            );
+    }
+}
+
+/// Recomputes the intersections of displays whose geometry entities changed or moved
+#[allow(clippy::type_complexity)]
+pub fn update_intersection_displays<T: FloatingPoint>(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    settings: Res<NurbsIntersectionSettings<T>>,
+    displays: Query<(Entity, Ref<NurbsIntersectionDisplay>, Option<&Handle<Mesh>>)>,
+    geometries: Query<(
+        Option<Ref<NurbsCurveGeometry<T>>>,
+        Option<Ref<NurbsSurfaceGeometry<T>>>,
+        Ref<GlobalTransform>,
+    )>,
+) {
+    displays.iter().for_each(|(e, display, handle)| {
+        let (Ok(a), Ok(b)) = (geometries.get(display.a), geometries.get(display.b)) else {
+            return;
+        };
+        let changed = |g: &(
+            Option<Ref<NurbsCurveGeometry<T>>>,
+            Option<Ref<NurbsSurfaceGeometry<T>>>,
+            Ref<GlobalTransform>,
+        )| {
+            g.0.as_ref().is_some_and(|c| c.is_changed())
+                || g.1.as_ref().is_some_and(|s| s.is_changed())
+                || g.2.is_changed()
+        };
+        if !(display.is_changed() || settings.is_changed() || changed(&a) || changed(&b)) {
+            return;
+        }
+
+        // intersect in the local space of the first entity, where the coordinates keep their precision,
+        // and move the results to world space afterwards
+        let world = to_matrix::<T>(&a.2);
+        let Some(local) = world.try_inverse() else {
+            return;
+        };
+        let (local_a, local_b) = (Matrix4::identity(), local * to_matrix::<T>(&b.2));
+        let options = &settings.options;
+        let surface = |g: &Option<Ref<NurbsSurfaceGeometry<T>>>, m: &Matrix4<T>| {
+            g.as_ref().and_then(|s| transform_surface(s, m))
+        };
+        let curve = |g: &Option<Ref<NurbsCurveGeometry<T>>>, m: &Matrix4<T>| {
+            g.as_ref().and_then(|c| transform_curve(c, m))
+        };
+
+        let mut result = NurbsIntersectionCurves {
+            polylines: vec![],
+            curves: vec![],
+            points: vec![],
+        };
+        match (
+            surface(&a.1, &local_a),
+            surface(&b.1, &local_b),
+            curve(&a.0, &local_a),
+            curve(&b.0, &local_b),
+        ) {
+            (Some(sa), Some(sb), _, _) => {
+                result.polylines = intersect_surfaces(&sa, &sb, options);
+                result.curves = result
+                    .polylines
+                    .iter()
+                    .filter_map(|p| p.try_fit(settings.fit_degree).ok())
+                    .filter_map(|c| transform_curve(&c, &world))
+                    .collect();
+                result
+                    .polylines
+                    .iter_mut()
+                    .flat_map(|p| p.points.iter_mut())
+                    .for_each(|p| p.point = world.transform_point(&p.point));
+            }
+            (Some(s), None, _, Some(c)) | (None, Some(s), Some(c), _) => {
+                result.points = intersect_curve_surface(&c, &s, options);
+                result
+                    .points
+                    .iter_mut()
+                    .for_each(|p| p.point = world.transform_point(&p.point));
+            }
+            _ => {}
+        }
+
+        let lines = intersection_lines(&result, display.marker_size);
+        match handle.and_then(|h| meshes.get_mut(h)) {
+            Some(existing) if existing.primitive_topology() == PrimitiveTopology::LineList => {
+                write_attribute(existing, Mesh::ATTRIBUTE_POSITION, lines);
+            }
+            _ => {
+                let mut mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::default());
+                write_attribute(&mut mesh, Mesh::ATTRIBUTE_POSITION, lines);
+                match handle {
+                    Some(handle) => {
+                        meshes.insert(handle, mesh);
+                    }
+                    None => {
+                        commands.entity(e).insert(meshes.add(mesh));
+                    }
+                }
+            }
+        }
+        commands.entity(e).insert(result);
+    });
+}
+
+/// Returns the line list vertices of the polylines and crosses at the points
+fn intersection_lines<T: FloatingPoint>(
+    intersections: &NurbsIntersectionCurves<T>,
+    marker_size: f32,
+) -> Vec<[f32; 3]> {
+    let segments = intersections.polylines.iter().flat_map(|p| {
+        p.polyline()
+            .windows(2)
+            .flat_map(|w| {
+                [
+                    to_vec3(w[0].coords.as_slice()),
+                    to_vec3(w[1].coords.as_slice()),
+                ]
+            })
+            .collect::<Vec<_>>()
+    });
+    let crosses = intersections.points.iter().flat_map(|p| {
+        let center = to_vec3(p.point.coords.as_slice());
+        [Vec3::X, Vec3::Y, Vec3::Z]
+            .into_iter()
+            .flat_map(move |axis| {
+                let offset = axis * marker_size * 0.5;
+                [center - offset, center + offset]
+            })
+    });
+    segments.chain(crosses).map(|v| v.to_array()).collect()
+}
+
+pub(crate) fn to_matrix<T: FloatingPoint>(transform: &GlobalTransform) -> Matrix4<T> {
+    let columns = transform.compute_matrix().to_cols_array();
+    Matrix4::from_iterator(columns.iter().map(|v| T::from_f32(*v).unwrap()))
+}
+
+pub(crate) fn transform_curve<T: FloatingPoint>(
+    curve: &NurbsCurve3D<T>,
+    matrix: &Matrix4<T>,
+) -> Option<NurbsCurve3D<T>> {
+    let control_points = curve
+        .control_points()
+        .iter()
+        .map(|p| Point4::from(matrix * p.coords))
+        .collect();
+    NurbsCurve3D::try_new(
+        curve.degree(),
+        control_points,
+        curve.knots().as_slice().to_vec(),
+    )
+    .ok()
+}
+
+pub(crate) fn transform_surface<T: FloatingPoint>(
+    surface: &ControlNet<T, Const<4>>,
+    matrix: &Matrix4<T>,
+) -> Option<ControlNet<T, Const<4>>> {
+    let control_points = surface
+        .control_points()
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|p| Point4::from(matrix * p.coords))
+                .collect()
+        })
+        .collect();
+    ControlNet::try_new(
+        surface.u_degree(),
+        surface.v_degree(),
+        surface.u_knots().to_vec(),
+        surface.v_knots().to_vec(),
+        control_points,
+    )
+    .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use curvo::prelude::NurbsSurface;
+    use nalgebra::Point4;
+
+    use super::*;
+
+    const OFFSET: f32 = 100.;
+
+    /// Wave profile along x extruded along z, offset from the origin
+    fn wave() -> NurbsSurface3D<f32> {
+        let points = [(0., 0.), (1., 0.5), (2., -0.5), (3., 0.)]
+            .map(|(x, y)| Point3::new(x + OFFSET, y + OFFSET, OFFSET));
+        let profile = NurbsCurve3D::try_interpolate(&points, 3, None, None).unwrap();
+        NurbsSurface::extrude(&profile, Vector3::z() * 2.)
+    }
+
+    fn line(a: Point3<f32>, b: Point3<f32>) -> NurbsCurve3D<f32> {
+        NurbsCurve3D::try_new(
+            1,
+            vec![
+                Point4::new(a.x, a.y, a.z, 1.),
+                Point4::new(b.x, b.y, b.z, 1.),
+            ],
+            vec![0., 0., 1., 1.],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn intersects_far_from_the_origin_in_f32() {
+        let wave = wave();
+        let x = OFFSET + 1.3;
+        let plane = NurbsSurface::extrude(
+            &line(
+                Point3::new(x, OFFSET - 1., OFFSET),
+                Point3::new(x, OFFSET + 1., OFFSET),
+            ),
+            Vector3::z() * 2.,
+        );
+        let options = IntersectionOptions::default();
+        let points: Vec<_> = intersect_surfaces(&wave, &plane, &options)
+            .iter()
+            .flat_map(|i| i.polyline())
+            .collect();
+        assert!(points.len() >= 2);
+        assert!(points.iter().all(|p| (p.x - x).abs() < 1e-3));
+
+        let curve = line(
+            Point3::new(x, OFFSET + 1., OFFSET + 1.),
+            Point3::new(x, OFFSET - 1., OFFSET + 1.),
+        );
+        let hits = intersect_curve_surface(&curve, &wave, &options);
+        assert_eq!(hits.len(), 1);
+        assert!((hits[0].point.x - x).abs() < 1e-3);
+    }
+}
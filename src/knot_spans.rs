@@ -0,0 +1,209 @@
+use std::collections::BTreeMap;
+
+use bevy::render::{mesh::MeshVertexAttribute, render_resource::VertexFormat};
+use curvo::prelude::{FloatingPoint, NurbsSurface};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, Vector3, U1,
+};
+
+use crate::{
+    mesh_optimization::TriangleBuffers,
+    prelude::{ControlNet, MeshProjection},
+    surface_creases::{split_knot_lines, KnotLines},
+};
+
+/// Vertex attribute holding the indices of the u and v knot spans of the Bézier patch of each vertex
+pub const ATTRIBUTE_KNOT_SPAN: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_KnotSpan", 1_284_076_513, VertexFormat::Uint32x2);
+
+/// Interior knots separating the Bézier patches of a surface
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct KnotSpans<T: FloatingPoint = f32> {
+    pub u: Vec<T>,
+    pub v: Vec<T>,
+}
+
+impl<T: FloatingPoint> KnotSpans<T> {
+    /// Collects the distinct knots inside the domain of the surface in both directions
+    pub fn detect<D>(surface: &ControlNet<T, D>) -> Self
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        Self {
+            u: interior_knots(surface.u_knots(), surface.u_degree()),
+            v: interior_knots(surface.v_knots(), surface.v_degree()),
+        }
+    }
+
+    /// Returns the number of knot spans along u and v
+    pub fn count(&self) -> (usize, usize) {
+        (self.u.len() + 1, self.v.len() + 1)
+    }
+}
+
+/// Returns the distinct knots strictly inside the domain
+fn interior_knots<T: FloatingPoint>(knots: &[T], degree: usize) -> Vec<T> {
+    let (start, end) = (knots[degree], knots[knots.len() - degree - 1]);
+    let mut interior: Vec<T> = vec![];
+    knots
+        .iter()
+        .filter(|k| start < **k && **k < end)
+        .for_each(|k| {
+            if interior.last() != Some(k) {
+                interior.push(*k);
+            }
+        });
+    interior
+}
+
+/// Cuts the triangles along the knot lines and assigns the knot span of its patch to every vertex
+pub(crate) fn split_knot_spans<T: FloatingPoint, D>(
+    buffers: &mut TriangleBuffers<T>,
+    surface: &NurbsSurface<T, D>,
+    spans: &KnotSpans<T>,
+    projection: &MeshProjection<T>,
+    origin: &Vector3<T>,
+) where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let lines = KnotLines::new(surface, &spans.u, &spans.v);
+    buffers.spans = split_knot_lines(buffers, surface, &lines, projection, origin)
+        .into_iter()
+        .map(|(u, v)| [u as u32, v as u32])
+        .collect();
+}
+
+/// Splits the buffers into the triangles of each knot span, all in the first span if none were assigned
+pub(crate) fn group_by_span<T: FloatingPoint>(
+    buffers: &TriangleBuffers<T>,
+) -> Vec<([u32; 2], TriangleBuffers<T>)> {
+    let mut groups: BTreeMap<[u32; 2], Vec<[usize; 3]>> = BTreeMap::new();
+    buffers.faces.iter().for_each(|face| {
+        let span = buffers.spans.get(face[0]).copied().unwrap_or_default();
+        groups.entry(span).or_default().push(*face);
+    });
+    groups
+        .into_iter()
+        .map(|(span, faces)| (span, buffers.subset(&faces)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::{Mesh, VertexAttributeValues};
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::{Const, Point4};
+
+    use super::*;
+    use crate::prelude::NurbsSurfaceMesh;
+
+    /// Extrusion of a quadratic profile with three knot spans
+    fn extrusion() -> ControlNet<f64, Const<4>> {
+        let profile = NurbsCurve3D::try_new(
+            2,
+            vec![
+                Point4::new(0., 0., 0., 1.),
+                Point4::new(1., 1., 0., 1.),
+                Point4::new(2., -1., 0., 1.),
+                Point4::new(3., 1., 0., 1.),
+                Point4::new(4., 0., 0., 1.),
+            ],
+            vec![0., 0., 0., 0.25, 0.5, 1., 1., 1.],
+        )
+        .unwrap();
+        ControlNet::extrude(&profile, Vector3::z())
+    }
+
+    #[test]
+    fn writes_knot_span_of_every_vertex() {
+        let surface = extrusion();
+        let spans = KnotSpans::detect(&surface);
+        assert_eq!(spans.u.len() + spans.v.len(), 2);
+
+        let mesh = NurbsSurfaceMesh::from(surface.tessellate(None))
+            .with_knot_spans(&surface)
+            .build_surface_triangle_list(None);
+        let Some(VertexAttributeValues::Uint32x2(values)) = mesh.attribute(ATTRIBUTE_KNOT_SPAN)
+        else {
+            panic!("expected knot spans");
+        };
+        assert_eq!(values.len(), mesh.count_vertices());
+        let indices: Vec<_> = mesh.indices().unwrap().iter().collect();
+        assert!(indices
+            .chunks(3)
+            .all(|f| values[f[0]] == values[f[1]] && values[f[1]] == values[f[2]]));
+        let mut distinct = values.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), 3);
+    }
+
+    #[test]
+    fn groups_triangles_by_patch() {
+        let surface = extrusion();
+        let mesh = NurbsSurfaceMesh::from(surface.tessellate(None)).with_knot_spans(&surface);
+        let whole = mesh.build_surface_triangle_list(None);
+        let patches = mesh.build_knot_span_meshes(None);
+        assert_eq!(patches.len(), 3);
+        assert!(patches.windows(2).all(|w| w[0].0 < w[1].0));
+        let triangles: usize = patches
+            .iter()
+            .map(|(_, m)| m.indices().unwrap().len() / 3)
+            .sum();
+        assert_eq!(triangles, whole.indices().unwrap().len() / 3);
+        assert!(patches.iter().all(|(span, m)| {
+            let Some(VertexAttributeValues::Uint32x2(values)) = m.attribute(ATTRIBUTE_KNOT_SPAN)
+            else {
+                return false;
+            };
+            values.iter().all(|v| v == span)
+        }));
+    }
+
+    #[test]
+    fn keeps_normals_on_the_side_of_the_winding() {
+        // knots off the dyadic subdivision of the tessellation so that triangles are cut on them
+        let profile = NurbsCurve3D::try_new(
+            2,
+            vec![
+                Point4::new(0., 0., 0., 1.),
+                Point4::new(1., 1., 0., 1.),
+                Point4::new(2., -1., 0., 1.),
+                Point4::new(3., 1., 0., 1.),
+                Point4::new(4., 0., 0., 1.),
+            ],
+            vec![0., 0., 0., 0.3, 0.7, 1., 1., 1.],
+        )
+        .unwrap();
+        let surface = ControlNet::extrude(&profile, Vector3::z());
+        let mesh = NurbsSurfaceMesh::from(surface.tessellate(None))
+            .with_knot_spans(&surface)
+            .build_surface_triangle_list(None);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expected positions");
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("expected normals");
+        };
+        let indices: Vec<_> = mesh.indices().unwrap().iter().collect();
+
+        // vertices cut on the knot lines are evaluated on the surface and must face the same side
+        // as the vertices tessellated by curvo
+        indices.chunks(3).for_each(|face| {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|i| Vector3::from(positions[i]));
+            let plane = (b - a).cross(&(c - a));
+            face.iter().for_each(|i| {
+                let n = Vector3::from(normals[*i]);
+                assert!(n.dot(&plane) > 0., "{:?} on {:?}", n, plane);
+            });
+        });
+    }
+}
@@ -1,6 +1,68 @@
+mod arc_length;
+mod bounds;
+mod collider;
+mod compatibility;
+mod contour;
+mod control_net;
+mod control_point_editing;
+mod curvature_comb;
+mod curve_picking;
+mod derivatives;
+mod displacement;
+mod distribution;
+mod floating_origin;
+mod geometry;
+#[cfg(feature = "gizmos")]
+mod gizmos;
+mod grid_tessellation;
+mod intersection;
+mod knot_spans;
+mod mesh_buffer;
+mod mesh_optimization;
+mod mesh_projection;
+mod mesh_validation;
+mod morph;
+mod nurbs_curve_mesh;
 mod nurbs_surface_mesh;
+mod path_following;
+mod primitives;
+mod surface_creases;
+mod surface_raycast;
+mod surface_seams;
+mod surfacing;
+mod vertex_colors;
 
 pub mod prelude {
+    pub use crate::arc_length::*;
+    pub use crate::bounds::*;
+    pub use crate::collider::*;
+    pub use crate::compatibility::*;
+    pub use crate::contour::*;
+    pub use crate::control_net::*;
+    pub use crate::control_point_editing::*;
+    pub use crate::curvature_comb::*;
+    pub use crate::curve_picking::*;
+    pub use crate::displacement::*;
+    pub use crate::distribution::*;
+    pub use crate::floating_origin::*;
+    pub use crate::geometry::*;
+    #[cfg(feature = "gizmos")]
+    pub use crate::gizmos::*;
+    pub use crate::grid_tessellation::*;
+    pub use crate::intersection::*;
+    pub use crate::knot_spans::*;
+    pub use crate::mesh_optimization::*;
+    pub use crate::mesh_projection::*;
+    pub use crate::mesh_validation::*;
+    pub use crate::morph::*;
+    pub use crate::nurbs_curve_mesh::*;
     pub use crate::nurbs_surface_mesh::*;
+    pub use crate::path_following::*;
+    pub use crate::primitives::*;
+    pub use crate::surface_creases::*;
+    pub use crate::surface_raycast::*;
+    pub use crate::surface_seams::*;
+    pub use crate::surfacing::*;
+    pub use crate::vertex_colors::*;
     pub use curvo::prelude::*;
 }
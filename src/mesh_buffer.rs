@@ -0,0 +1,122 @@
+use bevy::render::mesh::{
+    Indices, Mesh, MeshVertexAttribute, MeshVertexAttributeId, VertexAttributeValues,
+};
+
+/// Element of a mesh buffer stored in one variant of the attribute values or indices
+pub(crate) trait BufferElement: Sized {
+    /// The attribute values or indices holding the buffer
+    type Buffer;
+
+    /// Takes the buffer out if it holds elements of this type
+    fn take(buffer: Self::Buffer) -> Option<Vec<Self>>;
+
+    /// Wraps the elements into the buffer
+    fn wrap(elements: Vec<Self>) -> Self::Buffer;
+}
+
+macro_rules! impl_buffer_element {
+    ($buffer:ident, $($element:ty => $variant:ident),*) => {
+        $(
+            impl BufferElement for $element {
+                type Buffer = $buffer;
+
+                fn take(buffer: Self::Buffer) -> Option<Vec<Self>> {
+                    match buffer {
+                        $buffer::$variant(elements) => Some(elements),
+                        _ => None,
+                    }
+                }
+
+                fn wrap(elements: Vec<Self>) -> Self::Buffer {
+                    $buffer::$variant(elements)
+                }
+            }
+        )*
+    };
+}
+
+impl_buffer_element!(
+    VertexAttributeValues,
+    [f32; 4] => Float32x4,
+    [f32; 3] => Float32x3,
+    [f32; 2] => Float32x2,
+    [u32; 2] => Uint32x2
+);
+impl_buffer_element!(Indices, u32 => U32, u16 => U16);
+
+/// Writes the values into the attribute of the mesh, reusing its buffer if the format matches
+pub(crate) fn write_attribute<E: BufferElement<Buffer = VertexAttributeValues>>(
+    mesh: &mut Mesh,
+    attribute: MeshVertexAttribute,
+    values: impl IntoIterator<Item = E>,
+) {
+    let buffer = mesh.remove_attribute(attribute.id).and_then(E::take);
+    mesh.insert_attribute(attribute, E::wrap(refill(buffer, values)));
+}
+
+/// Writes the indices into the mesh, reusing its index buffer if the format matches
+pub(crate) fn write_indices<E: BufferElement<Buffer = Indices>>(
+    mesh: &mut Mesh,
+    indices: impl IntoIterator<Item = E>,
+) {
+    let buffer = mesh.remove_indices().and_then(E::take);
+    mesh.insert_indices(E::wrap(refill(buffer, indices)));
+}
+
+/// Clears the buffer, or creates one if there is none, and fills it with the elements
+fn refill<E>(buffer: Option<Vec<E>>, elements: impl IntoIterator<Item = E>) -> Vec<E> {
+    let mut buffer = buffer.unwrap_or_default();
+    buffer.clear();
+    buffer.extend(elements);
+    buffer
+}
+
+/// Removes the attributes of the mesh that were not written by the current build
+///
+/// The mesh belongs to the builder writing it, so attributes left by an earlier build, such as coordinate attributes
+/// that are no longer requested, describe stale vertices even when their length still matches.
+pub(crate) fn retain_attributes(
+    mesh: &mut Mesh,
+    written: impl IntoIterator<Item = MeshVertexAttributeId>,
+) {
+    let written: Vec<_> = written.into_iter().collect();
+    let stale: Vec<_> = mesh
+        .attributes()
+        .map(|(id, _)| id)
+        .filter(|id| !written.contains(id))
+        .collect();
+    stale.into_iter().for_each(|id| {
+        mesh.remove_attribute(id);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::{
+        mesh::PrimitiveTopology, render_asset::RenderAssetUsages, render_resource::VertexFormat,
+    };
+
+    use super::*;
+
+    #[test]
+    fn removes_attributes_of_earlier_builds_with_the_same_length() {
+        let stale = MeshVertexAttribute::new("Vertex_Stale", 1_284_076_600, VertexFormat::Float32);
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::default(),
+        );
+        write_attribute(&mut mesh, Mesh::ATTRIBUTE_POSITION, [[0f32; 3]; 3]);
+        mesh.insert_attribute(stale.clone(), vec![0f32; 3]);
+
+        write_attribute(&mut mesh, Mesh::ATTRIBUTE_POSITION, [[1f32; 3]; 3]);
+        write_indices(&mut mesh, [0u16, 1, 2]);
+        retain_attributes(&mut mesh, [Mesh::ATTRIBUTE_POSITION.id]);
+
+        assert!(!mesh.contains_attribute(stale.id));
+        assert!(matches!(
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            Some(VertexAttributeValues::Float32x3(p)) if p == &vec![[1f32; 3]; 3]
+        ));
+        assert!(matches!(mesh.indices(), Some(Indices::U16(i)) if i.len() == 3));
+    }
+}
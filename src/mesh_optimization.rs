@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+
+use curvo::prelude::FloatingPoint;
+use nalgebra::{Vector2, Vector3};
+
+/// Size of the LRU vertex cache targeted by the triangle reordering
+const CACHE_SIZE: usize = 32;
+
+/// Options to reduce the size of surface meshes
+#[derive(Debug, Clone, Copy)]
+pub struct MeshOptimization<T: FloatingPoint = f32> {
+    /// Emits u16 indices if the vertex count allows it
+    pub compact_indices: bool,
+    /// The distance within which vertices are welded. If None, vertices are not welded
+    pub weld_tolerance: Option<T>,
+    /// The largest angle in radians between the normals of welded vertices
+    pub weld_normal_angle: T,
+    /// The largest distance between the uvs of welded vertices
+    pub weld_uv_tolerance: T,
+    /// Reorders the triangles for vertex cache locality and the vertices by first use
+    pub reorder_for_cache: bool,
+}
+
+impl<T: FloatingPoint> Default for MeshOptimization<T> {
+    fn default() -> Self {
+        Self {
+            compact_indices: true,
+            weld_tolerance: None,
+            weld_normal_angle: T::from_f64(1e-3).unwrap(),
+            weld_uv_tolerance: T::from_f64(1e-6).unwrap(),
+            reorder_for_cache: true,
+        }
+    }
+}
+
+/// Statistics of a surface mesh before and after the optimization
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeshStatistics {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub triangles_before: usize,
+    pub triangles_after: usize,
+    /// The size of the index buffer in bytes
+    pub index_bytes_before: usize,
+    pub index_bytes_after: usize,
+    /// The average cache miss ratio per triangle with a 32 entry LRU cache
+    pub acmr_before: f32,
+    pub acmr_after: f32,
+}
+
+/// Returns true if the vertex count is addressable by u16 indices
+pub(crate) fn fits_u16(vertex_count: usize) -> bool {
+    vertex_count <= u16::MAX as usize + 1
+}
+
+/// Triangle list whose vertices refer to the source vertices of a tessellation
+pub(crate) struct TriangleBuffers<T: FloatingPoint> {
+    pub positions: Vec<Vector3<T>>,
+    pub normals: Vec<Vector3<T>>,
+    pub uvs: Vec<Vector2<T>>,
+    /// The index of the tessellation vertex of each vertex
+    pub sources: Vec<usize>,
+    /// The u and v knot span of each vertex, empty if they are not written
+    pub spans: Vec<[u32; 2]>,
+    pub faces: Vec<[usize; 3]>,
+    /// True if the indices are written as u16
+    pub compact_indices: bool,
+}
+
+impl<T: FloatingPoint> TriangleBuffers<T> {
+    pub fn new(
+        positions: Vec<Vector3<T>>,
+        normals: Vec<Vector3<T>>,
+        uvs: Vec<Vector2<T>>,
+        faces: Vec<[usize; 3]>,
+    ) -> Self {
+        Self {
+            sources: (0..positions.len()).collect(),
+            positions,
+            normals,
+            uvs,
+            spans: vec![],
+            faces,
+            compact_indices: false,
+        }
+    }
+
+    /// Returns the buffers of the triangles with only the vertices they use, in the order of first use
+    pub fn subset(&self, faces: &[[usize; 3]]) -> Self {
+        let mut remap = vec![usize::MAX; self.positions.len()];
+        let mut order = vec![];
+        let faces = faces
+            .iter()
+            .map(|f| {
+                f.map(|v| {
+                    if remap[v] == usize::MAX {
+                        remap[v] = order.len();
+                        order.push(v);
+                    }
+                    remap[v]
+                })
+            })
+            .collect();
+        Self {
+            positions: order.iter().map(|i| self.positions[*i]).collect(),
+            normals: order.iter().map(|i| self.normals[*i]).collect(),
+            uvs: order.iter().map(|i| self.uvs[*i]).collect(),
+            sources: order.iter().map(|i| self.sources[*i]).collect(),
+            spans: if self.spans.is_empty() {
+                vec![]
+            } else {
+                order.iter().map(|i| self.spans[*i]).collect()
+            },
+            faces,
+            compact_indices: self.compact_indices,
+        }
+    }
+
+    /// Applies the optimization and returns the statistics
+    pub fn optimize(&mut self, options: &MeshOptimization<T>) -> MeshStatistics {
+        let vertices_before = self.positions.len();
+        let triangles_before = self.faces.len();
+        let acmr_before = acmr(&self.faces);
+
+        if let Some(tolerance) = options.weld_tolerance {
+            self.weld(
+                tolerance,
+                options.weld_normal_angle,
+                options.weld_uv_tolerance,
+            );
+        }
+        if options.reorder_for_cache {
+            self.reorder_triangles();
+            self.reorder_vertices();
+        }
+        self.compact_indices = options.compact_indices && fits_u16(self.positions.len());
+
+        let index_size = if self.compact_indices { 2 } else { 4 };
+        MeshStatistics {
+            vertices_before,
+            vertices_after: self.positions.len(),
+            triangles_before,
+            triangles_after: self.faces.len(),
+            index_bytes_before: triangles_before * 3 * 4,
+            index_bytes_after: self.faces.len() * 3 * index_size,
+            acmr_before,
+            acmr_after: acmr(&self.faces),
+        }
+    }
+
+    /// Merges vertices within the tolerance whose normals and uvs also match,
+    /// dropping the triangles collapsed by the merge
+    fn weld(&mut self, tolerance: T, normal_angle: T, uv_tolerance: T) {
+        let cos = T::from_f64(normal_angle.to_f64().unwrap().cos()).unwrap();
+        let (remap, kept) = cluster(&self.positions, tolerance, |i, j| {
+            self.normals[j].dot(&self.normals[i])
+                >= cos * self.normals[j].norm() * self.normals[i].norm()
+                && (self.uvs[j] - self.uvs[i]).norm() <= uv_tolerance
+                && (self.spans.is_empty() || self.spans[j] == self.spans[i])
+        });
+
+        self.faces = self
+            .faces
+            .iter()
+            .map(|f| f.map(|i| remap[i]))
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .collect();
+        self.select(&kept);
+    }
+
+    /// Reorders the triangles for an LRU vertex cache with Forsyth's algorithm
+    fn reorder_triangles(&mut self) {
+        let n = self.positions.len();
+        let mut vertex_faces = vec![vec![]; n];
+        self.faces.iter().enumerate().for_each(|(i, f)| {
+            f.iter().for_each(|v| vertex_faces[*v].push(i));
+        });
+        let mut cache_position: Vec<Option<usize>> = vec![None; n];
+        let mut scores: Vec<f32> = vertex_faces
+            .iter()
+            .map(|faces| vertex_score(None, faces.len()))
+            .collect();
+
+        let mut emitted = vec![false; self.faces.len()];
+        let mut order = Vec::with_capacity(self.faces.len());
+        let mut cache: Vec<usize> = vec![];
+        let mut best: Option<usize> = None;
+        let mut cursor = 0;
+        while order.len() < self.faces.len() {
+            let face = best.unwrap_or_else(|| {
+                while emitted[cursor] {
+                    cursor += 1;
+                }
+                cursor
+            });
+            emitted[face] = true;
+            order.push(face);
+
+            let vertices = self.faces[face];
+            vertices
+                .iter()
+                .for_each(|v| vertex_faces[*v].retain(|f| *f != face));
+            let mut next: Vec<usize> = vec![];
+            vertices.iter().for_each(|v| {
+                if !next.contains(v) {
+                    next.push(*v);
+                }
+            });
+            next.extend(cache.iter().filter(|v| !vertices.contains(v)));
+            next.iter().enumerate().for_each(|(i, v)| {
+                cache_position[*v] = (i < CACHE_SIZE).then_some(i);
+                scores[*v] = vertex_score(cache_position[*v], vertex_faces[*v].len());
+            });
+            next.truncate(CACHE_SIZE);
+            cache = next;
+
+            best = cache
+                .iter()
+                .flat_map(|v| vertex_faces[*v].iter())
+                .map(|f| (*f, self.faces[*f].iter().map(|v| scores[*v]).sum::<f32>()))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(f, _)| f);
+        }
+        self.faces = order.into_iter().map(|i| self.faces[i]).collect();
+    }
+
+    /// Reorders the vertices by their first use in the triangles
+    fn reorder_vertices(&mut self) {
+        let mut remap = vec![usize::MAX; self.positions.len()];
+        let mut order = Vec::with_capacity(self.positions.len());
+        self.faces.iter_mut().for_each(|f| {
+            f.iter_mut().for_each(|v| {
+                if remap[*v] == usize::MAX {
+                    remap[*v] = order.len();
+                    order.push(*v);
+                }
+                *v = remap[*v];
+            });
+        });
+        self.select(&order);
+    }
+
+    /// Appends a copy of the vertex and returns its index
+    pub fn copy_vertex(&mut self, index: usize) -> usize {
+        self.push_vertex(
+            self.positions[index],
+            self.normals[index],
+            self.uvs[index],
+            index,
+        )
+    }
+
+    /// Appends a vertex and returns its index
+    /// * `origin` - The vertex whose source and knot span the new vertex takes
+    pub fn push_vertex(
+        &mut self,
+        position: Vector3<T>,
+        normal: Vector3<T>,
+        uv: Vector2<T>,
+        origin: usize,
+    ) -> usize {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.uvs.push(uv);
+        self.sources.push(self.sources[origin]);
+        if !self.spans.is_empty() {
+            self.spans.push(self.spans[origin]);
+        }
+        self.positions.len() - 1
+    }
+
+    /// Keeps the vertices at the indices in order
+    pub fn select(&mut self, indices: &[usize]) {
+        self.positions = indices.iter().map(|i| self.positions[*i]).collect();
+        self.normals = indices.iter().map(|i| self.normals[*i]).collect();
+        self.uvs = indices.iter().map(|i| self.uvs[*i]).collect();
+        self.sources = indices.iter().map(|i| self.sources[*i]).collect();
+        if !self.spans.is_empty() {
+            self.spans = indices.iter().map(|i| self.spans[*i]).collect();
+        }
+    }
+}
+
+/// Groups the positions within the tolerance of the first position of a group for which `same` also holds,
+/// returning the group of every position and the first position of every group
+/// * `same` - Whether the position joins the group of the first position, given their indices
+pub(crate) fn cluster<T: FloatingPoint>(
+    positions: &[Vector3<T>],
+    tolerance: T,
+    same: impl Fn(usize, usize) -> bool,
+) -> (Vec<usize>, Vec<usize>) {
+    let cell = tolerance.to_f64().unwrap().max(f64::EPSILON);
+    let key = |p: &Vector3<T>| p.map(|c| (c.to_f64().unwrap() / cell).floor() as i64);
+
+    let mut grid: HashMap<Vector3<i64>, Vec<usize>> = HashMap::new();
+    let mut firsts: Vec<usize> = vec![];
+    let groups = positions
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let k = key(p);
+            let found = (-1..=1)
+                .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| (x, y, z))))
+                .filter_map(|(x, y, z)| grid.get(&(k + Vector3::new(x, y, z))))
+                .flatten()
+                .find(|g| {
+                    let j = firsts[**g];
+                    (positions[j] - p).norm() <= tolerance && same(i, j)
+                })
+                .copied();
+            found.unwrap_or_else(|| {
+                grid.entry(k).or_default().push(firsts.len());
+                firsts.push(i);
+                firsts.len() - 1
+            })
+        })
+        .collect();
+    (groups, firsts)
+}
+
+/// Score of a vertex in Forsyth's algorithm, favoring recently used vertices with few remaining triangles
+fn vertex_score(cache_position: Option<usize>, remaining: usize) -> f32 {
+    if remaining == 0 {
+        return -1.;
+    }
+    let cache_score = match cache_position {
+        Some(p) if p < 3 => 0.75,
+        Some(p) => (1. - (p - 3) as f32 / (CACHE_SIZE - 3) as f32).powf(1.5),
+        None => 0.,
+    };
+    cache_score + 2. * (remaining as f32).powf(-0.5)
+}
+
+/// Average cache miss ratio per triangle with an LRU vertex cache
+fn acmr(faces: &[[usize; 3]]) -> f32 {
+    if faces.is_empty() {
+        return 0.;
+    }
+    let mut cache: Vec<usize> = Vec::with_capacity(CACHE_SIZE + 1);
+    let misses = faces
+        .iter()
+        .flatten()
+        .filter(|v| {
+            let hit = match cache.iter().position(|c| c == *v) {
+                Some(i) => {
+                    cache.remove(i);
+                    true
+                }
+                None => false,
+            };
+            cache.insert(0, **v);
+            cache.truncate(CACHE_SIZE);
+            !hit
+        })
+        .count();
+    misses as f32 / faces.len() as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::{Indices, Mesh, PrimitiveTopology};
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::Point3;
+
+    use super::*;
+    use crate::prelude::{ControlNet, MeshRepair, NurbsSurfaceMesh};
+
+    /// Quad of two triangles whose shared edge is duplicated, with the normals of the second triangle
+    fn split_quad(normal: Vector3<f64>) -> TriangleBuffers<f64> {
+        let corners = [(0., 0.), (1., 0.), (1., 1.), (0., 0.), (1., 1.), (0., 1.)];
+        TriangleBuffers::new(
+            corners
+                .iter()
+                .map(|(x, y)| Vector3::new(*x, *y, 0.))
+                .collect(),
+            (0..6)
+                .map(|i| if i < 3 { Vector3::z() } else { normal })
+                .collect(),
+            corners.iter().map(|(x, y)| Vector2::new(*x, *y)).collect(),
+            vec![[0, 1, 2], [3, 4, 5]],
+        )
+    }
+
+    #[test]
+    fn welds_coincident_vertices() {
+        let mut buffers = split_quad(Vector3::z());
+        let statistics = buffers.optimize(&MeshOptimization {
+            weld_tolerance: Some(1e-9),
+            reorder_for_cache: false,
+            ..Default::default()
+        });
+        assert_eq!(statistics.vertices_before, 6);
+        assert_eq!(statistics.vertices_after, 4);
+        assert_eq!(statistics.triangles_after, 2);
+        assert_eq!(buffers.sources, vec![0, 1, 2, 5]);
+        assert_eq!(buffers.faces, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn keeps_vertices_with_different_normals() {
+        let mut buffers = split_quad(Vector3::new(0., 1., 1.).normalize());
+        let statistics = buffers.optimize(&MeshOptimization {
+            weld_tolerance: Some(1e-9),
+            ..Default::default()
+        });
+        assert_eq!(statistics.vertices_after, 6);
+        assert_eq!(statistics.triangles_after, 2);
+    }
+
+    #[test]
+    fn compacts_indices_to_u16() {
+        assert!(fits_u16(u16::MAX as usize + 1));
+        assert!(!fits_u16(u16::MAX as usize + 2));
+
+        let points =
+            [(0., 0.), (1., 0.5), (2., -0.5), (3., 0.)].map(|(x, y)| Point3::new(x, y, 0.));
+        let profile = NurbsCurve3D::<f64>::try_interpolate(&points, 3, None, None).unwrap();
+        let surface = ControlNet::extrude(&profile, Vector3::z());
+        let mesh = NurbsSurfaceMesh::from(surface.tessellate(None))
+            .with_optimization(MeshOptimization::default());
+        let mut target = Mesh::new(PrimitiveTopology::TriangleList, Default::default());
+        let statistics = mesh
+            .try_write_surface_triangle_list(&mut target, &MeshRepair::default())
+            .unwrap()
+            .statistics
+            .unwrap();
+
+        let Some(Indices::U16(indices)) = target.indices() else {
+            panic!("expected u16 indices");
+        };
+        assert_eq!(indices.len(), statistics.triangles_after * 3);
+        assert_eq!(
+            statistics.index_bytes_before,
+            statistics.triangles_before * 12
+        );
+        assert_eq!(statistics.index_bytes_after, statistics.triangles_after * 6);
+        assert!(statistics.acmr_after <= statistics.acmr_before);
+    }
+}
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+
+use bevy::render::{
+    mesh::{MeshVertexAttribute, VertexAttributeValues},
+    render_resource::VertexFormat,
+};
+use curvo::prelude::FloatingPoint;
+use nalgebra::{DimName, Vector3, U1, U2, U3};
+
+use crate::mesh_validation::{is_finite_f32, NurbsMeshError};
+
+/// Dimensions of points used as mesh positions without an explicit projection
+pub trait PositionDim: DimName {}
+
+impl PositionDim for U1 {}
+impl PositionDim for U2 {}
+impl PositionDim for U3 {}
+
+/// Closure mapping the coordinates of a point to a position
+pub type ProjectionFn<T> = Arc<dyn Fn(&[T]) -> Vector3<T> + Send + Sync>;
+
+/// Projection of tessellated points into the 3D positions of a mesh
+#[derive(Clone, Default)]
+pub enum MeshProjection<T: FloatingPoint = f32> {
+    /// Points with up to three coordinates are used as they are, padding missing ones with zero
+    #[default]
+    Identity,
+    /// Selects the coordinates used as the x, y and z of the position
+    Axes([usize; 3]),
+    /// Maps the coordinates of a point to a position
+    Custom(ProjectionFn<T>),
+}
+
+impl<T: FloatingPoint> MeshProjection<T> {
+    /// Creates a projection from a closure mapping the coordinates of a point to a position
+    pub fn custom(f: impl Fn(&[T]) -> Vector3<T> + Send + Sync + 'static) -> Self {
+        Self::Custom(Arc::new(f))
+    }
+
+    /// Checks that the projection is defined for points of the dimension
+    pub fn validate(&self, dim: usize) -> Result<(), NurbsMeshError> {
+        match self {
+            MeshProjection::Identity if dim > 3 => Err(NurbsMeshError::MissingProjection { dim }),
+            MeshProjection::Axes(axes) => match axes.iter().find(|axis| **axis >= dim) {
+                Some(axis) => Err(NurbsMeshError::AxisOutOfRange { axis: *axis, dim }),
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns true if the normals of the tessellation remain valid after the projection
+    pub fn preserves_normals(&self) -> bool {
+        matches!(self, MeshProjection::Identity)
+    }
+
+    /// Projects the coordinates of a point to a position
+    pub fn project(&self, coords: &[T]) -> Vector3<T> {
+        match self {
+            MeshProjection::Identity => {
+                Vector3::from_fn(|i, _| coords.get(i).copied().unwrap_or(T::zero()))
+            }
+            MeshProjection::Axes(axes) => {
+                Vector3::new(coords[axes[0]], coords[axes[1]], coords[axes[2]])
+            }
+            MeshProjection::Custom(f) => f(coords),
+        }
+    }
+}
+
+/// Vertex attribute filled with coordinates of the tessellated points
+///
+/// Useful to keep extra dimensions of a tessellation, e.g. a color or a scalar field.
+#[derive(Debug, Clone)]
+pub struct CoordinateAttribute {
+    /// The attribute whose format is `Float32` to `Float32x4` for one to four components
+    pub attribute: MeshVertexAttribute,
+    /// The indices of the coordinates written to the attribute
+    pub components: Vec<usize>,
+}
+
+impl CoordinateAttribute {
+    pub fn new(attribute: MeshVertexAttribute, components: impl Into<Vec<usize>>) -> Self {
+        Self {
+            attribute,
+            components: components.into(),
+        }
+    }
+
+    /// Checks that the components exist in points of the dimension and match the format of the attribute
+    pub fn validate(&self, dim: usize) -> Result<(), NurbsMeshError> {
+        let attribute = self.attribute.name;
+        if let Some(component) = self.components.iter().find(|c| **c >= dim) {
+            return Err(NurbsMeshError::ComponentOutOfRange {
+                attribute,
+                component: *component,
+                dim,
+            });
+        }
+        let expected = match self.components.len() {
+            1 => VertexFormat::Float32,
+            2 => VertexFormat::Float32x2,
+            3 => VertexFormat::Float32x3,
+            4 => VertexFormat::Float32x4,
+            count => return Err(NurbsMeshError::ComponentCount { attribute, count }),
+        };
+        if self.attribute.format != expected {
+            return Err(NurbsMeshError::FormatMismatch {
+                attribute,
+                format: self.attribute.format,
+                expected,
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks that the components of the points can be cast to finite f32
+    pub(crate) fn check_finite<'a, T: FloatingPoint>(
+        &self,
+        mut points: impl Iterator<Item = &'a [T]>,
+    ) -> Result<(), NurbsMeshError> {
+        match points.position(|p| !self.components.iter().all(|c| is_finite_f32(p[*c]))) {
+            Some(index) => Err(NurbsMeshError::NonFiniteCoordinate {
+                attribute: self.attribute.name,
+                index,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Collects the components of the points into attribute values
+    pub(crate) fn values<'a, T: FloatingPoint>(
+        &self,
+        points: impl Iterator<Item = &'a [T]>,
+    ) -> VertexAttributeValues {
+        let c = &self.components;
+        let f = |coords: &[T], i: usize| coords[c[i]].to_f32().unwrap();
+        match c.len() {
+            1 => VertexAttributeValues::Float32(points.map(|p| f(p, 0)).collect()),
+            2 => VertexAttributeValues::Float32x2(points.map(|p| [f(p, 0), f(p, 1)]).collect()),
+            3 => VertexAttributeValues::Float32x3(
+                points.map(|p| [f(p, 0), f(p, 1), f(p, 2)]).collect(),
+            ),
+            _ => VertexAttributeValues::Float32x4(
+                points
+                    .map(|p| [f(p, 0), f(p, 1), f(p, 2), f(p, 3)])
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// Computes area weighted vertex normals of a triangle list
+pub(crate) fn vertex_normals<T: FloatingPoint>(
+    positions: &[Vector3<T>],
+    faces: &[[usize; 3]],
+) -> Vec<Vector3<T>> {
+    let mut normals = vec![Vector3::zeros(); positions.len()];
+    faces.iter().for_each(|[a, b, c]| {
+        let n = (positions[*b] - positions[*a]).cross(&(positions[*c] - positions[*a]));
+        normals[*a] += n;
+        normals[*b] += n;
+        normals[*c] += n;
+    });
+    normals
+        .into_iter()
+        .map(|n| n.try_normalize(T::default_epsilon()).unwrap_or(n))
+        .collect()
+}
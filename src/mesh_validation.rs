@@ -0,0 +1,314 @@
+use std::fmt;
+
+use bevy::render::render_resource::VertexFormat;
+use curvo::prelude::FloatingPoint;
+use nalgebra::Vector3;
+
+use crate::{
+    mesh_projection::vertex_normals,
+    prelude::{MeshStatistics, ScalarFieldLegend},
+};
+
+/// Error raised when a tessellation cannot be written into a valid mesh
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NurbsMeshError {
+    /// Points with more than three coordinates were given without an explicit projection
+    MissingProjection { dim: usize },
+    /// An axis of the projection is out of range of the coordinates
+    AxisOutOfRange { axis: usize, dim: usize },
+    /// A component of the attribute is out of range of the coordinates
+    ComponentOutOfRange {
+        attribute: &'static str,
+        component: usize,
+        dim: usize,
+    },
+    /// The attribute does not have one to four components
+    ComponentCount {
+        attribute: &'static str,
+        count: usize,
+    },
+    /// The format of the attribute does not match the number of components
+    FormatMismatch {
+        attribute: &'static str,
+        format: VertexFormat,
+        expected: VertexFormat,
+    },
+    /// The tessellation has no vertices or no primitives
+    EmptyTessellation,
+    /// A value of the attribute at the vertex index is NaN, infinite or out of the f32 range
+    NonFiniteCoordinate {
+        attribute: &'static str,
+        index: usize,
+    },
+    /// The attribute has a different number of values than the vertices
+    MismatchedAttributeLength {
+        attribute: &'static str,
+        expected: usize,
+        found: usize,
+    },
+    /// The face refers to a vertex index beyond the vertex count
+    IndexOutOfBounds {
+        face: usize,
+        index: usize,
+        vertex_count: usize,
+    },
+    /// The vertex count exceeds the range of u32 indices
+    IndexOverflow { vertex_count: usize },
+}
+
+impl fmt::Display for NurbsMeshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NurbsMeshError::MissingProjection { dim } => {
+                write!(f, "{}-dimensional points need an explicit projection", dim)
+            }
+            NurbsMeshError::AxisOutOfRange { axis, dim } => write!(
+                f,
+                "axis {} is out of range for {}-dimensional points",
+                axis, dim
+            ),
+            NurbsMeshError::ComponentOutOfRange {
+                attribute,
+                component,
+                dim,
+            } => write!(
+                f,
+                "component {} of attribute {} is out of range for {}-dimensional points",
+                component, attribute, dim
+            ),
+            NurbsMeshError::ComponentCount { attribute, count } => write!(
+                f,
+                "attribute {} has {} components, expected 1 to 4",
+                attribute, count
+            ),
+            NurbsMeshError::FormatMismatch {
+                attribute,
+                format,
+                expected,
+            } => write!(
+                f,
+                "attribute {} has format {:?}, expected {:?}",
+                attribute, format, expected
+            ),
+            NurbsMeshError::EmptyTessellation => write!(f, "tessellation is empty"),
+            NurbsMeshError::NonFiniteCoordinate { attribute, index } => {
+                write!(f, "{} of vertex {} is not finite in f32", attribute, index)
+            }
+            NurbsMeshError::MismatchedAttributeLength {
+                attribute,
+                expected,
+                found,
+            } => write!(
+                f,
+                "{} has {} values, expected {}",
+                attribute, found, expected
+            ),
+            NurbsMeshError::IndexOutOfBounds {
+                face,
+                index,
+                vertex_count,
+            } => write!(
+                f,
+                "face {} refers to vertex {} out of {} vertices",
+                face, index, vertex_count
+            ),
+            NurbsMeshError::IndexOverflow { vertex_count } => write!(
+                f,
+                "{} vertices exceed the range of u32 indices",
+                vertex_count
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NurbsMeshError {}
+
+/// Repairs applied while validating a triangle list
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MeshRepair {
+    /// Drops triangles with repeated vertices or zero area
+    pub drop_degenerate_triangles: bool,
+    /// Replaces zero or non-finite normals with the area weighted normal of the adjacent triangles
+    pub renormalize_zero_normals: bool,
+}
+
+impl MeshRepair {
+    /// Enables all repairs
+    pub fn all() -> Self {
+        Self {
+            drop_degenerate_triangles: true,
+            renormalize_zero_normals: true,
+        }
+    }
+}
+
+/// Report of the validation and optimization passes over a triangle list
+#[derive(Debug, Clone, Default)]
+pub struct MeshValidation {
+    /// The indices of the triangles with repeated vertices or zero area
+    pub degenerate_triangles: Vec<usize>,
+    /// The indices of the vertices with zero or non-finite normals
+    pub zero_normals: Vec<usize>,
+    /// True if the degenerate triangles were dropped
+    pub dropped_triangles: bool,
+    /// True if the zero normals were recomputed
+    pub renormalized_normals: bool,
+    /// The statistics of the optimization if it was applied
+    pub statistics: Option<MeshStatistics>,
+    /// The legend of the scalar vertex colors written to the mesh if a scalar field is set
+    pub legend: Option<ScalarFieldLegend>,
+}
+
+impl MeshValidation {
+    /// Returns true if no issues were found
+    pub fn is_clean(&self) -> bool {
+        self.degenerate_triangles.is_empty() && self.zero_normals.is_empty()
+    }
+}
+
+/// Returns true if the value can be cast to a finite f32
+pub(crate) fn is_finite_f32<T: FloatingPoint>(value: T) -> bool {
+    value.to_f32().is_some_and(f32::is_finite)
+}
+
+/// Checks that all values of the attribute can be cast to finite f32
+pub(crate) fn check_finite<'a, T: FloatingPoint>(
+    attribute: &'static str,
+    values: impl IntoIterator<Item = &'a [T]>,
+) -> Result<(), NurbsMeshError> {
+    match values
+        .into_iter()
+        .position(|v| !v.iter().all(|c| is_finite_f32(*c)))
+    {
+        Some(index) => Err(NurbsMeshError::NonFiniteCoordinate { attribute, index }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that the attribute has a value per vertex
+pub(crate) fn check_length(
+    attribute: &'static str,
+    expected: usize,
+    found: usize,
+) -> Result<(), NurbsMeshError> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(NurbsMeshError::MismatchedAttributeLength {
+            attribute,
+            expected,
+            found,
+        })
+    }
+}
+
+/// Checks that the faces refer to existing vertices addressable by u32 indices
+pub(crate) fn check_faces(faces: &[[usize; 3]], vertex_count: usize) -> Result<(), NurbsMeshError> {
+    if vertex_count == 0 || faces.is_empty() {
+        return Err(NurbsMeshError::EmptyTessellation);
+    }
+    if vertex_count > u32::MAX as usize + 1 {
+        return Err(NurbsMeshError::IndexOverflow { vertex_count });
+    }
+    faces.iter().enumerate().try_for_each(|(face, f)| {
+        match f.iter().find(|i| **i >= vertex_count) {
+            Some(index) => Err(NurbsMeshError::IndexOutOfBounds {
+                face,
+                index: *index,
+                vertex_count,
+            }),
+            None => Ok(()),
+        }
+    })
+}
+
+/// Finds degenerate triangles and zero normals, repairing them if requested
+///
+/// A triangle has zero area if its doubled area is within the precision of T relative to its squared longest edge,
+/// so the test does not depend on the scale of the mesh.
+pub(crate) fn validate_triangles<T: FloatingPoint>(
+    positions: &[Vector3<T>],
+    normals: &mut [Vector3<T>],
+    faces: &mut Vec<[usize; 3]>,
+    repair: &MeshRepair,
+) -> MeshValidation {
+    let eps = T::default_epsilon();
+    let degenerate_triangles: Vec<_> = faces
+        .iter()
+        .enumerate()
+        .filter(|(_, [a, b, c])| {
+            let (pa, pb, pc) = (positions[*a], positions[*b], positions[*c]);
+            let longest = (pb - pa)
+                .norm_squared()
+                .max((pc - pb).norm_squared())
+                .max((pa - pc).norm_squared());
+            a == b || b == c || c == a || (pb - pa).cross(&(pc - pa)).norm() <= eps * longest
+        })
+        .map(|(i, _)| i)
+        .collect();
+    if repair.drop_degenerate_triangles && !degenerate_triangles.is_empty() {
+        let mut degenerate = degenerate_triangles.iter().peekable();
+        let mut index = 0;
+        faces.retain(|_| {
+            let keep = degenerate.next_if_eq(&&index).is_none();
+            index += 1;
+            keep
+        });
+    }
+
+    let zero_normals: Vec<_> = normals
+        .iter()
+        .enumerate()
+        .filter(|(_, n)| !n.iter().all(|c| is_finite_f32(*c)) || n.norm() <= eps)
+        .map(|(i, _)| i)
+        .collect();
+    if repair.renormalize_zero_normals && !zero_normals.is_empty() {
+        let recomputed = vertex_normals(positions, faces);
+        zero_normals
+            .iter()
+            .for_each(|i| normals[*i] = recomputed[*i]);
+    }
+
+    MeshValidation {
+        dropped_triangles: repair.drop_degenerate_triangles && !degenerate_triangles.is_empty(),
+        renormalized_normals: repair.renormalize_zero_normals && !zero_normals.is_empty(),
+        degenerate_triangles,
+        zero_normals,
+        statistics: None,
+        legend: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn degenerate(positions: &[Vector3<f32>]) -> Vec<usize> {
+        let mut normals = vec![Vector3::z(); positions.len()];
+        let mut faces = vec![[0, 1, 2]];
+        validate_triangles(positions, &mut normals, &mut faces, &MeshRepair::default())
+            .degenerate_triangles
+    }
+
+    #[test]
+    fn detects_degenerate_triangles_relative_to_their_size() {
+        let small = [Vector3::zeros(), Vector3::x(), Vector3::y()].map(|p| p * 1e-4);
+        assert!(degenerate(&small).is_empty());
+
+        let sliver = [
+            Vector3::new(1000., 0., 0.),
+            Vector3::new(1001., 0., 0.),
+            Vector3::new(1002., 1e-7, 0.),
+        ];
+        assert_eq!(degenerate(&sliver), vec![0]);
+    }
+
+    #[test]
+    fn checks_indices_against_the_vertex_count() {
+        assert!(check_faces(&[[0, 1, 2]], 3).is_ok());
+        assert!(matches!(
+            check_faces(&[[0, 1, 3]], 3),
+            Err(NurbsMeshError::IndexOutOfBounds { index: 3, .. })
+        ));
+    }
+}
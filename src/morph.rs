@@ -0,0 +1,495 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{Assets, Handle},
+    ecs::{
+        change_detection::DetectChangesMut,
+        component::Component,
+        entity::Entity,
+        query::{Changed, Or},
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, Res, ResMut},
+    },
+    log::warn,
+    render::mesh::{Mesh, VertexAttributeValues},
+    time::Time,
+};
+use curvo::prelude::{AdaptiveTessellationOptions, FloatingPoint, NurbsCurve3D};
+use nalgebra::{Const, OPoint, Vector2, Vector3};
+
+use crate::{
+    geometry::{translated, update_scalar_legend, with_surface_settings},
+    prelude::{
+        try_make_curves_compatible, try_make_surfaces_compatible, ControlNet, GridTessellation,
+        NurbsCurveGeometry, NurbsCurveMesh, NurbsDisplacement, NurbsMeshSet, NurbsSurfaceGeometry,
+        NurbsSurfaceMesh, NurbsTessellation, NurbsVertexColors,
+    },
+};
+
+/// Easing applied to the interpolation between two keyframes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Holds the shape of the keyframe until the next keyframe
+    Step,
+    /// Cubic ease in
+    EaseIn,
+    /// Cubic ease out
+    EaseOut,
+    /// Cubic ease in and out
+    EaseInOut,
+}
+
+impl Easing {
+    /// Maps the linear progress in [0, 1] to the eased progress
+    pub fn ease(&self, t: f32) -> f32 {
+        let t = t.clamp(0., 1.);
+        match self {
+            Easing::Linear => t,
+            Easing::Step => 0.,
+            Easing::EaseIn => t * t * t,
+            Easing::EaseOut => 1. - (1. - t).powi(3),
+            Easing::EaseInOut => {
+                if t < 0.5 {
+                    4. * t * t * t
+                } else {
+                    1. - (-2. * t + 2.).powi(3) * 0.5
+                }
+            }
+        }
+    }
+}
+
+/// Shape at a time of a morph animation
+#[derive(Debug, Clone)]
+pub struct Keyframe<G> {
+    /// The time in seconds
+    pub time: f32,
+    /// The shape at the time
+    pub shape: G,
+    /// The easing of the interpolation towards the next keyframe
+    pub easing: Easing,
+}
+
+impl<G> Keyframe<G> {
+    pub fn new(time: f32, shape: G) -> Self {
+        Self {
+            time,
+            shape,
+            easing: Easing::default(),
+        }
+    }
+
+    pub fn with_easing(mut self, easing: Easing) -> Self {
+        self.easing = easing;
+        self
+    }
+}
+
+/// Playback state shared by curve and surface morphs
+#[derive(Debug, Clone, Copy)]
+pub struct MorphPlayback {
+    /// The current time in seconds
+    pub time: f32,
+    /// The playback speed multiplier
+    pub speed: f32,
+    /// Whether the animation loops from the last keyframe to the first
+    pub looping: bool,
+    /// Whether the animation is playing
+    pub playing: bool,
+}
+
+impl Default for MorphPlayback {
+    fn default() -> Self {
+        Self {
+            time: 0.,
+            speed: 1.,
+            looping: true,
+            playing: true,
+        }
+    }
+}
+
+impl MorphPlayback {
+    /// Advances the time and returns the keyframe index and the eased factor towards the next keyframe
+    fn advance<G>(&mut self, keyframes: &[Keyframe<G>], dt: f32) -> Option<(usize, f32)> {
+        let first = keyframes.first()?.time;
+        let last = keyframes.last()?.time;
+        if self.playing {
+            self.time += dt * self.speed;
+        }
+        let duration = last - first;
+        self.time = if self.looping && duration > 0. {
+            first + (self.time - first).rem_euclid(duration)
+        } else {
+            self.time.clamp(first, last)
+        };
+
+        let i = keyframes
+            .partition_point(|k| k.time <= self.time)
+            .saturating_sub(1)
+            .min(keyframes.len().saturating_sub(2));
+        if keyframes.len() < 2 {
+            return Some((0, 0.));
+        }
+        let (k0, k1) = (&keyframes[i], &keyframes[i + 1]);
+        let span = k1.time - k0.time;
+        let f = if span > 0. {
+            (self.time - k0.time) / span
+        } else {
+            1.
+        };
+        Some((i, k0.easing.ease(f)))
+    }
+}
+
+/// Component to morph the curve geometry of the entity between keyframed shapes
+///
+/// The keyframe shapes are made compatible by degree elevation and knot refinement when the component is changed,
+/// and the mesh is updated in place at fixed parameters every frame.
+/// The geometry component is updated with change detection, but `NurbsGeometryPlugin` does not remesh morphed entities.
+///
+/// The keyframes are in the local space of the geometry, and `NurbsOriginRebase` translates them with the geometry,
+/// so insert the morph before or together with the marker.
+#[derive(Component, Debug, Clone)]
+pub struct NurbsCurveMorph<T: FloatingPoint = f32> {
+    /// The keyframes sorted by time
+    pub keyframes: Vec<Keyframe<NurbsCurve3D<T>>>,
+    /// The playback state
+    pub playback: MorphPlayback,
+    /// The number of samples per control point of the line strip
+    pub samples_per_control_point: usize,
+    compatible: Vec<NurbsCurve3D<T>>,
+    parameters: Vec<T>,
+}
+
+impl<T: FloatingPoint> NurbsCurveMorph<T> {
+    pub fn new(keyframes: Vec<Keyframe<NurbsCurve3D<T>>>) -> Self {
+        Self {
+            keyframes,
+            playback: Default::default(),
+            samples_per_control_point: 8,
+            compatible: vec![],
+            parameters: vec![],
+        }
+    }
+
+    /// Translates the control points of the keyframe shapes by the offset
+    pub fn translate(&mut self, offset: &Vector3<T>) {
+        self.keyframes.iter_mut().for_each(|k| {
+            let control_points = k
+                .shape
+                .control_points()
+                .iter()
+                .map(|p| translated(p, offset))
+                .collect();
+            if let Ok(curve) = NurbsCurve3D::try_new(
+                k.shape.degree(),
+                control_points,
+                k.shape.knots().as_slice().to_vec(),
+            ) {
+                k.shape = curve;
+            }
+        });
+    }
+}
+
+/// Component to morph the surface geometry of the entity between keyframed shapes
+///
+/// The keyframe shapes are made compatible by degree elevation and knot refinement when the component is changed,
+/// and the mesh is rewritten at the fixed (u, v) parameters and triangles of the first keyframe's tessellation every frame,
+/// with the `NurbsTessellation` settings and `NurbsVertexColors` of the entity applied.
+/// The geometry component is updated with change detection, but `NurbsGeometryPlugin` does not remesh morphed entities.
+///
+/// The keyframes are in the local space of the geometry, and `NurbsOriginRebase` translates them with the geometry,
+/// so insert the morph before or together with the marker.
+#[derive(Component, Debug, Clone)]
+pub struct NurbsSurfaceMorph<T: FloatingPoint = f32> {
+    /// The keyframes sorted by time
+    pub keyframes: Vec<Keyframe<ControlNet<T, Const<4>>>>,
+    /// The playback state
+    pub playback: MorphPlayback,
+    /// The normal tolerance of the tessellation fixing the mesh topology.
+    /// If None, the surface tessellation of the entity's `NurbsTessellation` is used
+    pub norm_tolerance: Option<T>,
+    compatible: Vec<ControlNet<T, Const<4>>>,
+    uvs: Vec<Vector2<T>>,
+    faces: Vec<[usize; 3]>,
+}
+
+impl<T: FloatingPoint> NurbsSurfaceMorph<T> {
+    pub fn new(keyframes: Vec<Keyframe<ControlNet<T, Const<4>>>>) -> Self {
+        Self {
+            keyframes,
+            playback: Default::default(),
+            norm_tolerance: None,
+            compatible: vec![],
+            uvs: vec![],
+            faces: vec![],
+        }
+    }
+
+    /// Translates the control points of the keyframe shapes by the offset
+    pub fn translate(&mut self, offset: &Vector3<T>) {
+        self.keyframes.iter_mut().for_each(|k| {
+            let control_points = k
+                .shape
+                .control_points()
+                .iter()
+                .map(|row| row.iter().map(|p| translated(p, offset)).collect())
+                .collect();
+            if let Ok(surface) = ControlNet::try_new(
+                k.shape.u_degree(),
+                k.shape.v_degree(),
+                k.shape.u_knots().to_vec(),
+                k.shape.v_knots().to_vec(),
+                control_points,
+            ) {
+                k.shape = surface;
+            }
+        });
+    }
+}
+
+/// System set in which morphs are prepared and evaluated
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NurbsMorphSet;
+
+/// Plugin to animate NURBS geometry components between keyframed shapes
+pub struct NurbsMorphPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for NurbsMorphPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for NurbsMorphPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            PostUpdate,
+            (
+                (prepare_curve_morphs::<T>, prepare_surface_morphs::<T>),
+                (morph_curves::<T>, morph_surfaces::<T>),
+            )
+                .chain()
+                .in_set(NurbsMorphSet)
+                .after(NurbsMeshSet),
+        );
+    }
+}
+
+/// Makes the keyframes of changed curve morphs compatible and sets up their meshes
+#[allow(clippy::type_complexity)]
+pub fn prepare_curve_morphs<T: FloatingPoint>(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut morphs: Query<
+        (&mut NurbsCurveMorph<T>, &Handle<Mesh>),
+        Or<(Changed<NurbsCurveMorph<T>>, Changed<Handle<Mesh>>)>,
+    >,
+) {
+    morphs.iter_mut().for_each(|(mut morph, handle)| {
+        let morph = morph.bypass_change_detection();
+        morph.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let shapes = morph
+            .keyframes
+            .iter()
+            .map(|k| k.shape.clone())
+            .collect::<Vec<_>>();
+        morph.compatible = try_make_curves_compatible(&shapes).unwrap_or_default();
+        let Some(first) = morph.compatible.first() else {
+            return;
+        };
+
+        let n = (first.control_points().len() * morph.samples_per_control_point).max(2);
+        morph.parameters = (0..n)
+            .map(|i| T::from_usize(i).unwrap() / T::from_usize(n - 1).unwrap())
+            .collect();
+        let points = morph
+            .parameters
+            .iter()
+            .map(|t| first.point_at(*t))
+            .collect();
+        NurbsCurveMesh::new(points).update_line_strip(&mut meshes, handle);
+    });
+}
+
+/// Makes the keyframes of changed surface morphs compatible and fixes the parameters and triangles of their meshes
+#[allow(clippy::type_complexity)]
+pub fn prepare_surface_morphs<T: FloatingPoint>(
+    mut morphs: Query<
+        (&mut NurbsSurfaceMorph<T>, Option<&NurbsTessellation<T>>),
+        Or<(
+            Changed<NurbsSurfaceMorph<T>>,
+            Changed<NurbsTessellation<T>>,
+            Changed<Handle<Mesh>>,
+        )>,
+    >,
+) {
+    morphs.iter_mut().for_each(|(mut morph, tessellation)| {
+        let morph = morph.bypass_change_detection();
+        morph.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        let shapes = morph
+            .keyframes
+            .iter()
+            .map(|k| k.shape.clone())
+            .collect::<Vec<_>>();
+        morph.compatible = try_make_surfaces_compatible(&shapes).unwrap_or_default();
+        let Some(first) = morph.compatible.first() else {
+            return;
+        };
+
+        let grid = tessellation
+            .and_then(|t| t.surface_grid)
+            .filter(|_| morph.norm_tolerance.is_none());
+        (morph.uvs, morph.faces) = match grid {
+            Some(grid) => {
+                let grid = GridTessellation::new(first, &grid);
+                (grid.uvs().clone(), grid.faces().clone())
+            }
+            None => {
+                let options = match morph.norm_tolerance {
+                    Some(norm_tolerance) => Some(AdaptiveTessellationOptions {
+                        norm_tolerance,
+                        ..Default::default()
+                    }),
+                    None => tessellation.and_then(|t| t.surface_options()),
+                };
+                let tessellation = first.tessellate(options);
+                (tessellation.uvs().clone(), tessellation.faces().clone())
+            }
+        };
+    });
+}
+
+/// Interpolates the control points of the compatible keyframes
+fn interpolate_points<T: FloatingPoint>(
+    a: &[OPoint<T, Const<4>>],
+    b: &[OPoint<T, Const<4>>],
+    f: T,
+) -> Vec<OPoint<T, Const<4>>> {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| OPoint::from(a.coords * (T::one() - f) + b.coords * f))
+        .collect()
+}
+
+/// Evaluates curve morphs, updating the geometry and writing the mesh vertices in place
+pub fn morph_curves<T: FloatingPoint>(
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut morphs: Query<(
+        &mut NurbsCurveMorph<T>,
+        &mut NurbsCurveGeometry<T>,
+        &Handle<Mesh>,
+    )>,
+) {
+    morphs
+        .iter_mut()
+        .for_each(|(mut morph, mut geometry, handle)| {
+            let morph = morph.bypass_change_detection();
+            let Some((i, f)) = morph
+                .playback
+                .advance(&morph.keyframes, time.delta_seconds())
+            else {
+                return;
+            };
+            let Some(a) = morph.compatible.get(i) else {
+                return;
+            };
+            let b = morph.compatible.get(i + 1).unwrap_or(a);
+            let points = interpolate_points(
+                a.control_points(),
+                b.control_points(),
+                T::from_f32(f).unwrap(),
+            );
+            let Ok(curve) =
+                NurbsCurve3D::try_new(a.degree(), points, a.knots().as_slice().to_vec())
+            else {
+                return;
+            };
+
+            if let Some(VertexAttributeValues::Float32x3(positions)) = meshes
+                .get_mut(handle)
+                .and_then(|m| m.attribute_mut(Mesh::ATTRIBUTE_POSITION))
+            {
+                positions
+                    .iter_mut()
+                    .zip(morph.parameters.iter())
+                    .for_each(|(v, t)| {
+                        let p = curve.point_at(*t);
+                        *v = [
+                            p.x.to_f32().unwrap(),
+                            p.y.to_f32().unwrap(),
+                            p.z.to_f32().unwrap(),
+                        ];
+                    });
+            }
+
+            geometry.0 = curve;
+        });
+}
+
+/// Evaluates surface morphs, updating the geometry and rewriting the mesh at the fixed parameters
+///
+/// The tessellation settings, vertex colors, displacement and scalar legend of the entity are updated as in `update_surface_meshes`.
+/// Meshes failing the validation are left as they are and a warning is logged.
+#[allow(clippy::type_complexity)]
+pub fn morph_surfaces<T: FloatingPoint>(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut morphs: Query<(
+        Entity,
+        &mut NurbsSurfaceMorph<T>,
+        &mut NurbsSurfaceGeometry<T>,
+        Option<&NurbsTessellation<T>>,
+        Option<&NurbsVertexColors<T>>,
+        Option<&NurbsDisplacement<T>>,
+        &Handle<Mesh>,
+    )>,
+) {
+    morphs.iter_mut().for_each(
+        |(e, mut morph, mut geometry, tessellation, colors, displacement, handle)| {
+            let morph = morph.bypass_change_detection();
+            let Some((i, f)) = morph
+                .playback
+                .advance(&morph.keyframes, time.delta_seconds())
+            else {
+                return;
+            };
+            let Some(a) = morph.compatible.get(i) else {
+                return;
+            };
+            let b = morph.compatible.get(i + 1).unwrap_or(a);
+            let f = T::from_f32(f).unwrap();
+            let points = a
+                .control_points()
+                .iter()
+                .zip(b.control_points().iter())
+                .map(|(a, b)| interpolate_points(a, b, f))
+                .collect();
+            let Ok(surface) = ControlNet::try_new(
+                a.u_degree(),
+                a.v_degree(),
+                a.u_knots().to_vec(),
+                a.v_knots().to_vec(),
+                points,
+            ) else {
+                return;
+            };
+
+            let mesh = NurbsSurfaceMesh::from_parameters(&surface, &morph.uvs, &morph.faces);
+            let mesh = with_surface_settings(mesh, &surface, tessellation, colors, displacement);
+            let repair = tessellation.map(|t| t.repair).unwrap_or_default();
+            match mesh.try_update_surface_triangle_list(&mut meshes, handle, &repair) {
+                Ok(validation) => update_scalar_legend(&mut commands, e, validation.legend),
+                Err(err) => warn!("failed to mesh the morphed surface of {:?}: {}", e, err),
+            }
+
+            geometry.0 = surface;
+        },
+    );
+}
@@ -0,0 +1,266 @@
+use bevy::{
+    asset::{Assets, Handle},
+    render::{
+        mesh::{Mesh, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+    },
+    transform::components::Transform,
+};
+use curvo::prelude::{FloatingPoint, NurbsCurve};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, OPoint, Vector3, U1,
+};
+
+use crate::{
+    floating_origin::origin_transform,
+    mesh_buffer::{retain_attributes, write_attribute},
+    mesh_validation::check_finite,
+    prelude::{CoordinateAttribute, MeshOrigin, MeshProjection, NurbsMeshError, PositionDim},
+};
+
+/// Bevy mesh generator for NURBS curves by curvo
+pub struct NurbsCurveMesh<T: FloatingPoint, D: DimName>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    points: Vec<OPoint<T, D>>,
+    origin: MeshOrigin<T>,
+    projection: MeshProjection<T>,
+    attributes: Vec<CoordinateAttribute>,
+}
+
+impl<T: FloatingPoint, D: PositionDim> NurbsCurveMesh<T, D>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Creates a curve mesh generator from the tessellated points of a curve with up to three coordinates
+    ///
+    /// Points with more coordinates need an explicit projection given to `with_projection`.
+    pub fn new(points: Vec<OPoint<T, D>>) -> Self {
+        Self {
+            points,
+            origin: MeshOrigin::default(),
+            projection: MeshProjection::Identity,
+            attributes: vec![],
+        }
+    }
+
+    /// Creates a curve mesh generator by tessellating the curve
+    /// * `tolerance` - The tolerance for the tessellation. If None, curvo's default is used
+    pub fn from_curve<D2>(curve: &NurbsCurve<T, D2>, tolerance: Option<T>) -> Self
+    where
+        D2: DimName + DimNameSub<U1, Output = D>,
+        DefaultAllocator: Allocator<T, D2>,
+    {
+        Self::new(curve.tessellate(tolerance))
+    }
+}
+
+impl<T: FloatingPoint, D: DimName> NurbsCurveMesh<T, D>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    /// Creates a curve mesh generator projecting the tessellated points of a curve to positions
+    pub fn with_projection(
+        points: Vec<OPoint<T, D>>,
+        projection: MeshProjection<T>,
+    ) -> Result<Self, NurbsMeshError> {
+        projection.validate(D::dim())?;
+        Ok(Self {
+            points,
+            origin: MeshOrigin::default(),
+            projection,
+            attributes: vec![],
+        })
+    }
+
+    /// Adds a vertex attribute filled with coordinates of the points
+    pub fn with_coordinate_attribute(
+        mut self,
+        attribute: CoordinateAttribute,
+    ) -> Result<Self, NurbsMeshError> {
+        attribute.validate(D::dim())?;
+        self.attributes.push(attribute);
+        Ok(self)
+    }
+
+    /// Sets the origin subtracted from the positions before they are cast to f32
+    pub fn with_origin(mut self, origin: MeshOrigin<T>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Returns the origin subtracted from the positions
+    pub fn origin(&self) -> Vector3<T> {
+        self.origin.resolve(self.positions())
+    }
+
+    /// Returns the transform placing the mesh at its origin
+    pub fn origin_transform(&self) -> Transform {
+        origin_transform(&self.origin())
+    }
+
+    /// Returns the tessellated points of the curve
+    pub fn points(&self) -> &[OPoint<T, D>] {
+        &self.points
+    }
+
+    /// Builds a line strip mesh from the tessellated curve
+    /// * `asset_usage` - The asset usage for the mesh. If None, default usage is used
+    pub fn build_line_strip(&self, asset_usage: Option<RenderAssetUsages>) -> Mesh {
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::LineStrip,
+            asset_usage.unwrap_or_default(),
+        );
+        self.write_line_strip(&mut mesh);
+        mesh
+    }
+
+    /// Builds a line strip mesh from the tessellated curve, validating the points
+    /// * `asset_usage` - The asset usage for the mesh. If None, default usage is used
+    pub fn try_build_line_strip(
+        &self,
+        asset_usage: Option<RenderAssetUsages>,
+    ) -> Result<Mesh, NurbsMeshError> {
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::LineStrip,
+            asset_usage.unwrap_or_default(),
+        );
+        self.try_write_line_strip(&mut mesh)?;
+        Ok(mesh)
+    }
+
+    /// Writes the line strip of the tessellated curve into an existing mesh
+    /// * `mesh` - The mesh with the line strip topology. Its position buffer is reused when the format matches
+    pub fn write_line_strip(&self, mesh: &mut Mesh) {
+        let origin = self.origin();
+        self.write_buffers(mesh, self.positions().into_iter().map(|p| p - origin));
+    }
+
+    /// Validates the points and writes the line strip of the tessellated curve into an existing mesh
+    ///
+    /// The mesh is left untouched if the validation fails.
+    pub fn try_write_line_strip(&self, mesh: &mut Mesh) -> Result<(), NurbsMeshError> {
+        if self.points.len() < 2 {
+            return Err(NurbsMeshError::EmptyTessellation);
+        }
+        let origin = self.origin();
+        let positions: Vec<_> = self.positions().into_iter().map(|p| p - origin).collect();
+        check_finite(
+            Mesh::ATTRIBUTE_POSITION.name,
+            positions.iter().map(|p| p.as_slice()),
+        )?;
+        self.attributes
+            .iter()
+            .try_for_each(|a| a.check_finite(self.points.iter().map(|p| p.coords.as_slice())))?;
+        self.write_buffers(mesh, positions.into_iter());
+        Ok(())
+    }
+
+    /// Updates the mesh asset of the handle with the line strip of the tessellated curve
+    ///
+    /// The mesh is written in place if it exists with the line strip topology, otherwise a new mesh is inserted at the handle.
+    pub fn update_line_strip(&self, meshes: &mut Assets<Mesh>, handle: &Handle<Mesh>) {
+        match meshes.get_mut(handle) {
+            Some(mesh) if mesh.primitive_topology() == PrimitiveTopology::LineStrip => {
+                self.write_line_strip(mesh);
+            }
+            _ => {
+                meshes.insert(handle, self.build_line_strip(None));
+            }
+        }
+    }
+
+    /// Validates the points and updates the mesh asset of the handle with the line strip
+    ///
+    /// The mesh asset is left untouched if the validation fails.
+    pub fn try_update_line_strip(
+        &self,
+        meshes: &mut Assets<Mesh>,
+        handle: &Handle<Mesh>,
+    ) -> Result<(), NurbsMeshError> {
+        match meshes.get_mut(handle) {
+            Some(mesh) if mesh.primitive_topology() == PrimitiveTopology::LineStrip => {
+                self.try_write_line_strip(mesh)
+            }
+            _ => {
+                meshes.insert(handle, self.try_build_line_strip(None)?);
+                Ok(())
+            }
+        }
+    }
+
+    /// Writes the positions relative to the origin and the coordinate attributes
+    fn write_buffers(&self, mesh: &mut Mesh, positions: impl Iterator<Item = Vector3<T>>) {
+        write_attribute(
+            mesh,
+            Mesh::ATTRIBUTE_POSITION,
+            positions.map(|p| {
+                [
+                    p.x.to_f32().unwrap(),
+                    p.y.to_f32().unwrap(),
+                    p.z.to_f32().unwrap(),
+                ]
+            }),
+        );
+        self.attributes.iter().for_each(|a| {
+            mesh.insert_attribute(
+                a.attribute.clone(),
+                a.values(self.points.iter().map(|p| p.coords.as_slice())),
+            );
+        });
+        retain_attributes(
+            mesh,
+            std::iter::once(Mesh::ATTRIBUTE_POSITION.id)
+                .chain(self.attributes.iter().map(|a| a.attribute.id)),
+        );
+    }
+
+    /// Projects the tessellated points to positions
+    fn positions(&self) -> Vec<Vector3<T>> {
+        self.points
+            .iter()
+            .map(|p| self.projection.project(p.coords.as_slice()))
+            .collect()
+    }
+}
+
+impl<T: FloatingPoint, D: PositionDim> From<Vec<OPoint<T, D>>> for NurbsCurveMesh<T, D>
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn from(points: Vec<OPoint<T, D>>) -> Self {
+        Self::new(points)
+    }
+}
+
+impl<'a, T: FloatingPoint, D: DimName> From<&'a NurbsCurve<T, D>>
+    for NurbsCurveMesh<T, DimNameDiff<D, U1>>
+where
+    D: DimNameSub<U1>,
+    DimNameDiff<D, U1>: PositionDim,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    fn from(curve: &'a NurbsCurve<T, D>) -> Self {
+        Self::new(curve.tessellate(None))
+    }
+}
+
+impl<T: FloatingPoint, D: DimName> From<NurbsCurveMesh<T, D>> for Mesh
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn from(value: NurbsCurveMesh<T, D>) -> Self {
+        value.build_line_strip(None)
+    }
+}
+
+impl<'a, T: FloatingPoint, D: DimName> From<&'a NurbsCurveMesh<T, D>> for Mesh
+where
+    DefaultAllocator: Allocator<T, D>,
+{
+    fn from(value: &'a NurbsCurveMesh<T, D>) -> Self {
+        value.build_line_strip(None)
+    }
+}
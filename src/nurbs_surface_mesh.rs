@@ -1,9 +1,81 @@
-use bevy::render::{
-    mesh::{Indices, Mesh, PrimitiveTopology, VertexAttributeValues},
-    render_asset::RenderAssetUsages,
+use bevy::{
+    asset::{Assets, Handle},
+    render::{
+        mesh::{Mesh, PrimitiveTopology, VertexAttributeValues},
+        render_asset::RenderAssetUsages,
+    },
+    transform::components::Transform,
 };
-use curvo::prelude::{FloatingPoint, SurfaceTessellation};
-use nalgebra::{allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, U1};
+use curvo::prelude::{FloatingPoint, NurbsSurface, SurfaceTessellation};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, OPoint, OVector,
+    Vector2, Vector3, U1,
+};
+
+use crate::{
+    floating_origin::origin_transform,
+    knot_spans::{group_by_span, split_knot_spans},
+    mesh_buffer::{retain_attributes, write_attribute, write_indices},
+    mesh_optimization::TriangleBuffers,
+    mesh_projection::vertex_normals,
+    mesh_validation::{check_faces, check_finite, check_length, validate_triangles},
+    prelude::{
+        ControlNet, CoordinateAttribute, Displacement, GridTessellation, KnotSpans,
+        MeshOptimization, MeshOrigin, MeshProjection, MeshRepair, MeshValidation, NurbsMeshError,
+        PositionDim, ScalarFieldLegend, SurfaceTopology, VertexColorField, ATTRIBUTE_KNOT_SPAN,
+    },
+    surface_creases::{flatten, split_creases, SurfaceCreases},
+    surface_seams::close_seams,
+};
+
+/// Vertices and triangles of a tessellation, from curvo's adaptive tessellation or a grid
+struct Tessellation<T: FloatingPoint, D: DimName>
+where
+    D: DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    points: Vec<OPoint<T, DimNameDiff<D, U1>>>,
+    normals: Vec<OVector<T, DimNameDiff<D, U1>>>,
+    uvs: Vec<Vector2<T>>,
+    faces: Vec<[usize; 3]>,
+}
+
+impl<T: FloatingPoint, D: DimName> From<SurfaceTessellation<T, D>> for Tessellation<T, D>
+where
+    D: DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    fn from(tessellation: SurfaceTessellation<T, D>) -> Self {
+        Self {
+            points: tessellation.points().clone(),
+            normals: tessellation.normals().clone(),
+            uvs: tessellation
+                .uvs()
+                .iter()
+                .map(|uv| Vector2::new(uv[0], uv[1]))
+                .collect(),
+            faces: tessellation.faces().clone(),
+        }
+    }
+}
+
+impl<T: FloatingPoint, D: DimName> From<GridTessellation<T, D>> for Tessellation<T, D>
+where
+    D: DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    fn from(grid: GridTessellation<T, D>) -> Self {
+        Self {
+            points: grid.points().clone(),
+            normals: grid.normals().clone(),
+            uvs: grid.uvs().clone(),
+            faces: grid.faces().clone(),
+        }
+    }
+}
 
 /// Bevy mesh generator for NURBS surfaces by curvo
 pub struct NurbsSurfaceMesh<T: FloatingPoint, D: DimName>
@@ -12,17 +84,196 @@ where
     DefaultAllocator: Allocator<T, D>,
     DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
 {
-    tessellation: SurfaceTessellation<T, D>,
+    tessellation: Tessellation<T, D>,
+    origin: MeshOrigin<T>,
+    projection: MeshProjection<T>,
+    attributes: Vec<CoordinateAttribute>,
+    optimization: Option<MeshOptimization<T>>,
+    seams: Option<(NurbsSurface<T, D>, SurfaceTopology)>,
+    creases: Option<SurfaceCreases<T, D>>,
+    spans: Option<(NurbsSurface<T, D>, KnotSpans<T>)>,
+    flat_shading: bool,
+    colors: Option<VertexColorField<T>>,
+    displacement: Option<(NurbsSurface<T, D>, Displacement<T>)>,
 }
 
 impl<T: FloatingPoint, D: DimName> NurbsSurfaceMesh<T, D>
 where
     D: DimNameSub<U1>,
+    DimNameDiff<D, U1>: PositionDim,
     DefaultAllocator: Allocator<T, D>,
     DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
 {
+    /// Creates a surface mesh generator from a tessellation with up to three coordinates
+    ///
+    /// Points with more coordinates need an explicit projection given to `with_projection`.
     pub fn new(tessellation: SurfaceTessellation<T, D>) -> Self {
-        Self { tessellation }
+        Self::from_parts(tessellation.into(), MeshProjection::Identity)
+    }
+
+    /// Creates a surface mesh generator from a grid tessellation with up to three coordinates
+    ///
+    /// Points with more coordinates need an explicit projection given to `from_grid_with_projection`.
+    pub fn from_grid(grid: GridTessellation<T, D>) -> Self {
+        Self::from_parts(grid.into(), MeshProjection::Identity)
+    }
+
+    /// Creates a surface mesh generator evaluating the surface at fixed parameters with fixed triangles
+    ///
+    /// Used to keep the topology of a tessellation while the control points of the surface change.
+    pub(crate) fn from_parameters(
+        surface: &NurbsSurface<T, D>,
+        uvs: &[Vector2<T>],
+        faces: &[[usize; 3]],
+    ) -> Self {
+        let tessellation = Tessellation {
+            points: uvs.iter().map(|uv| surface.point_at(uv.x, uv.y)).collect(),
+            // oriented as the normals of curvo's tessellation
+            normals: uvs
+                .iter()
+                .map(|uv| -surface.normal_at(uv.x, uv.y))
+                .collect(),
+            uvs: uvs.to_vec(),
+            faces: faces.to_vec(),
+        };
+        Self::from_parts(tessellation, MeshProjection::Identity)
+    }
+}
+
+impl<T: FloatingPoint, D: DimName> NurbsSurfaceMesh<T, D>
+where
+    D: DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    /// Creates a surface mesh generator projecting the points of the tessellation to positions
+    ///
+    /// Normals are recomputed from the projected triangles unless the projection is the identity.
+    pub fn with_projection(
+        tessellation: SurfaceTessellation<T, D>,
+        projection: MeshProjection<T>,
+    ) -> Result<Self, NurbsMeshError> {
+        projection.validate(DimNameDiff::<D, U1>::dim())?;
+        Ok(Self::from_parts(tessellation.into(), projection))
+    }
+
+    /// Creates a surface mesh generator projecting the points of a grid tessellation to positions
+    ///
+    /// The vertices keep the order of the lattice unless the optimization reorders them.
+    pub fn from_grid_with_projection(
+        grid: GridTessellation<T, D>,
+        projection: MeshProjection<T>,
+    ) -> Result<Self, NurbsMeshError> {
+        projection.validate(DimNameDiff::<D, U1>::dim())?;
+        Ok(Self::from_parts(grid.into(), projection))
+    }
+
+    fn from_parts(tessellation: Tessellation<T, D>, projection: MeshProjection<T>) -> Self {
+        Self {
+            tessellation,
+            origin: MeshOrigin::default(),
+            projection,
+            attributes: vec![],
+            optimization: None,
+            seams: None,
+            creases: None,
+            spans: None,
+            flat_shading: false,
+            colors: None,
+            displacement: None,
+        }
+    }
+
+    /// Adds a vertex attribute filled with coordinates of the points
+    pub fn with_coordinate_attribute(
+        mut self,
+        attribute: CoordinateAttribute,
+    ) -> Result<Self, NurbsMeshError> {
+        attribute.validate(DimNameDiff::<D, U1>::dim())?;
+        self.attributes.push(attribute);
+        Ok(self)
+    }
+
+    /// Sets the origin subtracted from the positions before they are cast to f32
+    pub fn with_origin(mut self, origin: MeshOrigin<T>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Sets the optimization applied to the triangle list when it is written
+    pub fn with_optimization(mut self, optimization: MeshOptimization<T>) -> Self {
+        self.optimization = Some(optimization);
+        self
+    }
+
+    /// Closes the seams and poles of the tessellated surface
+    ///
+    /// Vertices on the seams of closed directions share their positions and average their normals,
+    /// and vertices on degenerate edges get normals from the derivatives around the pole.
+    /// * `surface` - The control net of the surface the tessellation was made from
+    /// * `tolerance` - The distance within which control points are considered coincident
+    pub fn with_seams(mut self, surface: &ControlNet<T, D>, tolerance: T) -> Self {
+        let topology = SurfaceTopology::detect(surface, tolerance);
+        self.seams = (!topology.is_open()).then(|| (surface.surface().clone(), topology));
+        self
+    }
+
+    /// Splits the mesh along the C0 knot lines of the surface so that creases render sharp
+    ///
+    /// Triangles crossing a knot line are cut along it, and the vertices on it are duplicated per side
+    /// with the normal of the piece of the surface on that side.
+    /// The mesh is left smooth if the surface cannot be split at its creases.
+    /// * `surface` - The control net of the surface the tessellation was made from
+    pub fn with_creases(mut self, surface: &ControlNet<T, D>) -> Self {
+        self.creases = SurfaceCreases::try_detect(surface).ok().flatten();
+        self
+    }
+
+    /// Splits the mesh along all knot lines of the surface and writes the knot span of each vertex
+    ///
+    /// The spans are written to `ATTRIBUTE_KNOT_SPAN` and used by `build_knot_span_meshes`.
+    /// * `surface` - The surface the tessellation was made from
+    pub fn with_knot_spans(mut self, surface: &ControlNet<T, D>) -> Self {
+        self.spans = Some((surface.surface().clone(), KnotSpans::detect(surface)));
+        self
+    }
+
+    /// Gives every triangle its own vertices with the normal of its plane
+    pub fn with_flat_shading(mut self, flat_shading: bool) -> Self {
+        self.flat_shading = flat_shading;
+        self
+    }
+
+    /// Displaces the vertices along their normals, recomputing the normals from the displaced triangles
+    ///
+    /// Vertices split along creases and knot spans share one averaged normal so that the mesh stays closed,
+    /// and the triangles are flattened after the displacement.
+    /// * `surface` - The control net of the surface the tessellation was made from, whose knot domain the height maps cover
+    pub fn with_displacement(
+        mut self,
+        surface: &ControlNet<T, D>,
+        displacement: Displacement<T>,
+    ) -> Self {
+        self.displacement = Some((surface.surface().clone(), displacement));
+        self
+    }
+
+    /// Writes vertex colors evaluated from the uv, position and normal of each vertex to `ATTRIBUTE_COLOR`
+    ///
+    /// Positions are given before the origin is subtracted.
+    pub fn with_vertex_colors(mut self, colors: VertexColorField<T>) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+
+    /// Returns the origin subtracted from the positions
+    pub fn origin(&self) -> Vector3<T> {
+        self.origin.resolve(self.positions())
+    }
+
+    /// Returns the transform placing the mesh at its origin
+    pub fn origin_transform(&self) -> Transform {
+        origin_transform(&self.origin())
     }
 
     /// Builds a triangle list mesh from the NURBS surface
@@ -32,45 +283,144 @@ where
             PrimitiveTopology::TriangleList,
             asset_usage.unwrap_or_default(),
         );
+        self.write_surface_triangle_list(&mut mesh);
+        mesh
+    }
 
-        let to_array = Self::to_array_helper();
-
-        let vertices = self
-            .tessellation
-            .points()
-            .iter()
-            .map(|v| to_array(v.coords.as_slice()))
-            .collect();
-        let normals = self
-            .tessellation
-            .normals()
-            .iter()
-            .map(|n| to_array(n.as_slice()))
-            .collect();
-        let uvs = self
-            .tessellation
-            .uvs()
-            .iter()
-            .map(|uv| [uv[0].to_f32().unwrap(), uv[1].to_f32().unwrap()])
-            .collect();
-        let indices = self
-            .tessellation
-            .faces()
-            .iter()
-            .flat_map(|f| f.iter().map(|i| *i as u32))
-            .collect();
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            VertexAttributeValues::Float32x3(vertices),
-        );
-        mesh.insert_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            VertexAttributeValues::Float32x3(normals),
+    /// Builds a triangle list mesh from the NURBS surface, validating the tessellation
+    /// * `asset_usage` - The asset usage for the mesh. If None, default usage is used
+    /// * `repair` - The repairs applied to degenerate triangles and zero normals
+    pub fn try_build_surface_triangle_list(
+        &self,
+        asset_usage: Option<RenderAssetUsages>,
+        repair: &MeshRepair,
+    ) -> Result<Mesh, NurbsMeshError> {
+        let mut mesh = Mesh::new(
+            PrimitiveTopology::TriangleList,
+            asset_usage.unwrap_or_default(),
         );
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, VertexAttributeValues::Float32x2(uvs));
-        mesh.insert_indices(Indices::U32(indices));
+        self.try_write_surface_triangle_list(&mut mesh, repair)?;
+        Ok(mesh)
+    }
 
-        mesh
+    /// Writes the triangle list of the NURBS surface into an existing mesh
+    /// * `mesh` - The mesh with the triangle list topology. Its attribute and index buffers are reused when the formats match
+    pub fn write_surface_triangle_list(&self, mesh: &mut Mesh) {
+        let (positions, normals, uvs) = self.vertex_buffers();
+        let mut buffers =
+            TriangleBuffers::new(positions, normals, uvs, self.tessellation.faces.to_vec());
+        self.shade(&mut buffers);
+        if let Some(optimization) = &self.optimization {
+            buffers.optimize(optimization);
+        }
+        self.write_buffers(mesh, &buffers, None);
+    }
+
+    /// Validates the tessellation and writes its triangle list into an existing mesh
+    ///
+    /// The mesh is left untouched if the validation fails.
+    /// Returns the report of the degenerate triangles and zero normals found before the repairs,
+    /// the statistics of the optimization if it is set and the legend of scalar vertex colors.
+    pub fn try_write_surface_triangle_list(
+        &self,
+        mesh: &mut Mesh,
+        repair: &MeshRepair,
+    ) -> Result<MeshValidation, NurbsMeshError> {
+        let vertex_count = self.tessellation.points.len();
+        check_faces(&self.tessellation.faces, vertex_count)?;
+        check_length(
+            Mesh::ATTRIBUTE_NORMAL.name,
+            vertex_count,
+            self.tessellation.normals.len(),
+        )?;
+        check_length(
+            Mesh::ATTRIBUTE_UV_0.name,
+            vertex_count,
+            self.tessellation.uvs.len(),
+        )?;
+
+        let (positions, mut normals, uvs) = self.vertex_buffers();
+        let mut faces = self.tessellation.faces.to_vec();
+        let mut validation = validate_triangles(&positions, &mut normals, &mut faces, repair);
+        if faces.is_empty() {
+            return Err(NurbsMeshError::EmptyTessellation);
+        }
+
+        let mut buffers = TriangleBuffers::new(positions, normals, uvs, faces);
+        self.shade(&mut buffers);
+        validation.statistics = self
+            .optimization
+            .as_ref()
+            .map(|optimization| buffers.optimize(optimization));
+        self.check_buffers(&buffers)?;
+        validation.legend = self.write_buffers(mesh, &buffers, None);
+        Ok(validation)
+    }
+
+    /// Builds a triangle list mesh per Bézier patch of the surface, keyed by the indices of its u and v knot spans
+    ///
+    /// The mesh is split only if `with_knot_spans` is set, otherwise the whole surface is in the first span.
+    /// Scalar vertex colors share the range resolved over the whole surface.
+    /// * `asset_usage` - The asset usage for the meshes. If None, default usage is used
+    pub fn build_knot_span_meshes(
+        &self,
+        asset_usage: Option<RenderAssetUsages>,
+    ) -> Vec<([u32; 2], Mesh)> {
+        let (positions, normals, uvs) = self.vertex_buffers();
+        let mut buffers =
+            TriangleBuffers::new(positions, normals, uvs, self.tessellation.faces.to_vec());
+        self.shade(&mut buffers);
+        let legend = self.legend(&buffers);
+        group_by_span(&buffers)
+            .into_iter()
+            .map(|(span, mut buffers)| {
+                if let Some(optimization) = &self.optimization {
+                    buffers.optimize(optimization);
+                }
+                let mut mesh = Mesh::new(
+                    PrimitiveTopology::TriangleList,
+                    asset_usage.unwrap_or_default(),
+                );
+                self.write_buffers(&mut mesh, &buffers, legend.as_ref());
+                (span, mesh)
+            })
+            .collect()
+    }
+
+    /// Updates the mesh asset of the handle with the triangle list of the NURBS surface
+    ///
+    /// The mesh is written in place if it exists with the triangle list topology, otherwise a new mesh is inserted at the handle.
+    pub fn update_surface_triangle_list(&self, meshes: &mut Assets<Mesh>, handle: &Handle<Mesh>) {
+        match meshes.get_mut(handle) {
+            Some(mesh) if mesh.primitive_topology() == PrimitiveTopology::TriangleList => {
+                self.write_surface_triangle_list(mesh);
+            }
+            _ => {
+                meshes.insert(handle, self.build_surface_triangle_list(None));
+            }
+        }
+    }
+
+    /// Validates the tessellation and updates the mesh asset of the handle with its triangle list
+    ///
+    /// The mesh asset is left untouched if the validation fails.
+    pub fn try_update_surface_triangle_list(
+        &self,
+        meshes: &mut Assets<Mesh>,
+        handle: &Handle<Mesh>,
+        repair: &MeshRepair,
+    ) -> Result<MeshValidation, NurbsMeshError> {
+        match meshes.get_mut(handle) {
+            Some(mesh) if mesh.primitive_topology() == PrimitiveTopology::TriangleList => {
+                self.try_write_surface_triangle_list(mesh, repair)
+            }
+            _ => {
+                let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, Default::default());
+                let validation = self.try_write_surface_triangle_list(&mut mesh, repair)?;
+                meshes.insert(handle, mesh);
+                Ok(validation)
+            }
+        }
     }
 
     /// Builds a line list mesh from the normals
@@ -85,24 +435,20 @@ where
             bevy::render::mesh::PrimitiveTopology::LineList,
             asset_usage.unwrap_or_default(),
         );
-        let normals = self.tessellation.normals();
-
-        let to_array = Self::to_array_helper();
+        let origin = self.origin();
+        let positions = self.positions();
+        let normals = self.normals(&positions);
 
-        let vertices = self
-            .tessellation
-            .points()
+        let vertices = positions
             .iter()
-            .enumerate()
-            .flat_map(|(i, p)| {
-                let n = &normals[i];
+            .zip(normals.iter())
+            .flat_map(|(p, n)| {
                 let n = match normal_length {
                     Some(l) => n.normalize() * l,
-                    _ => n.clone(),
+                    _ => *n,
                 };
-                let p0 = to_array(p.coords.as_slice());
-                let p1 = to_array((p + n).coords.as_slice());
-                [p0, p1]
+                let p = p - origin;
+                [to_array(&p), to_array(&(p + n))]
             })
             .collect();
 
@@ -114,26 +460,203 @@ where
         line_list
     }
 
-    fn to_array_helper() -> impl Fn(&[T]) -> [f32; 3] {
-        match D::dim() {
-            1 => |slice: &[T]| -> [f32; 3] { [slice[0].to_f32().unwrap(), 0., 0.] },
-            2 => |slice: &[T]| -> [f32; 3] {
-                [slice[0].to_f32().unwrap(), slice[1].to_f32().unwrap(), 0.]
-            },
-            _ => |slice: &[T]| -> [f32; 3] {
-                [
-                    slice[0].to_f32().unwrap(),
-                    slice[1].to_f32().unwrap(),
-                    slice[2].to_f32().unwrap(),
-                ]
-            },
+    /// Checks that the final buffers have a value per vertex, addressable indices and values castable to finite f32
+    ///
+    /// Splitting and flat shading add vertices after the tessellation is validated, so the limits are checked again here.
+    fn check_buffers(&self, buffers: &TriangleBuffers<T>) -> Result<(), NurbsMeshError> {
+        let vertex_count = buffers.positions.len();
+        check_faces(&buffers.faces, vertex_count)?;
+        check_length(
+            Mesh::ATTRIBUTE_NORMAL.name,
+            vertex_count,
+            buffers.normals.len(),
+        )?;
+        check_length(Mesh::ATTRIBUTE_UV_0.name, vertex_count, buffers.uvs.len())?;
+        check_finite(
+            Mesh::ATTRIBUTE_POSITION.name,
+            buffers.positions.iter().map(|p| p.as_slice()),
+        )?;
+        check_finite(
+            Mesh::ATTRIBUTE_NORMAL.name,
+            buffers.normals.iter().map(|n| n.as_slice()),
+        )?;
+        check_finite(
+            Mesh::ATTRIBUTE_UV_0.name,
+            buffers.uvs.iter().map(|uv| uv.as_slice()),
+        )?;
+        let points = &self.tessellation.points;
+        self.attributes.iter().try_for_each(|a| {
+            a.check_finite(buffers.sources.iter().map(|i| points[*i].coords.as_slice()))
+        })
+    }
+
+    /// Writes the positions, normals, uvs, indices, colors and coordinate attributes of the buffers,
+    /// returning the legend of the scalar colors if any
+    /// * `legend` - The legend of scalar colors. If None, it is resolved over the buffers
+    fn write_buffers(
+        &self,
+        mesh: &mut Mesh,
+        buffers: &TriangleBuffers<T>,
+        legend: Option<&ScalarFieldLegend>,
+    ) -> Option<ScalarFieldLegend> {
+        write_attribute(
+            mesh,
+            Mesh::ATTRIBUTE_POSITION,
+            buffers.positions.iter().map(to_array),
+        );
+        write_attribute(
+            mesh,
+            Mesh::ATTRIBUTE_NORMAL,
+            buffers.normals.iter().map(to_array),
+        );
+        write_attribute(
+            mesh,
+            Mesh::ATTRIBUTE_UV_0,
+            buffers
+                .uvs
+                .iter()
+                .map(|uv| [uv.x.to_f32().unwrap(), uv.y.to_f32().unwrap()]),
+        );
+        let indices = buffers.faces.iter().flatten();
+        if buffers.compact_indices {
+            write_indices(mesh, indices.map(|i| *i as u16));
+        } else {
+            write_indices(mesh, indices.map(|i| *i as u32));
+        }
+        let mut written = vec![
+            Mesh::ATTRIBUTE_POSITION.id,
+            Mesh::ATTRIBUTE_NORMAL.id,
+            Mesh::ATTRIBUTE_UV_0.id,
+        ];
+        let mut scalar_legend = None;
+        if let Some(colors) = &self.colors {
+            let origin = self.origin();
+            let positions: Vec<_> = buffers.positions.iter().map(|p| p + origin).collect();
+            let (colors, legend) =
+                colors.colors(&buffers.uvs, &positions, &buffers.normals, legend);
+            write_attribute(mesh, Mesh::ATTRIBUTE_COLOR, colors);
+            written.push(Mesh::ATTRIBUTE_COLOR.id);
+            scalar_legend = legend;
+        }
+        if !buffers.spans.is_empty() {
+            write_attribute(mesh, ATTRIBUTE_KNOT_SPAN, buffers.spans.iter().copied());
+            written.push(ATTRIBUTE_KNOT_SPAN.id);
+        }
+        let points = &self.tessellation.points;
+        self.attributes.iter().for_each(|a| {
+            mesh.insert_attribute(
+                a.attribute.clone(),
+                a.values(buffers.sources.iter().map(|i| points[*i].coords.as_slice())),
+            );
+            written.push(a.attribute.id);
+        });
+        retain_attributes(mesh, written);
+        scalar_legend
+    }
+
+    /// Evaluates the legend of the scalar vertex colors over the buffers
+    fn legend(&self, buffers: &TriangleBuffers<T>) -> Option<ScalarFieldLegend> {
+        let colors = self.colors.as_ref()?;
+        let origin = self.origin();
+        let positions: Vec<_> = buffers.positions.iter().map(|p| p + origin).collect();
+        colors.legend(&buffers.uvs, &positions, &buffers.normals)
+    }
+
+    /// Splits the creases and knot spans, displaces the vertices and flattens the triangles if requested
+    fn shade(&self, buffers: &mut TriangleBuffers<T>) {
+        if let Some(creases) = &self.creases {
+            split_creases(
+                buffers,
+                creases,
+                &self.projection,
+                &self.origin(),
+                self.projection.preserves_normals(),
+            );
+        }
+        if let Some((surface, spans)) = &self.spans {
+            split_knot_spans(buffers, surface, spans, &self.projection, &self.origin());
+        }
+        if let Some((surface, displacement)) = &self.displacement {
+            displacement.apply(
+                buffers,
+                (surface.u_knots_domain(), surface.v_knots_domain()),
+            );
+        }
+        if self.flat_shading {
+            flatten(buffers);
+        }
+    }
+
+    /// Returns the positions relative to the origin, normals and uvs of the vertices with the seams closed
+    #[allow(clippy::type_complexity)]
+    fn vertex_buffers(&self) -> (Vec<Vector3<T>>, Vec<Vector3<T>>, Vec<Vector2<T>>) {
+        let origin = self.origin();
+        let mut positions: Vec<_> = self.positions().iter().map(|p| p - origin).collect();
+        let uvs = self.uvs();
+        let preserves_normals = self.projection.preserves_normals();
+        let mut normals = if preserves_normals {
+            self.normals(&positions)
+        } else {
+            vec![]
+        };
+        if let Some((surface, topology)) = &self.seams {
+            close_seams(
+                surface,
+                topology,
+                &self.projection,
+                &origin,
+                &uvs,
+                &mut positions,
+                &mut normals,
+                preserves_normals,
+            );
+        }
+        if !preserves_normals {
+            normals = self.normals(&positions);
+        }
+        (positions, normals, uvs)
+    }
+
+    /// Returns the uvs of the tessellation
+    fn uvs(&self) -> Vec<Vector2<T>> {
+        self.tessellation.uvs.clone()
+    }
+
+    /// Projects the points of the tessellation to positions
+    fn positions(&self) -> Vec<Vector3<T>> {
+        self.tessellation
+            .points
+            .iter()
+            .map(|p| self.projection.project(p.coords.as_slice()))
+            .collect()
+    }
+
+    /// Returns the normals of the tessellation, or recomputes them from the projected positions
+    fn normals(&self, positions: &[Vector3<T>]) -> Vec<Vector3<T>> {
+        if self.projection.preserves_normals() {
+            self.tessellation
+                .normals
+                .iter()
+                .map(|n| self.projection.project(n.as_slice()))
+                .collect()
+        } else {
+            vertex_normals(positions, &self.tessellation.faces)
         }
     }
 }
 
+fn to_array<T: FloatingPoint>(v: &Vector3<T>) -> [f32; 3] {
+    [
+        v.x.to_f32().unwrap(),
+        v.y.to_f32().unwrap(),
+        v.z.to_f32().unwrap(),
+    ]
+}
+
 impl<T: FloatingPoint, D: DimName> From<SurfaceTessellation<T, D>> for NurbsSurfaceMesh<T, D>
 where
     D: DimNameSub<U1>,
+    DimNameDiff<D, U1>: PositionDim,
     DefaultAllocator: Allocator<T, D>,
     DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
 {
@@ -142,6 +665,18 @@ where
     }
 }
 
+impl<T: FloatingPoint, D: DimName> From<GridTessellation<T, D>> for NurbsSurfaceMesh<T, D>
+where
+    D: DimNameSub<U1>,
+    DimNameDiff<D, U1>: PositionDim,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    fn from(grid: GridTessellation<T, D>) -> Self {
+        Self::from_grid(grid)
+    }
+}
+
 impl<T: FloatingPoint, D: DimName> From<NurbsSurfaceMesh<T, D>> for Mesh
 where
     D: DimNameSub<U1>,
@@ -0,0 +1,444 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        event::{Event, EventWriter},
+        query::Changed,
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, Res, Resource},
+    },
+    hierarchy::Parent,
+    math::{Mat3, Quat, Vec3},
+    time::Time,
+    transform::components::{GlobalTransform, Transform},
+    utils::HashSet,
+};
+use curvo::prelude::{FloatingPoint, NurbsCurve3D};
+
+use crate::{
+    derivatives::curve_derivatives,
+    geometry::to_vec3,
+    prelude::{ArcLengthTable, NurbsCurveGeometry},
+};
+
+/// How a follower behaves when it reaches the end of the curve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FollowMode {
+    /// Stops at the end of the curve
+    #[default]
+    Once,
+    /// Jumps back to the start of the curve
+    Loop,
+    /// Reverses the direction at both ends of the curve
+    PingPong,
+}
+
+/// How a follower is oriented along the curve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FollowOrientation {
+    /// Keeps the rotation of the follower
+    Fixed,
+    /// Looks along the tangent with the up vector hint
+    #[default]
+    Tangent,
+    /// Uses the frenet frame, falling back to the up vector hint where the curvature vanishes
+    Frenet,
+    /// Uses the rotation minimizing frame starting from the up vector hint
+    RotationMinimizing,
+}
+
+/// Marker on a curve that emits an event when a follower passes it
+#[derive(Debug, Clone, Copy)]
+pub struct CurveMarker<T: FloatingPoint = f32> {
+    /// The identifier reported by the event
+    pub id: usize,
+    /// The parameter of the curve at the marker
+    pub parameter: T,
+}
+
+/// Component to move an entity along a curve geometry at constant speed
+#[derive(Component, Debug, Clone)]
+pub struct FollowCurve<T: FloatingPoint = f32> {
+    /// The entity holding the curve geometry
+    pub curve: Entity,
+    /// The speed in arc length per second
+    pub speed: T,
+    /// The behavior at the end of the curve
+    pub mode: FollowMode,
+    /// The orientation of the follower
+    pub orientation: FollowOrientation,
+    /// The up vector hint in world space
+    pub up: Vec3,
+    /// The markers emitting events when passed
+    pub markers: Vec<CurveMarker<T>>,
+    /// The current arc length from the start of the curve
+    pub distance: T,
+    /// Whether the follower moves from the end to the start of the curve
+    pub reversed: bool,
+    /// Whether the follower is moving
+    pub playing: bool,
+}
+
+impl<T: FloatingPoint> FollowCurve<T> {
+    pub fn new(curve: Entity, speed: T) -> Self {
+        Self {
+            curve,
+            speed,
+            mode: Default::default(),
+            orientation: Default::default(),
+            up: Vec3::Y,
+            markers: vec![],
+            distance: T::zero(),
+            reversed: false,
+            playing: true,
+        }
+    }
+
+    pub fn with_mode(mut self, mode: FollowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_orientation(mut self, orientation: FollowOrientation, up: Vec3) -> Self {
+        self.orientation = orientation;
+        self.up = up;
+        self
+    }
+
+    pub fn with_markers(mut self, markers: Vec<CurveMarker<T>>) -> Self {
+        self.markers = markers;
+        self
+    }
+}
+
+/// Event sent when a follower passes a marker on the curve
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CurveMarkerReached<T: FloatingPoint = f32> {
+    /// The follower entity
+    pub follower: Entity,
+    /// The entity holding the curve geometry
+    pub curve: Entity,
+    /// The passed marker
+    pub marker: CurveMarker<T>,
+}
+
+/// Arc length table and rotation minimizing frames of a followed curve
+#[derive(Component, Debug, Clone)]
+pub struct FollowCurveCache<T: FloatingPoint = f32> {
+    table: ArcLengthTable<T>,
+    /// Reference normals of the rotation minimizing frames at the table parameters
+    normals: Vec<Vec3>,
+}
+
+impl<T: FloatingPoint> FollowCurveCache<T> {
+    /// Builds the arc length table and the rotation minimizing frames by double reflection
+    pub fn new(curve: &NurbsCurve3D<T>, segments: usize) -> Self {
+        let table = ArcLengthTable::new(curve, segments);
+        let frames = table
+            .parameters()
+            .iter()
+            .map(|t| {
+                (
+                    to_vec3(curve.point_at(*t).coords.as_slice()),
+                    tangent(curve, *t),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let (_, t0) = frames[0];
+        let r0 = t0.any_orthonormal_vector();
+        let normals = frames
+            .windows(2)
+            .scan(r0, |r, w| {
+                let ((x0, t0), (x1, t1)) = (w[0], w[1]);
+                let v1 = x1 - x0;
+                let c1 = v1.length_squared();
+                if c1 <= f32::EPSILON {
+                    return Some(*r);
+                }
+                let rl = *r - (2. / c1) * v1.dot(*r) * v1;
+                let tl = t0 - (2. / c1) * v1.dot(t0) * v1;
+                let v2 = t1 - tl;
+                let c2 = v2.length_squared();
+                *r = if c2 <= f32::EPSILON {
+                    rl
+                } else {
+                    rl - (2. / c2) * v2.dot(rl) * v2
+                };
+                Some(*r)
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            table,
+            normals: std::iter::once(r0).chain(normals).collect(),
+        }
+    }
+
+    /// Returns the arc length table
+    pub fn table(&self) -> &ArcLengthTable<T> {
+        &self.table
+    }
+
+    /// Returns the rotation minimizing reference normal at the arc length
+    fn normal_at_length(&self, length: T) -> Vec3 {
+        let lengths = self.table.lengths();
+        let i = lengths
+            .partition_point(|l| *l <= length)
+            .saturating_sub(1)
+            .min(lengths.len() - 2);
+        let span = lengths[i + 1] - lengths[i];
+        let f = if span > T::zero() {
+            ((length - lengths[i]) / span).to_f32().unwrap()
+        } else {
+            0.
+        };
+        self.normals[i].lerp(self.normals[i + 1], f)
+    }
+}
+
+/// System set in which followers are moved along curves
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FollowCurveSet;
+
+/// Plugin to move entities along curve geometries by arc length
+pub struct FollowCurvePlugin<T: FloatingPoint = f32> {
+    /// The number of segments of the arc length tables
+    pub segments: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: FloatingPoint> Default for FollowCurvePlugin<T> {
+    fn default() -> Self {
+        Self {
+            segments: 256,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// The number of segments of the arc length tables of curves followed with the scalar type `T`
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct FollowCurveSegments<T: FloatingPoint = f32> {
+    pub segments: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: FloatingPoint> FollowCurveSegments<T> {
+    pub fn new(segments: usize) -> Self {
+        Self {
+            segments,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: FloatingPoint> Plugin for FollowCurvePlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(FollowCurveSegments::<T>::new(self.segments))
+            .add_event::<CurveMarkerReached<T>>()
+            .add_systems(
+                Update,
+                (update_follow_curve_caches::<T>, follow_curves::<T>)
+                    .chain()
+                    .in_set(FollowCurveSet),
+            );
+    }
+}
+
+/// Builds the caches of followed curves when missing or when the geometry changes
+#[allow(clippy::type_complexity)]
+pub fn update_follow_curve_caches<T: FloatingPoint>(
+    mut commands: Commands,
+    segments: Res<FollowCurveSegments<T>>,
+    followers: Query<&FollowCurve<T>>,
+    curves: Query<(Entity, &NurbsCurveGeometry<T>, Option<&FollowCurveCache<T>>)>,
+    changed: Query<(), Changed<NurbsCurveGeometry<T>>>,
+) {
+    let followed: HashSet<Entity> = followers.iter().map(|f| f.curve).collect();
+    followed.into_iter().for_each(|e| {
+        if let Ok((e, curve, cache)) = curves.get(e) {
+            if cache.is_none() || changed.contains(e) {
+                commands
+                    .entity(e)
+                    .insert(FollowCurveCache::new(curve, segments.segments));
+            }
+        }
+    });
+}
+
+/// Moves followers along their curves and emits events for passed markers
+///
+/// The pose is computed in world space and converted into the local space of the follower's parent.
+pub fn follow_curves<T: FloatingPoint>(
+    time: Res<Time>,
+    mut followers: Query<(Entity, &mut FollowCurve<T>, &mut Transform, Option<&Parent>)>,
+    curves: Query<(
+        &NurbsCurveGeometry<T>,
+        &FollowCurveCache<T>,
+        &GlobalTransform,
+    )>,
+    parents: Query<&GlobalTransform>,
+    mut reached: EventWriter<CurveMarkerReached<T>>,
+) {
+    let dt = T::from_f32(time.delta_seconds()).unwrap();
+    followers
+        .iter_mut()
+        .for_each(|(e, mut follow, mut transform, parent)| {
+            let Ok((curve, cache, curve_transform)) = curves.get(follow.curve) else {
+                return;
+            };
+            let table = cache.table();
+            let total = table.length();
+
+            let previous = follow.distance;
+            let mut passed = vec![];
+            if follow.playing && total > T::zero() {
+                let step = follow.speed * dt;
+                let step = if follow.reversed { -step } else { step };
+                let next = previous + step;
+                match follow.mode {
+                    FollowMode::Once => {
+                        let next = nalgebra::clamp(next, T::zero(), total);
+                        passed.push((previous, next));
+                        follow.distance = next;
+                        if next <= T::zero() || next >= total {
+                            follow.playing = false;
+                        }
+                    }
+                    FollowMode::Loop => {
+                        let wrapped = wrap(next, total);
+                        if next > total {
+                            passed.push((previous, total));
+                            passed.push((T::zero(), wrapped));
+                        } else if next < T::zero() {
+                            passed.push((previous, T::zero()));
+                            passed.push((total, wrapped));
+                        } else {
+                            passed.push((previous, next));
+                        }
+                        follow.distance = wrapped;
+                    }
+                    FollowMode::PingPong => {
+                        if next > total {
+                            let bounced = total - (next - total);
+                            passed.push((previous, total));
+                            passed.push((total, bounced));
+                            follow.distance = nalgebra::clamp(bounced, T::zero(), total);
+                            follow.reversed = !follow.reversed;
+                        } else if next < T::zero() {
+                            let bounced = -next;
+                            passed.push((previous, T::zero()));
+                            passed.push((T::zero(), bounced));
+                            follow.distance = nalgebra::clamp(bounced, T::zero(), total);
+                            follow.reversed = !follow.reversed;
+                        } else {
+                            passed.push((previous, next));
+                            follow.distance = next;
+                        }
+                    }
+                }
+            }
+
+            follow.markers.iter().for_each(|marker| {
+                let l = table.length_at_parameter(curve, marker.parameter);
+                let crossed = passed.iter().any(|(from, to)| {
+                    if from < to {
+                        *from < l && l <= *to
+                    } else {
+                        *to <= l && l < *from
+                    }
+                });
+                if crossed {
+                    reached.send(CurveMarkerReached {
+                        follower: e,
+                        curve: follow.curve,
+                        marker: *marker,
+                    });
+                }
+            });
+
+            let parent = parent
+                .and_then(|p| parents.get(p.get()).ok())
+                .map(|p| p.affine().inverse());
+            let t = table.parameter_at_length(curve, follow.distance);
+            let position =
+                curve_transform.transform_point(to_vec3(curve.point_at(t).coords.as_slice()));
+            transform.translation = match parent {
+                Some(parent) => parent.transform_point3(position),
+                None => position,
+            };
+
+            let world_tangent = |v: Vec3| curve_transform.affine().transform_vector3(v);
+            let forward = world_tangent(tangent(curve, t));
+            let forward = if follow.reversed { -forward } else { forward };
+            let up = match follow.orientation {
+                FollowOrientation::Fixed => return,
+                FollowOrientation::Tangent => follow.up,
+                FollowOrientation::Frenet => {
+                    let derivs = curve_derivatives(curve, t, 2);
+                    let (d1, d2) = (to_vec3(derivs[1].as_slice()), to_vec3(derivs[2].as_slice()));
+                    let normal = world_tangent(d2 - d1 * d2.dot(d1) / d1.length_squared());
+                    let binormal = forward.cross(normal);
+                    if binormal.length_squared() <= f32::EPSILON {
+                        follow.up
+                    } else if binormal.dot(follow.up) < 0. {
+                        -binormal
+                    } else {
+                        binormal
+                    }
+                }
+                FollowOrientation::RotationMinimizing => {
+                    // rotate the reference frames so that the frame at the start meets the up hint
+                    let start = cache.normals[0];
+                    let t0 = tangent(curve, table.parameters()[0]);
+                    let hint = curve_transform
+                        .affine()
+                        .inverse()
+                        .transform_vector3(follow.up);
+                    let hint = hint - t0 * hint.dot(t0);
+                    let angle = if hint.length_squared() <= f32::EPSILON {
+                        0.
+                    } else {
+                        start.cross(hint).dot(t0).atan2(start.dot(hint))
+                    };
+                    let tangent = tangent(curve, t);
+                    let normal = cache.normal_at_length(follow.distance);
+                    world_tangent(Quat::from_axis_angle(tangent, angle) * normal)
+                }
+            };
+            if let Some(rotation) = look_to(forward, up) {
+                transform.rotation = match parent {
+                    Some(parent) => {
+                        let (_, parent_rotation, _) = parent.to_scale_rotation_translation();
+                        parent_rotation * rotation
+                    }
+                    None => rotation,
+                };
+            }
+        });
+}
+
+/// Returns the rotation looking along the direction with the up vector
+pub(crate) fn look_to(forward: Vec3, up: Vec3) -> Option<Quat> {
+    let back = -forward.try_normalize()?;
+    let right = up.cross(back).try_normalize()?;
+    let up = back.cross(right);
+    Some(Quat::from_mat3(&Mat3::from_cols(right, up, back)))
+}
+
+fn wrap<T: FloatingPoint>(value: T, total: T) -> T {
+    let r = value % total;
+    if r < T::zero() {
+        r + total
+    } else {
+        r
+    }
+}
+
+fn tangent<T: FloatingPoint>(curve: &NurbsCurve3D<T>, t: T) -> Vec3 {
+    to_vec3(curve.tangent_at(t).as_slice()).normalize_or_zero()
+}
@@ -0,0 +1,513 @@
+use std::f64::consts::{FRAC_PI_2, TAU};
+
+use bevy::{
+    render::mesh::{Mesh, Meshable},
+    transform::components::Transform,
+};
+use curvo::prelude::{AdaptiveTessellationOptions, FloatingPoint, NurbsCurve3D};
+use nalgebra::{Const, Point3, Point4, Vector2, Vector3};
+
+use crate::{
+    prelude::{
+        try_revolve, ControlNet, GridTessellation, GridTessellationOptions, MeshOrigin,
+        NurbsCurveMesh, NurbsSurfaceMesh, RevolutionAxis,
+    },
+    surfacing::{arc_divisions, arc_knots},
+};
+
+/// Exact rational circle in the XY plane centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsCircle<T: FloatingPoint = f32> {
+    pub radius: T,
+}
+
+/// Exact rational circular arc in the XY plane centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsArc<T: FloatingPoint = f32> {
+    pub radius: T,
+    /// The start angle from the X axis in radians
+    pub start_angle: T,
+    /// The end angle from the X axis in radians.
+    /// The arc runs counterclockwise from the start angle, wrapping around for end angles at or before it,
+    /// so equal angles give a full turn
+    pub end_angle: T,
+}
+
+/// Exact rational ellipse in the XY plane centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsEllipse<T: FloatingPoint = f32> {
+    /// The radii along the X and Y axes
+    pub half_size: Vector2<T>,
+}
+
+/// Rectangle polyline in the XY plane centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsRectangle<T: FloatingPoint = f32> {
+    /// The half lengths along the X and Y axes
+    pub half_size: Vector2<T>,
+}
+
+/// Exact rational sphere centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsSphere<T: FloatingPoint = f32> {
+    pub radius: T,
+}
+
+/// Exact rational cylinder along the Y axis centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsCylinder<T: FloatingPoint = f32> {
+    pub radius: T,
+    pub half_height: T,
+}
+
+/// Exact rational cone along the Y axis with the apex at the top, centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsCone<T: FloatingPoint = f32> {
+    /// The radius of the base
+    pub radius: T,
+    pub half_height: T,
+}
+
+/// Exact rational torus around the Y axis centered at the origin
+#[derive(Debug, Clone, Copy)]
+pub struct NurbsTorus<T: FloatingPoint = f32> {
+    /// The radius of the tube
+    pub minor_radius: T,
+    /// The distance from the center of the torus to the center of the tube
+    pub major_radius: T,
+}
+
+impl<T: FloatingPoint> NurbsCircle<T> {
+    pub fn new(radius: T) -> Self {
+        Self { radius }
+    }
+
+    pub fn curve(&self) -> NurbsCurve3D<T> {
+        ellipse_arc(Point3::origin(), (self.radius, self.radius), 0., TAU)
+    }
+}
+
+impl<T: FloatingPoint> NurbsArc<T> {
+    pub fn new(radius: T, start_angle: T, end_angle: T) -> Self {
+        Self {
+            radius,
+            start_angle,
+            end_angle,
+        }
+    }
+
+    pub fn curve(&self) -> NurbsCurve3D<T> {
+        ellipse_arc(
+            Point3::origin(),
+            (self.radius, self.radius),
+            self.start_angle.to_f64().unwrap(),
+            self.end_angle.to_f64().unwrap(),
+        )
+    }
+}
+
+impl<T: FloatingPoint> NurbsEllipse<T> {
+    pub fn new(half_width: T, half_height: T) -> Self {
+        Self {
+            half_size: Vector2::new(half_width, half_height),
+        }
+    }
+
+    pub fn curve(&self) -> NurbsCurve3D<T> {
+        ellipse_arc(
+            Point3::origin(),
+            (self.half_size.x, self.half_size.y),
+            0.,
+            TAU,
+        )
+    }
+}
+
+impl<T: FloatingPoint> NurbsRectangle<T> {
+    pub fn new(width: T, height: T) -> Self {
+        let two = T::one() + T::one();
+        Self {
+            half_size: Vector2::new(width / two, height / two),
+        }
+    }
+
+    pub fn curve(&self) -> NurbsCurve3D<T> {
+        let (x, y) = (self.half_size.x, self.half_size.y);
+        let control_points = [(-x, -y), (x, -y), (x, y), (-x, y), (-x, -y)]
+            .into_iter()
+            .map(|(x, y)| Point4::new(x, y, T::zero(), T::one()))
+            .collect();
+        let quarter = |i: usize| T::from_usize(i).unwrap() / T::from_usize(4).unwrap();
+        let knots = vec![
+            T::zero(),
+            T::zero(),
+            quarter(1),
+            quarter(2),
+            quarter(3),
+            T::one(),
+            T::one(),
+        ];
+        NurbsCurve3D::try_new(1, control_points, knots).unwrap()
+    }
+}
+
+impl<T: FloatingPoint> NurbsSphere<T> {
+    pub fn new(radius: T) -> Self {
+        Self { radius }
+    }
+
+    pub fn surface(&self) -> ControlNet<T, Const<4>> {
+        let profile = ellipse_arc(
+            Point3::origin(),
+            (self.radius, self.radius),
+            -FRAC_PI_2,
+            FRAC_PI_2,
+        );
+        revolve_around_y(&profile)
+    }
+}
+
+impl<T: FloatingPoint> NurbsCylinder<T> {
+    pub fn new(radius: T, height: T) -> Self {
+        Self {
+            radius,
+            half_height: height / (T::one() + T::one()),
+        }
+    }
+
+    pub fn surface(&self) -> ControlNet<T, Const<4>> {
+        let profile = line(
+            Point3::new(self.radius, -self.half_height, T::zero()),
+            Point3::new(self.radius, self.half_height, T::zero()),
+        );
+        revolve_around_y(&profile)
+    }
+}
+
+impl<T: FloatingPoint> NurbsCone<T> {
+    pub fn new(radius: T, height: T) -> Self {
+        Self {
+            radius,
+            half_height: height / (T::one() + T::one()),
+        }
+    }
+
+    pub fn surface(&self) -> ControlNet<T, Const<4>> {
+        let profile = line(
+            Point3::new(self.radius, -self.half_height, T::zero()),
+            Point3::new(T::zero(), self.half_height, T::zero()),
+        );
+        revolve_around_y(&profile)
+    }
+}
+
+impl<T: FloatingPoint> NurbsTorus<T> {
+    pub fn new(minor_radius: T, major_radius: T) -> Self {
+        Self {
+            minor_radius,
+            major_radius,
+        }
+    }
+
+    pub fn surface(&self) -> ControlNet<T, Const<4>> {
+        let profile = ellipse_arc(
+            Point3::new(self.major_radius, T::zero(), T::zero()),
+            (self.minor_radius, self.minor_radius),
+            0.,
+            TAU,
+        );
+        revolve_around_y(&profile)
+    }
+}
+
+/// Builds an elliptic arc in the XY plane from rational quadratic segments
+/// * `radii` - The radii along the X and Y axes
+/// * `start` - The start angle in radians
+/// * `end` - The end angle in radians, wrapped to within a full turn after the start angle
+fn ellipse_arc<T: FloatingPoint>(
+    center: Point3<T>,
+    radii: (T, T),
+    start: f64,
+    end: f64,
+) -> NurbsCurve3D<T> {
+    let (arcs, half) = arc_divisions(sweep(start, end));
+    let to_t = |v: f64| T::from_f64(v).unwrap();
+    let control_points = (0..=2 * arcs)
+        .map(|i| {
+            let a = start + half * i as f64;
+            let (scale, weight) = if i % 2 == 0 {
+                (1., 1.)
+            } else {
+                (1. / half.cos(), half.cos())
+            };
+            let w = to_t(weight);
+            Point4::new(
+                (center.x + radii.0 * to_t(a.cos() * scale)) * w,
+                (center.y + radii.1 * to_t(a.sin() * scale)) * w,
+                center.z * w,
+                w,
+            )
+        })
+        .collect();
+    NurbsCurve3D::try_new(2, control_points, arc_knots(arcs)).unwrap()
+}
+
+/// Returns the counterclockwise angle from the start to the end in (0, 2π], a full turn for equal angles
+fn sweep(start: f64, end: f64) -> f64 {
+    let sweep = end - start;
+    if sweep > 0. && sweep <= TAU {
+        return sweep;
+    }
+    match sweep.rem_euclid(TAU) {
+        s if s > 0. => s,
+        _ => TAU,
+    }
+}
+
+fn line<T: FloatingPoint>(start: Point3<T>, end: Point3<T>) -> NurbsCurve3D<T> {
+    NurbsCurve3D::try_new(
+        1,
+        vec![start.to_homogeneous().into(), end.to_homogeneous().into()],
+        vec![T::zero(), T::zero(), T::one(), T::one()],
+    )
+    .unwrap()
+}
+
+fn revolve_around_y<T: FloatingPoint>(profile: &NurbsCurve3D<T>) -> ControlNet<T, Const<4>> {
+    let axis = RevolutionAxis::new(Point3::origin(), Vector3::y());
+    try_revolve(profile, &axis, T::from_f64(TAU).unwrap()).unwrap()
+}
+
+/// Mesh builder for NURBS curve primitives
+#[derive(Debug, Clone)]
+pub struct NurbsCurveMeshBuilder<T: FloatingPoint = f32> {
+    pub curve: NurbsCurve3D<T>,
+    /// The tolerance for the tessellation. If None, curvo's default is used
+    pub tolerance: Option<T>,
+    /// The origin subtracted from the positions before they are cast to f32
+    pub origin: MeshOrigin<T>,
+}
+
+impl<T: FloatingPoint> NurbsCurveMeshBuilder<T> {
+    pub fn new(curve: NurbsCurve3D<T>) -> Self {
+        Self {
+            curve,
+            tolerance: None,
+            origin: MeshOrigin::default(),
+        }
+    }
+
+    /// Sets the tolerance for the tessellation
+    pub fn tolerance(mut self, tolerance: T) -> Self {
+        self.tolerance = Some(tolerance);
+        self
+    }
+
+    /// Sets the origin subtracted from the positions
+    pub fn origin(mut self, origin: MeshOrigin<T>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Builds a line strip mesh from the curve
+    pub fn build(&self) -> Mesh {
+        self.build_with_transform().0
+    }
+
+    /// Builds a line strip mesh from the curve with the transform placing it at its origin
+    pub fn build_with_transform(&self) -> (Mesh, Transform) {
+        let mesh = NurbsCurveMesh::from_curve(&self.curve, self.tolerance).with_origin(self.origin);
+        (mesh.build_line_strip(None), mesh.origin_transform())
+    }
+}
+
+/// Mesh builder for NURBS surface primitives
+#[derive(Debug, Clone)]
+pub struct NurbsSurfaceMeshBuilder<T: FloatingPoint = f32> {
+    pub surface: ControlNet<T, Const<4>>,
+    /// The normal tolerance for the adaptive tessellation. If None, curvo's default is used
+    pub norm_tolerance: Option<T>,
+    /// The grid tessellation with a lattice of stable topology. If Some, it replaces the adaptive tessellation
+    pub grid: Option<GridTessellationOptions>,
+    /// The origin subtracted from the positions before they are cast to f32
+    pub origin: MeshOrigin<T>,
+    /// The tolerance to detect the seams and poles of the surface. If None, seams are left as tessellated, which is the default
+    pub seam_tolerance: Option<T>,
+    /// Splits the mesh along the C0 knot lines of the surface so that creases render sharp. False by default
+    pub creases: bool,
+    /// Splits the mesh along all knot lines and writes the knot span of each vertex to `ATTRIBUTE_KNOT_SPAN`
+    pub knot_spans: bool,
+    /// Gives every triangle its own vertices with the normal of its plane
+    pub flat_shading: bool,
+}
+
+impl<T: FloatingPoint> NurbsSurfaceMeshBuilder<T> {
+    pub fn new(surface: ControlNet<T, Const<4>>) -> Self {
+        Self {
+            surface,
+            norm_tolerance: None,
+            grid: None,
+            origin: MeshOrigin::default(),
+            seam_tolerance: None,
+            creases: false,
+            knot_spans: false,
+            flat_shading: false,
+        }
+    }
+
+    /// Sets the normal tolerance for the adaptive tessellation
+    pub fn norm_tolerance(mut self, norm_tolerance: T) -> Self {
+        self.norm_tolerance = Some(norm_tolerance);
+        self
+    }
+
+    /// Sets the grid tessellation replacing the adaptive tessellation
+    pub fn grid(mut self, grid: GridTessellationOptions) -> Self {
+        self.grid = Some(grid);
+        self
+    }
+
+    /// Sets the origin subtracted from the positions
+    pub fn origin(mut self, origin: MeshOrigin<T>) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    /// Closes the seams and poles of the surface, detected with the tolerance on its control points
+    pub fn seam_tolerance(mut self, seam_tolerance: T) -> Self {
+        self.seam_tolerance = Some(seam_tolerance);
+        self
+    }
+
+    /// Sets whether the mesh is split along the C0 knot lines of the surface
+    pub fn creases(mut self, creases: bool) -> Self {
+        self.creases = creases;
+        self
+    }
+
+    /// Sets whether the knot span of each vertex is written
+    pub fn knot_spans(mut self, knot_spans: bool) -> Self {
+        self.knot_spans = knot_spans;
+        self
+    }
+
+    /// Sets whether every triangle gets its own vertices with the normal of its plane
+    pub fn flat_shading(mut self, flat_shading: bool) -> Self {
+        self.flat_shading = flat_shading;
+        self
+    }
+
+    /// Builds a triangle list mesh from the surface
+    pub fn build(&self) -> Mesh {
+        self.build_with_transform().0
+    }
+
+    /// Builds a triangle list mesh from the surface with the transform placing it at its origin
+    pub fn build_with_transform(&self) -> (Mesh, Transform) {
+        let mesh = match &self.grid {
+            Some(grid) => NurbsSurfaceMesh::from_grid(GridTessellation::new(&self.surface, grid)),
+            None => {
+                let options =
+                    self.norm_tolerance
+                        .map(|norm_tolerance| AdaptiveTessellationOptions {
+                            norm_tolerance,
+                            ..Default::default()
+                        });
+                NurbsSurfaceMesh::from(self.surface.tessellate(options))
+            }
+        };
+        let mut mesh = mesh.with_origin(self.origin);
+        if let Some(tolerance) = self.seam_tolerance {
+            mesh = mesh.with_seams(&self.surface, tolerance);
+        }
+        if self.creases {
+            mesh = mesh.with_creases(&self.surface);
+        }
+        if self.knot_spans {
+            mesh = mesh.with_knot_spans(&self.surface);
+        }
+        mesh = mesh.with_flat_shading(self.flat_shading);
+        (
+            mesh.build_surface_triangle_list(None),
+            mesh.origin_transform(),
+        )
+    }
+}
+
+impl<T: FloatingPoint> From<NurbsCurveMeshBuilder<T>> for Mesh {
+    fn from(builder: NurbsCurveMeshBuilder<T>) -> Self {
+        builder.build()
+    }
+}
+
+impl<T: FloatingPoint> From<NurbsSurfaceMeshBuilder<T>> for Mesh {
+    fn from(builder: NurbsSurfaceMeshBuilder<T>) -> Self {
+        builder.build()
+    }
+}
+
+macro_rules! impl_meshable {
+    ($builder:ident, $geometry:ident, $($primitive:ident),*) => {
+        $(
+            impl<T: FloatingPoint> Meshable for $primitive<T> {
+                type Output = $builder<T>;
+
+                fn mesh(&self) -> Self::Output {
+                    $builder::new(self.$geometry())
+                }
+            }
+
+            impl<T: FloatingPoint> From<$primitive<T>> for Mesh {
+                fn from(primitive: $primitive<T>) -> Self {
+                    primitive.mesh().build()
+                }
+            }
+        )*
+    };
+}
+
+impl_meshable!(
+    NurbsCurveMeshBuilder,
+    curve,
+    NurbsCircle,
+    NurbsArc,
+    NurbsEllipse,
+    NurbsRectangle
+);
+impl_meshable!(
+    NurbsSurfaceMeshBuilder,
+    surface,
+    NurbsSphere,
+    NurbsCylinder,
+    NurbsCone,
+    NurbsTorus
+);
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+
+    #[test]
+    fn wraps_arcs_ending_before_their_start() {
+        let arc = NurbsArc::new(2., 1.5 * PI, 0.5 * PI).curve();
+        let (t0, t1) = arc.knots_domain();
+        let mid = arc.point_at((t0 + t1) * 0.5);
+        assert!((arc.point_at(t1) - Point3::new(0., 2., 0.)).norm() < 1e-9);
+        assert!((mid - Point3::new(2., 0., 0.)).norm() < 1e-9);
+
+        let full = NurbsArc::new(1., 0.5, 0.5).curve();
+        let (t0, t1) = full.knots_domain();
+        let quarter = full.point_at(t0 + (t1 - t0) * 0.25);
+        assert!((quarter - Point3::new(-(0.5f64.sin()), 0.5f64.cos(), 0.)).norm() < 1e-9);
+    }
+
+    #[test]
+    fn builds_torus_from_its_radii() {
+        let torus = NurbsTorus::<f64>::new(0.5, 2.).surface();
+        let ((u0, _), (v0, _)) = (torus.u_knots_domain(), torus.v_knots_domain());
+        let p = torus.point_at(u0, v0);
+        assert!(((p.coords.xz().norm() - 2.).abs() - 0.5).abs() < 1e-9);
+    }
+}
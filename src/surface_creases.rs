@@ -0,0 +1,498 @@
+use std::collections::HashMap;
+
+use curvo::prelude::{FloatingPoint, NurbsSurface};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, Vector2, Vector3, U1,
+};
+
+use crate::{
+    mesh_optimization::TriangleBuffers,
+    mesh_projection::vertex_normals,
+    prelude::{ControlNet, MeshProjection},
+    surface_seams::tessellation_normal,
+};
+
+/// Interior knots where a surface is only C0, with a multiplicity of the degree or more
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CreaseKnots<T: FloatingPoint = f32> {
+    pub u: Vec<T>,
+    pub v: Vec<T>,
+}
+
+impl<T: FloatingPoint> CreaseKnots<T> {
+    /// Detects the C0 knots of the surface in both directions
+    pub fn detect<D>(surface: &ControlNet<T, D>) -> Self
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        Self {
+            u: c0_knots(surface.u_knots(), surface.u_degree()),
+            v: c0_knots(surface.v_knots(), surface.v_degree()),
+        }
+    }
+
+    /// Returns true if the surface has no creases
+    pub fn is_empty(&self) -> bool {
+        self.u.is_empty() && self.v.is_empty()
+    }
+}
+
+/// Returns the distinct interior knots with a multiplicity of the degree or more
+fn c0_knots<T: FloatingPoint>(knots: &[T], degree: usize) -> Vec<T> {
+    let (first, last) = (knots[0], knots[knots.len() - 1]);
+    let mut creases = vec![];
+    let mut i = 0;
+    while i < knots.len() {
+        let multiplicity = knots[i..].iter().take_while(|k| **k == knots[i]).count();
+        if knots[i] != first && knots[i] != last && multiplicity >= degree {
+            creases.push(knots[i]);
+        }
+        i += multiplicity;
+    }
+    creases
+}
+
+/// Splits a knot vector at a knot of the degree multiplicity or more,
+/// returning the left knots, the number of left control points, the right knots and the first right control point
+fn split_knots<T: FloatingPoint>(
+    knots: &[T],
+    degree: usize,
+    knot: T,
+) -> (Vec<T>, usize, Vec<T>, usize) {
+    let start = knots.iter().position(|k| *k == knot).unwrap();
+    let multiplicity = knots[start..].iter().take_while(|k| **k == knot).count();
+    let clamp = vec![knot; degree + 1];
+    let left = [&knots[..start], &clamp].concat();
+    let right = [&clamp, &knots[start + multiplicity..]].concat();
+    (left, start, right, start + multiplicity - degree - 1)
+}
+
+/// Splits the surface exactly at a C0 knot into the pieces before and after it
+/// * `direction` - 0 for the u direction, 1 for the v direction
+fn try_split_at<T: FloatingPoint, D>(
+    surface: &ControlNet<T, D>,
+    direction: usize,
+    knot: T,
+) -> anyhow::Result<(ControlNet<T, D>, ControlNet<T, D>)>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let points = surface.control_points();
+    let (u_degree, v_degree) = (surface.u_degree(), surface.v_degree());
+    let (u_knots, v_knots) = (surface.u_knots().to_vec(), surface.v_knots().to_vec());
+    if direction == 0 {
+        let (left, count, right, start) = split_knots(&u_knots, u_degree, knot);
+        Ok((
+            ControlNet::try_new(
+                u_degree,
+                v_degree,
+                left,
+                v_knots.clone(),
+                points[..count].to_vec(),
+            )?,
+            ControlNet::try_new(u_degree, v_degree, right, v_knots, points[start..].to_vec())?,
+        ))
+    } else {
+        let (left, count, right, start) = split_knots(&v_knots, v_degree, knot);
+        Ok((
+            ControlNet::try_new(
+                u_degree,
+                v_degree,
+                u_knots.clone(),
+                left,
+                points.iter().map(|row| row[..count].to_vec()).collect(),
+            )?,
+            ControlNet::try_new(
+                u_degree,
+                v_degree,
+                u_knots,
+                right,
+                points.iter().map(|row| row[start..].to_vec()).collect(),
+            )?,
+        ))
+    }
+}
+
+/// Surface with its creases and the pieces between them, indexed by the u and v intervals
+pub(crate) struct SurfaceCreases<T: FloatingPoint, D>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    surface: NurbsSurface<T, D>,
+    knots: CreaseKnots<T>,
+    pieces: Vec<Vec<ControlNet<T, D>>>,
+}
+
+impl<T: FloatingPoint, D> SurfaceCreases<T, D>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    /// Detects the creases of the surface and splits it at all of them
+    ///
+    /// Returns None if the surface has no creases.
+    pub fn try_detect(surface: &ControlNet<T, D>) -> anyhow::Result<Option<Self>> {
+        let knots = CreaseKnots::detect(surface);
+        if knots.is_empty() {
+            return Ok(None);
+        }
+        let split_all = |surface: &ControlNet<T, D>, direction: usize, knots: &[T]| {
+            let mut pieces = vec![];
+            let rest = knots.iter().try_fold(surface.clone(), |rest, knot| {
+                let (left, right) = try_split_at(&rest, direction, *knot)?;
+                pieces.push(left);
+                anyhow::Ok(right)
+            })?;
+            pieces.push(rest);
+            anyhow::Ok(pieces)
+        };
+        let pieces = split_all(surface, 0, &knots.u)?
+            .iter()
+            .map(|strip| split_all(strip, 1, &knots.v))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        Ok(Some(Self {
+            surface: surface.surface().clone(),
+            knots,
+            pieces,
+        }))
+    }
+}
+
+/// Knot lines along u and v with the tolerance within which parameters lie on them
+pub(crate) struct KnotLines<'a, T: FloatingPoint> {
+    lines: [&'a [T]; 2],
+    tolerance: [T; 2],
+}
+
+impl<'a, T: FloatingPoint> KnotLines<'a, T> {
+    pub fn new<D>(surface: &NurbsSurface<T, D>, u: &'a [T], v: &'a [T]) -> Self
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+        let eps = T::from_f64(1e-8).unwrap();
+        Self {
+            lines: [u, v],
+            tolerance: [(u1 - u0) * eps, (v1 - v0) * eps],
+        }
+    }
+
+    /// Returns true if the parameter lies on a knot line of the axis
+    pub fn on_line(&self, axis: usize, t: T) -> bool {
+        self.lines[axis].iter().any(|k| {
+            let d = t - *k;
+            d * d <= self.tolerance[axis] * self.tolerance[axis]
+        })
+    }
+
+    /// Returns the index of the interval between the knot lines of the axis containing the parameter
+    pub fn interval(&self, axis: usize, t: T) -> usize {
+        self.lines[axis].iter().filter(|k| **k < t).count()
+    }
+}
+
+/// Cuts the triangles along the knot lines and duplicates the vertices on them per adjacent piece,
+/// returning the u and v intervals of the piece of every vertex
+pub(crate) fn split_knot_lines<T: FloatingPoint, D>(
+    buffers: &mut TriangleBuffers<T>,
+    surface: &NurbsSurface<T, D>,
+    lines: &KnotLines<T>,
+    projection: &MeshProjection<T>,
+    origin: &Vector3<T>,
+) -> Vec<(usize, usize)>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    (0..2).for_each(|axis| {
+        lines.lines[axis].iter().for_each(|knot| {
+            cut(
+                buffers,
+                surface,
+                projection,
+                origin,
+                axis,
+                *knot,
+                lines.tolerance[axis],
+            );
+        });
+    });
+
+    let mut pieces: Vec<_> = buffers
+        .uvs
+        .iter()
+        .map(|uv| (lines.interval(0, uv.x), lines.interval(1, uv.y)))
+        .collect();
+    let mut copies: HashMap<(usize, usize, usize), usize> = HashMap::new();
+    let mut assigned = vec![false; buffers.positions.len()];
+    let three = T::from_usize(3).unwrap();
+    let faces: Vec<_> = std::mem::take(&mut buffers.faces)
+        .into_iter()
+        .map(|face| {
+            let centroid = face
+                .iter()
+                .fold(Vector2::zeros(), |acc, i| acc + buffers.uvs[*i])
+                / three;
+            let piece = (lines.interval(0, centroid.x), lines.interval(1, centroid.y));
+            face.map(|i| {
+                let uv = buffers.uvs[i];
+                let (on_u, on_v) = (lines.on_line(0, uv.x), lines.on_line(1, uv.y));
+                if !on_u && !on_v {
+                    return i;
+                }
+                let key = (
+                    i,
+                    if on_u { piece.0 } else { usize::MAX },
+                    if on_v { piece.1 } else { usize::MAX },
+                );
+                *copies.entry(key).or_insert_with(|| {
+                    let index = if assigned[i] {
+                        buffers.copy_vertex(i)
+                    } else {
+                        assigned[i] = true;
+                        i
+                    };
+                    if index < pieces.len() {
+                        pieces[index] = piece;
+                    } else {
+                        pieces.push(piece);
+                    }
+                    index
+                })
+            })
+        })
+        .collect();
+    buffers.faces = faces;
+    pieces
+}
+
+/// Cuts the triangles along the creases and splits the vertices on them,
+/// so that each side gets the normal of its own piece of the surface
+/// * `fix_normals` - False if the normals are recomputed from the projected triangles instead
+pub(crate) fn split_creases<T: FloatingPoint, D>(
+    buffers: &mut TriangleBuffers<T>,
+    creases: &SurfaceCreases<T, D>,
+    projection: &MeshProjection<T>,
+    origin: &Vector3<T>,
+    fix_normals: bool,
+) where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let surface = &creases.surface;
+    let lines = KnotLines::new(surface, &creases.knots.u, &creases.knots.v);
+    let pieces = split_knot_lines(buffers, surface, &lines, projection, origin);
+    if !fix_normals {
+        buffers.normals = vertex_normals(&buffers.positions, &buffers.faces);
+        return;
+    }
+
+    pieces.iter().enumerate().for_each(|(i, piece)| {
+        let uv = buffers.uvs[i];
+        if !lines.on_line(0, uv.x) && !lines.on_line(1, uv.y) {
+            return;
+        }
+        let surface = &creases.pieces[piece.0][piece.1];
+        let ((u0, u1), (v0, v1)) = surface.knots_domain();
+        let n = projection.project(
+            tessellation_normal(
+                surface,
+                nalgebra::clamp(uv.x, u0, u1),
+                nalgebra::clamp(uv.y, v0, v1),
+            )
+            .as_slice(),
+        );
+        buffers.normals[i] = n
+            .try_normalize(T::default_epsilon())
+            .unwrap_or(buffers.normals[i]);
+    });
+}
+
+/// Cuts the triangles crossing the knot line, adding a vertex on the surface per crossed edge
+fn cut<T: FloatingPoint, D>(
+    buffers: &mut TriangleBuffers<T>,
+    surface: &NurbsSurface<T, D>,
+    projection: &MeshProjection<T>,
+    origin: &Vector3<T>,
+    axis: usize,
+    knot: T,
+    tolerance: T,
+) where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let side = |buffers: &TriangleBuffers<T>, i: usize| {
+        let d = buffers.uvs[i][axis] - knot;
+        if d * d <= tolerance * tolerance {
+            0
+        } else if d < T::zero() {
+            -1
+        } else {
+            1
+        }
+    };
+
+    let mut cuts: HashMap<(usize, usize), usize> = HashMap::new();
+    let faces = std::mem::take(&mut buffers.faces);
+    faces.into_iter().for_each(|face| {
+        let sides = face.map(|i| side(buffers, i));
+        if !(sides.contains(&-1) && sides.contains(&1)) {
+            buffers.faces.push(face);
+            return;
+        }
+
+        let mut polygons = [vec![], vec![]];
+        (0..3).for_each(|j| {
+            let (a, b) = (face[j], face[(j + 1) % 3]);
+            let (sa, sb) = (sides[j], sides[(j + 1) % 3]);
+            if sa <= 0 {
+                polygons[0].push(a);
+            }
+            if sa >= 0 {
+                polygons[1].push(a);
+            }
+            if sa * sb < 0 {
+                let c = *cuts.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                    cut_edge(buffers, surface, projection, origin, axis, knot, a, b)
+                });
+                polygons[0].push(c);
+                polygons[1].push(c);
+            }
+        });
+        polygons.iter().for_each(|polygon| {
+            (1..polygon.len().saturating_sub(1)).for_each(|i| {
+                buffers.faces.push([polygon[0], polygon[i], polygon[i + 1]]);
+            });
+        });
+    });
+}
+
+/// Adds the vertex where the edge crosses the knot line, evaluated on the surface
+#[allow(clippy::too_many_arguments)]
+fn cut_edge<T: FloatingPoint, D>(
+    buffers: &mut TriangleBuffers<T>,
+    surface: &NurbsSurface<T, D>,
+    projection: &MeshProjection<T>,
+    origin: &Vector3<T>,
+    axis: usize,
+    knot: T,
+    a: usize,
+    b: usize,
+) -> usize
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let (uva, uvb) = (buffers.uvs[a], buffers.uvs[b]);
+    let t = (knot - uva[axis]) / (uvb[axis] - uva[axis]);
+    let mut uv = uva + (uvb - uva) * t;
+    uv[axis] = knot;
+    let normal = projection
+        .project(tessellation_normal(surface, uv.x, uv.y).as_slice())
+        .try_normalize(T::default_epsilon())
+        .unwrap_or_else(|| buffers.normals[a] * (T::one() - t) + buffers.normals[b] * t);
+    let half = T::from_f64(0.5).unwrap();
+
+    buffers.push_vertex(
+        projection.project(surface.point_at(uv.x, uv.y).coords.as_slice()) - origin,
+        normal,
+        uv,
+        if t < half { a } else { b },
+    )
+}
+
+/// Gives every triangle its own vertices with the normal of its plane
+pub(crate) fn flatten<T: FloatingPoint>(buffers: &mut TriangleBuffers<T>) {
+    let corners: Vec<_> = buffers.faces.iter().flatten().copied().collect();
+    buffers.select(&corners);
+    buffers.faces = (0..corners.len() / 3)
+        .map(|i| [i * 3, i * 3 + 1, i * 3 + 2])
+        .collect();
+    buffers.faces.iter().for_each(|face| {
+        let [a, b, c] = face.map(|i| buffers.positions[i]);
+        let n = (b - a).cross(&(c - a));
+        let smooth = face
+            .iter()
+            .fold(Vector3::zeros(), |acc, i| acc + buffers.normals[*i]);
+        let n = if n.dot(&smooth) < T::zero() { -n } else { n };
+        let n = n.try_normalize(T::default_epsilon()).unwrap_or(n);
+        face.iter().for_each(|i| buffers.normals[*i] = n);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::{Mesh, VertexAttributeValues};
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::{Const, Point4, Vector3};
+
+    use super::*;
+    use crate::prelude::NurbsSurfaceMesh;
+
+    /// Extrusion of a folded quadratic profile whose middle knot makes it only C0
+    fn folded() -> ControlNet<f64, Const<4>> {
+        let profile = NurbsCurve3D::try_new(
+            2,
+            vec![
+                Point4::new(0., 0., 0., 1.),
+                Point4::new(0.5, 0., 0., 1.),
+                Point4::new(1., 0., 0., 1.),
+                Point4::new(1., 0.5, 0., 1.),
+                Point4::new(1., 1., 0., 1.),
+            ],
+            vec![0., 0., 0., 0.5, 0.5, 1., 1., 1.],
+        )
+        .unwrap();
+        ControlNet::extrude(&profile, Vector3::z())
+    }
+
+    #[test]
+    fn detects_c0_knots() {
+        let creases = CreaseKnots::detect(&folded());
+        assert_eq!(creases.u.len() + creases.v.len(), 1);
+        assert_eq!(creases.u.first().or(creases.v.first()), Some(&0.5));
+    }
+
+    #[test]
+    fn splits_normals_along_creases() {
+        let surface = folded();
+        let mesh = NurbsSurfaceMesh::from(surface.tessellate(None))
+            .with_creases(&surface)
+            .build_surface_triangle_list(None);
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+        else {
+            panic!("expected positions");
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) =
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+        else {
+            panic!("expected normals");
+        };
+        let indices: Vec<_> = mesh.indices().unwrap().iter().collect();
+
+        // both sides of the fold are planar, so every vertex normal matches the plane of its triangles
+        // and faces the same side as their counterclockwise winding
+        indices.chunks(3).for_each(|face| {
+            let [a, b, c] = [face[0], face[1], face[2]].map(|i| Vector3::from(positions[i]));
+            let plane = (b - a).cross(&(c - a)).normalize();
+            face.iter().for_each(|i| {
+                let n = Vector3::from(normals[*i]);
+                assert!(n.dot(&plane) > 1. - 1e-4, "{:?} on {:?}", n, plane);
+            });
+        });
+    }
+}
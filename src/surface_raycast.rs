@@ -0,0 +1,395 @@
+use std::marker::PhantomData;
+
+use bevy::{
+    app::{App, Plugin, PreUpdate},
+    ecs::{
+        component::Component,
+        entity::Entity,
+        query::{Changed, With},
+        schedule::{IntoSystemConfigs, SystemSet},
+        system::{Commands, Query, Res, ResMut, Resource},
+    },
+    math::Vec3,
+    render::camera::Camera,
+    transform::components::GlobalTransform,
+    utils::HashSet,
+    window::{PrimaryWindow, Window},
+};
+use curvo::prelude::{
+    AdaptiveTessellationOptions, FloatingPoint, NurbsSurface3D, SurfaceTessellation,
+};
+use nalgebra::{Const, Matrix3, Point3, Vector2, Vector3};
+
+use crate::{
+    geometry::to_vec3,
+    prelude::{NurbsBvh, NurbsSurfaceGeometry},
+};
+
+/// Intersection of a ray with a NURBS surface
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceRayHit<T: FloatingPoint> {
+    /// The intersection point on the surface
+    pub point: Point3<T>,
+    /// The unit normal of the surface at the intersection
+    pub normal: Vector3<T>,
+    /// The (u, v) parameter of the surface at the intersection
+    pub uv: Vector2<T>,
+    /// The distance from the ray origin to the intersection in units of the ray direction
+    pub distance: T,
+}
+
+/// Options for the ray-surface intersection
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceRaycastOptions<T: FloatingPoint> {
+    /// The distance between the surface and the ray below which the intersection is accepted,
+    /// relative to the magnitude of the coordinates around the intersection.
+    /// It is never tighter than the floating point precision at that magnitude.
+    pub tolerance: T,
+    /// The maximum number of newton iterations
+    pub max_iterations: usize,
+}
+
+impl<T: FloatingPoint> Default for SurfaceRaycastOptions<T> {
+    fn default() -> Self {
+        Self {
+            tolerance: T::from_f64(1e-6).unwrap(),
+            max_iterations: 16,
+        }
+    }
+}
+
+/// Intersects a ray with a NURBS surface
+/// * `tessellation` - The tessellation of the surface to find the initial guess
+/// * `origin` - The origin of the ray
+/// * `direction` - The direction of the ray
+///
+/// The closest hit on the tessellation is refined onto the exact surface by newton iteration.
+/// Returns None if the ray misses the tessellation or the refinement does not converge within the tolerance.
+pub fn intersect_ray_surface<T: FloatingPoint>(
+    surface: &NurbsSurface3D<T>,
+    tessellation: &SurfaceTessellation<T, Const<4>>,
+    origin: &Point3<T>,
+    direction: &Vector3<T>,
+    options: &SurfaceRaycastOptions<T>,
+) -> Option<SurfaceRayHit<T>> {
+    let points = tessellation.points();
+    let uvs = tessellation.uvs();
+    let (distance, face, b1, b2) = tessellation
+        .faces()
+        .iter()
+        .filter_map(|f| {
+            intersect_ray_triangle(
+                origin,
+                direction,
+                &points[f[0]],
+                &points[f[1]],
+                &points[f[2]],
+            )
+            .map(|(t, b1, b2)| (t, f, b1, b2))
+        })
+        .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal))?;
+
+    let b0 = T::one() - b1 - b2;
+    let uv = uvs[face[0]] * b0 + uvs[face[1]] * b1 + uvs[face[2]] * b2;
+
+    // the residual cannot get below the rounding error of the coordinates, so the tolerance
+    // scales with their magnitude and the precision of T
+    let guess = origin.coords + direction * distance;
+    let scale = origin.coords.amax().max(guess.amax()).max(T::one());
+    let precision = T::default_epsilon() * T::from_f64(64.).unwrap() * scale;
+    let tolerance = (options.tolerance * scale).max(precision);
+
+    let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+    let (mut u, mut v, mut t) = (uv.x, uv.y, distance);
+    // keep the best iterate in case the iteration stalls on rounding noise
+    let mut best = (T::max_value().unwrap(), u, v, t);
+    for _ in 0..options.max_iterations {
+        let derivs = surface.rational_derivatives(u, v, 1);
+        let f = derivs[0][0] - (origin.coords + direction * t);
+        let norm = f.norm();
+        if norm < best.0 {
+            best = (norm, u, v, t);
+        }
+        if norm < precision {
+            break;
+        }
+        let jacobian = Matrix3::from_columns(&[derivs[1][0], derivs[0][1], -direction]);
+        let delta = jacobian.lu().solve(&(-f))?;
+        u = nalgebra::clamp(u + delta.x, u0, u1);
+        v = nalgebra::clamp(v + delta.y, v0, v1);
+        t += delta.z;
+    }
+    let residual = (surface.point_at(u, v).coords - (origin.coords + direction * t)).norm();
+    if residual < best.0 {
+        best = (residual, u, v, t);
+    }
+    let (residual, u, v, t) = best;
+    if residual >= tolerance || t < T::zero() {
+        return None;
+    }
+
+    let point = surface.point_at(u, v);
+    let normal = surface.normal_at(u, v).normalize();
+    Some(SurfaceRayHit {
+        point,
+        normal,
+        uv: Vector2::new(u, v),
+        distance: t,
+    })
+}
+
+/// Möller-Trumbore ray-triangle intersection returning the distance and barycentric coordinates
+pub(crate) fn intersect_ray_triangle<T: FloatingPoint>(
+    origin: &Point3<T>,
+    direction: &Vector3<T>,
+    a: &Point3<T>,
+    b: &Point3<T>,
+    c: &Point3<T>,
+) -> Option<(T, T, T)> {
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = direction.cross(&e2);
+    let det = e1.dot(&p);
+    let eps = T::default_epsilon();
+    if det > -eps && det < eps {
+        return None;
+    }
+    let inv = T::one() / det;
+    let s = origin - a;
+    let b1 = s.dot(&p) * inv;
+    if b1 < T::zero() || b1 > T::one() {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let b2 = direction.dot(&q) * inv;
+    if b2 < T::zero() || b1 + b2 > T::one() {
+        return None;
+    }
+    let t = e2.dot(&q) * inv;
+    (t >= T::zero()).then_some((t, b1, b2))
+}
+
+/// Coarse tessellation of a surface geometry used as the initial guess of raycasts
+#[derive(Component)]
+pub struct SurfaceRaycastCache<T: FloatingPoint = f32>(pub SurfaceTessellation<T, Const<4>>);
+
+/// Hit of the cursor on a surface geometry
+#[derive(Debug, Clone, Copy)]
+pub struct SurfaceCursorHit<T: FloatingPoint = f32> {
+    /// The entity holding the surface
+    pub entity: Entity,
+    /// The intersection point in world space
+    pub position: Vec3,
+    /// The unit normal at the intersection in world space
+    pub normal: Vec3,
+    /// The (u, v) parameter of the surface at the intersection
+    pub uv: Vector2<T>,
+}
+
+/// The surface geometry under the cursor
+#[derive(Resource, Debug, Clone)]
+pub struct CursorSurfaceHit<T: FloatingPoint = f32>(pub Option<SurfaceCursorHit<T>>);
+
+impl<T: FloatingPoint> Default for CursorSurfaceHit<T> {
+    fn default() -> Self {
+        Self(None)
+    }
+}
+
+/// Settings for the surface raycast under the cursor
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct SurfaceRaycastSettings<T: FloatingPoint = f32> {
+    /// The normal tolerance of the coarse tessellation for the initial guess
+    pub norm_tolerance: T,
+    /// The options for the newton refinement
+    pub options: SurfaceRaycastOptions<T>,
+}
+
+impl<T: FloatingPoint> Default for SurfaceRaycastSettings<T> {
+    fn default() -> Self {
+        Self {
+            norm_tolerance: T::from_f64(1e-1).unwrap(),
+            options: Default::default(),
+        }
+    }
+}
+
+/// System set in which surface geometries are raycast from the cursor
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SurfaceRaycastSet;
+
+/// Plugin exposing the exact hit of the cursor on surface geometries
+pub struct SurfaceRaycastPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for SurfaceRaycastPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for SurfaceRaycastPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SurfaceRaycastSettings<T>>()
+            .init_resource::<CursorSurfaceHit<T>>()
+            .add_systems(
+                PreUpdate,
+                (update_surface_raycast_caches::<T>, raycast_surfaces::<T>)
+                    .chain()
+                    .in_set(SurfaceRaycastSet),
+            );
+    }
+}
+
+/// Rebuilds the coarse tessellations of changed surface geometries
+pub fn update_surface_raycast_caches<T: FloatingPoint>(
+    mut commands: Commands,
+    settings: Res<SurfaceRaycastSettings<T>>,
+    surfaces: Query<(Entity, &NurbsSurfaceGeometry<T>), Changed<NurbsSurfaceGeometry<T>>>,
+) {
+    surfaces.iter().for_each(|(e, surface)| {
+        let tessellation = surface.tessellate(Some(AdaptiveTessellationOptions {
+            norm_tolerance: settings.norm_tolerance,
+            ..Default::default()
+        }));
+        commands.entity(e).insert(SurfaceRaycastCache(tessellation));
+    });
+}
+
+/// Intersects the cursor ray with surface geometries and stores the closest hit
+///
+/// If `NurbsBoundsPlugin` maintains the `NurbsBvh`, only the surfaces whose bounds the ray hits are intersected.
+pub fn raycast_surfaces<T: FloatingPoint>(
+    settings: Res<SurfaceRaycastSettings<T>>,
+    bvh: Option<Res<NurbsBvh>>,
+    mut hit: ResMut<CursorSurfaceHit<T>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    surfaces: Query<(
+        Entity,
+        &NurbsSurfaceGeometry<T>,
+        &SurfaceRaycastCache<T>,
+        &GlobalTransform,
+    )>,
+) {
+    let cursor = windows.get_single().ok().and_then(|w| w.cursor_position());
+    let camera = cameras.iter().find(|(c, _)| c.is_active);
+    let ray = cursor
+        .zip(camera)
+        .and_then(|(cursor, (camera, tr))| camera.viewport_to_world(tr, cursor));
+
+    hit.0 = ray.and_then(|ray| {
+        let candidates: Option<HashSet<_>> =
+            bvh.map(|bvh| bvh.ray_candidates(ray).into_iter().collect());
+        surfaces
+            .iter()
+            .filter(|(e, ..)| candidates.as_ref().is_none_or(|c| c.contains(e)))
+            .filter_map(|(e, surface, cache, transform)| {
+                let inverse = transform.affine().inverse();
+                let origin = inverse.transform_point3(ray.origin);
+                let direction = inverse.transform_vector3(*ray.direction);
+                let hit = intersect_ray_surface(
+                    surface,
+                    &cache.0,
+                    &Point3::new(from_f32(origin.x), from_f32(origin.y), from_f32(origin.z)),
+                    &Vector3::new(
+                        from_f32(direction.x),
+                        from_f32(direction.y),
+                        from_f32(direction.z),
+                    ),
+                    &settings.options,
+                )?;
+                let position = transform.transform_point(to_vec3(hit.point.coords.as_slice()));
+                let normal = inverse
+                    .matrix3
+                    .transpose()
+                    .mul_vec3a(to_vec3(hit.normal.as_slice()).into())
+                    .normalize_or_zero()
+                    .into();
+                Some((
+                    position.distance_squared(ray.origin),
+                    SurfaceCursorHit {
+                        entity: e,
+                        position,
+                        normal,
+                        uv: hit.uv,
+                    },
+                ))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, hit)| hit)
+    });
+}
+
+fn from_f32<T: FloatingPoint>(v: f32) -> T {
+    T::from_f32(v).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use curvo::prelude::{AdaptiveTessellationOptions, NurbsCurve3D, NurbsSurface};
+    use nalgebra::{Point3, Vector3};
+
+    use super::*;
+
+    /// Wave profile along x extruded along z
+    fn surface() -> NurbsSurface3D<f64> {
+        let points =
+            [(0., 0.), (1., 0.5), (2., -0.5), (3., 0.)].map(|(x, y)| Point3::new(x, y, 0.));
+        let profile = NurbsCurve3D::try_interpolate(&points, 3, None, None).unwrap();
+        NurbsSurface::extrude(&profile, Vector3::z() * 2.)
+    }
+
+    #[test]
+    fn hits_the_exact_surface() {
+        let surface = surface();
+        let tessellation = surface.tessellate(Some(AdaptiveTessellationOptions {
+            norm_tolerance: 0.5,
+            ..Default::default()
+        }));
+        let options = SurfaceRaycastOptions::default();
+        let origin = Point3::new(1.3, 5., 0.7);
+        let hit = intersect_ray_surface(&surface, &tessellation, &origin, &-Vector3::y(), &options)
+            .unwrap();
+
+        let exact = surface.point_at(hit.uv.x, hit.uv.y);
+        assert!((exact - hit.point).norm() < 1e-6);
+        assert!((hit.point.x - 1.3).abs() < 1e-6 && (hit.point.z - 0.7).abs() < 1e-6);
+        assert!((hit.distance - (5. - hit.point.y)).abs() < 1e-6);
+        assert!((hit.normal.norm() - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn hits_far_from_the_origin_in_f32() {
+        let offset = Vector3::new(100f32, 100., 100.);
+        let points = [(0., 0.), (1., 0.5), (2., -0.5), (3., 0.)]
+            .map(|(x, y)| Point3::new(x, y, 0.) + offset);
+        let profile = NurbsCurve3D::try_interpolate(&points, 3, None, None).unwrap();
+        let surface = NurbsSurface::extrude(&profile, Vector3::z() * 2.);
+        let tessellation = surface.tessellate(Some(AdaptiveTessellationOptions {
+            norm_tolerance: 0.5,
+            ..Default::default()
+        }));
+        let options = SurfaceRaycastOptions::default();
+        let origin = Point3::new(1.3, 5., 0.7) + offset;
+        let hit = intersect_ray_surface(&surface, &tessellation, &origin, &-Vector3::y(), &options)
+            .unwrap();
+        assert!((hit.point.x - origin.x).abs() < 1e-3 && (hit.point.z - origin.z).abs() < 1e-3);
+    }
+
+    #[test]
+    fn misses_outside_and_behind() {
+        let surface = surface();
+        let tessellation = surface.tessellate(None);
+        let options = SurfaceRaycastOptions::default();
+        let outside = Point3::new(4., 5., 0.7);
+        let behind = Point3::new(1.3, -5., 0.7);
+        assert!(
+            intersect_ray_surface(&surface, &tessellation, &outside, &-Vector3::y(), &options)
+                .is_none()
+        );
+        assert!(
+            intersect_ray_surface(&surface, &tessellation, &behind, &-Vector3::y(), &options)
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use curvo::prelude::{FloatingPoint, NurbsSurface};
+use nalgebra::{
+    allocator::Allocator, DefaultAllocator, DimName, DimNameDiff, DimNameSub, Vector2, Vector3, U1,
+};
+
+use crate::prelude::{ControlNet, MeshProjection};
+
+/// Closed directions and degenerate edges of a surface
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SurfaceTopology {
+    /// The first and last control rows along u coincide
+    pub closed_u: bool,
+    /// The first and last control columns along v coincide
+    pub closed_v: bool,
+    /// The edges collapsing to a point, in the order of u min, u max, v min and v max
+    pub degenerate_edges: [bool; 4],
+}
+
+impl SurfaceTopology {
+    /// Detects the closed directions and degenerate edges from the control points
+    /// * `tolerance` - The distance within which control points are considered coincident
+    pub fn detect<T: FloatingPoint, D>(surface: &ControlNet<T, D>, tolerance: T) -> Self
+    where
+        D: DimName + DimNameSub<U1>,
+        DefaultAllocator: Allocator<T, D>,
+        DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+    {
+        let points: Vec<Vec<Vec<T>>> = surface
+            .control_points()
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|p| {
+                        let (w, coords) = p.coords.as_slice().split_last().unwrap();
+                        coords.iter().map(|c| *c / *w).collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        let coincide = |a: &[T], b: &[T]| {
+            a.iter()
+                .zip(b.iter())
+                .map(|(a, b)| (*a - *b) * (*a - *b))
+                .fold(T::zero(), |acc, d| acc + d)
+                <= tolerance * tolerance
+        };
+        let collapsed = |edge: Vec<&Vec<T>>| edge.iter().all(|p| coincide(edge[0], p));
+
+        let (first, last) = (&points[0], &points[points.len() - 1]);
+        let column = |j: usize| points.iter().map(move |row| &row[j]);
+        let columns = points[0].len();
+        Self {
+            closed_u: first.iter().zip(last.iter()).all(|(a, b)| coincide(a, b)),
+            closed_v: column(0)
+                .zip(column(columns - 1))
+                .all(|(a, b)| coincide(a, b)),
+            degenerate_edges: [
+                collapsed(first.iter().collect()),
+                collapsed(last.iter().collect()),
+                collapsed(column(0).collect()),
+                collapsed(column(columns - 1).collect()),
+            ],
+        }
+    }
+
+    /// Returns true if the surface has no seams or poles to handle
+    pub fn is_open(&self) -> bool {
+        !self.closed_u && !self.closed_v && self.degenerate_edges.iter().all(|e| !e)
+    }
+}
+
+/// Shares the positions of the vertices on the seams of closed directions and fixes the normals on seams and poles
+///
+/// Seam vertices keep their own uvs, while their positions are evaluated on the first side of the seam
+/// and their normals are averaged across it. Normals on degenerate edges are evaluated from the
+/// derivatives of the neighborhood, where the tangent along the edge vanishes.
+/// Normals are evaluated in the coordinates of the surface, so they are only fixed for projections preserving them.
+/// * `fix_normals` - False if the normals are recomputed from the projected triangles afterwards
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn close_seams<T: FloatingPoint, D>(
+    surface: &NurbsSurface<T, D>,
+    topology: &SurfaceTopology,
+    projection: &MeshProjection<T>,
+    origin: &Vector3<T>,
+    uvs: &[Vector2<T>],
+    positions: &mut [Vector3<T>],
+    normals: &mut [Vector3<T>],
+    fix_normals: bool,
+) where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+    let eps = T::from_f64(1e-8).unwrap();
+    let near = |a: T, b: T, width: T| {
+        let d = a - b;
+        d * d <= (eps * width) * (eps * width)
+    };
+    let [du, dv] = [u1 - u0, v1 - v0];
+    let normal_at = |u: T, v: T| tessellation_normal(surface, u, v);
+
+    uvs.iter().enumerate().for_each(|(i, uv)| {
+        let sides = [
+            near(uv.x, u0, du),
+            near(uv.x, u1, du),
+            near(uv.y, v0, dv),
+            near(uv.y, v1, dv),
+        ];
+        if let Some(edge) = (0..4).find(|e| sides[*e] && topology.degenerate_edges[*e]) {
+            if fix_normals {
+                normals[i] = pole_normal(surface, uv.x, uv.y, edge);
+            }
+            return;
+        }
+
+        let on_u_seam = topology.closed_u && (sides[0] || sides[1]);
+        let on_v_seam = topology.closed_v && (sides[2] || sides[3]);
+        if !on_u_seam && !on_v_seam {
+            return;
+        }
+        let us = if on_u_seam { vec![u0, u1] } else { vec![uv.x] };
+        let vs = if on_v_seam { vec![v0, v1] } else { vec![uv.y] };
+
+        positions[i] =
+            projection.project(surface.point_at(us[0], vs[0]).coords.as_slice()) - origin;
+        if fix_normals {
+            let n = us
+                .iter()
+                .flat_map(|u| vs.iter().map(|v| normal_at(*u, *v)))
+                .fold(Vector3::zeros(), |acc, n| {
+                    acc + n.try_normalize(T::default_epsilon()).unwrap_or(n)
+                });
+            normals[i] = n.try_normalize(T::default_epsilon()).unwrap_or(normals[i]);
+        }
+    });
+}
+
+/// Evaluates the normal on a degenerate edge by l'Hôpital's rule,
+/// replacing the vanishing tangent along the edge with its derivative across the edge
+fn pole_normal<T: FloatingPoint, D>(
+    surface: &NurbsSurface<T, D>,
+    u: T,
+    v: T,
+    edge: usize,
+) -> Vector3<T>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    let derivs = surface.rational_derivatives(u, v, 2);
+    let d = |k: usize, l: usize| to_vector3(derivs[k][l].as_slice());
+    let n = match edge {
+        0 => d(1, 0).cross(&d(1, 1)),
+        1 => -d(1, 0).cross(&d(1, 1)),
+        2 => d(1, 1).cross(&d(0, 1)),
+        _ => -d(1, 1).cross(&d(0, 1)),
+    };
+
+    // orient the normal as the surface normal slightly inside the domain
+    let ((u0, u1), (v0, v1)) = (surface.u_knots_domain(), surface.v_knots_domain());
+    let step = T::from_f64(1e-4).unwrap();
+    let (u, v) = match edge {
+        0 => (u0 + (u1 - u0) * step, v),
+        1 => (u1 - (u1 - u0) * step, v),
+        2 => (u, v0 + (v1 - v0) * step),
+        _ => (u, v1 - (v1 - v0) * step),
+    };
+    let inside = tessellation_normal(surface, u, v);
+    let n = if n.dot(&inside) < T::zero() { -n } else { n };
+    n.try_normalize(T::default_epsilon())
+        .or_else(|| inside.try_normalize(T::default_epsilon()))
+        .unwrap_or(n)
+}
+
+/// Evaluates the unnormalized normal oriented as the normals of curvo's tessellation,
+/// which cross the v derivative with the u derivative unlike `NurbsSurface::normal_at`
+pub(crate) fn tessellation_normal<T: FloatingPoint, D>(
+    surface: &NurbsSurface<T, D>,
+    u: T,
+    v: T,
+) -> Vector3<T>
+where
+    D: DimName + DimNameSub<U1>,
+    DefaultAllocator: Allocator<T, D>,
+    DefaultAllocator: Allocator<T, DimNameDiff<D, U1>>,
+{
+    -to_vector3(surface.normal_at(u, v).as_slice())
+}
+
+/// Takes the first three coordinates of a vector, filling missing ones with zero
+fn to_vector3<T: FloatingPoint>(coords: &[T]) -> Vector3<T> {
+    Vector3::from_fn(|i, _| coords.get(i).copied().unwrap_or(T::zero()))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        math::Vec3,
+        render::mesh::{Mesh, MeshVertexAttribute, VertexAttributeValues},
+    };
+
+    use super::*;
+    use crate::prelude::{NurbsSphere, NurbsSurfaceMesh};
+
+    fn vectors(mesh: &Mesh, attribute: MeshVertexAttribute) -> Vec<Vec3> {
+        match mesh.attribute(attribute) {
+            Some(VertexAttributeValues::Float32x3(values)) => {
+                values.iter().map(|v| Vec3::from_array(*v)).collect()
+            }
+            _ => panic!("expected a Float32x3 attribute"),
+        }
+    }
+
+    #[test]
+    fn detects_sphere_topology() {
+        let sphere = NurbsSphere::<f64>::new(1.).surface();
+        let topology = SurfaceTopology::detect(&sphere, 1e-6);
+        assert!(topology.closed_u != topology.closed_v);
+        assert_eq!(topology.degenerate_edges.iter().filter(|e| **e).count(), 2);
+        assert!(!topology.is_open());
+    }
+
+    #[test]
+    fn closes_seams_and_poles_of_sphere() {
+        let sphere = NurbsSphere::<f64>::new(1.).surface();
+        let mesh = NurbsSurfaceMesh::from(sphere.tessellate(None))
+            .with_seams(&sphere, 1e-6)
+            .build_surface_triangle_list(None);
+        let positions = vectors(&mesh, Mesh::ATTRIBUTE_POSITION);
+        let normals = vectors(&mesh, Mesh::ATTRIBUTE_NORMAL);
+
+        // the outward normals of a unit sphere are its positions, including the poles where the tangent vanishes
+        positions.iter().zip(normals.iter()).for_each(|(p, n)| {
+            assert!((*n - *p).length() < 1e-3, "{:?} at {:?}", n, p);
+        });
+
+        // vertices on the seam share their positions and normals bit for bit
+        positions.iter().enumerate().for_each(|(i, p)| {
+            positions
+                .iter()
+                .enumerate()
+                .filter(|(_, q)| (**q - *p).length() < 1e-5)
+                .for_each(|(j, q)| {
+                    if p.y.abs() < 1. - 1e-5 {
+                        assert_eq!(p, q);
+                        assert_eq!(normals[i], normals[j]);
+                    }
+                });
+        });
+    }
+}
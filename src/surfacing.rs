@@ -0,0 +1,487 @@
+use std::{
+    f64::consts::{FRAC_PI_2, TAU},
+    marker::PhantomData,
+};
+
+use bevy::{
+    app::{App, Plugin, Update},
+    ecs::{
+        entity::Entity,
+        event::{Event, EventReader},
+        system::{Commands, EntityCommands},
+        world::World,
+    },
+};
+use curvo::prelude::{dehomogenize, FloatingPoint, NurbsCurve3D};
+use nalgebra::{Const, Matrix3, Matrix4, Point3, Vector3};
+
+use crate::{
+    derivatives::curve_derivatives,
+    intersection::transform_curve,
+    prelude::{
+        homogenize, try_make_curves_compatible, ControlNet, NurbsGeometryPlugin,
+        NurbsSurfaceGeometry,
+    },
+};
+
+/// Axis of revolution
+#[derive(Debug, Clone, Copy)]
+pub struct RevolutionAxis<T: FloatingPoint = f32> {
+    /// A point on the axis
+    pub origin: Point3<T>,
+    /// The direction of the axis
+    pub direction: Vector3<T>,
+}
+
+impl<T: FloatingPoint> RevolutionAxis<T> {
+    pub fn new(origin: Point3<T>, direction: Vector3<T>) -> Self {
+        Self { origin, direction }
+    }
+}
+
+/// How the profile is oriented along the rail of a single rail sweep
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum SweepOrientation<T: FloatingPoint = f32> {
+    /// Uses the frenet frame, keeping the previous normal where the curvature vanishes
+    Frenet,
+    /// Uses the rotation minimizing frame
+    #[default]
+    RotationMinimizing,
+    /// Keeps the profile y axis towards the up vector
+    Up(Vector3<T>),
+}
+
+/// Options for sweeps
+#[derive(Debug, Clone, Copy)]
+pub struct SweepOptions<T: FloatingPoint = f32> {
+    /// The number of profile sections placed along the rails
+    pub sections: usize,
+    /// The degree of the loft through the sections
+    pub degree: usize,
+    /// The orientation of the profile along a single rail
+    pub orientation: SweepOrientation<T>,
+}
+
+impl<T: FloatingPoint> Default for SweepOptions<T> {
+    fn default() -> Self {
+        Self {
+            sections: 16,
+            degree: 3,
+            orientation: Default::default(),
+        }
+    }
+}
+
+/// Revolves the profile around the axis by the angle in radians
+///
+/// The surface is exact with rational quadratic arcs in the v direction, and the u direction follows the profile.
+pub fn try_revolve<T: FloatingPoint>(
+    profile: &NurbsCurve3D<T>,
+    axis: &RevolutionAxis<T>,
+    angle: T,
+) -> anyhow::Result<ControlNet<T, Const<4>>> {
+    let direction = axis
+        .direction
+        .try_normalize(T::default_epsilon())
+        .ok_or_else(|| anyhow::anyhow!("revolution axis has zero length"))?;
+    let theta = angle.to_f64().unwrap();
+    if theta <= 0. {
+        anyhow::bail!("revolution angle must be positive");
+    }
+    let (arcs, half) = arc_divisions(theta);
+    let to_t = |v: f64| T::from_f64(v).unwrap();
+
+    let control_points = profile
+        .control_points()
+        .iter()
+        .map(|p| {
+            let point =
+                dehomogenize(p).ok_or_else(|| anyhow::anyhow!("control point has zero weight"))?;
+            let center = axis.origin + direction * (point - axis.origin).dot(&direction);
+            // y has the length of x since the axis is orthogonal to x
+            let x = point - center;
+            let y = direction.cross(&x);
+            let row = (0..=2 * arcs)
+                .map(|i| {
+                    let a = half * i as f64;
+                    let scale = if i % 2 == 0 { 1. } else { 1. / half.cos() };
+                    let weight = if i % 2 == 0 {
+                        p.w
+                    } else {
+                        p.w * to_t(half.cos())
+                    };
+                    let q = center + x * to_t(a.cos() * scale) + y * to_t(a.sin() * scale);
+                    homogenize(&q, weight)
+                })
+                .collect();
+            Ok(row)
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    ControlNet::try_new(
+        profile.degree(),
+        2,
+        profile.knots().as_slice().to_vec(),
+        arc_knots(arcs),
+        control_points,
+    )
+}
+
+/// Returns the number of rational quadratic arcs spanning the angle and the half angle of each arc
+pub(crate) fn arc_divisions(theta: f64) -> (usize, f64) {
+    let theta = theta.min(TAU);
+    let arcs = (theta / FRAC_PI_2).ceil().clamp(1., 4.) as usize;
+    (arcs, theta / arcs as f64 * 0.5)
+}
+
+/// Returns the knot vector of consecutive rational quadratic arcs
+pub(crate) fn arc_knots<T: FloatingPoint>(arcs: usize) -> Vec<T> {
+    let mut knots = vec![T::zero(); 3];
+    (1..arcs).for_each(|i| {
+        let k = T::from_usize(i).unwrap() / T::from_usize(arcs).unwrap();
+        knots.extend([k, k]);
+    });
+    knots.extend([T::one(); 3]);
+    knots
+}
+
+/// Builds the ruled surface between two curves
+///
+/// The curves are made compatible, so the surface is linear in the v direction between them.
+pub fn try_ruled<T: FloatingPoint>(
+    a: &NurbsCurve3D<T>,
+    b: &NurbsCurve3D<T>,
+) -> anyhow::Result<ControlNet<T, Const<4>>> {
+    let curves = try_make_curves_compatible(&[a.clone(), b.clone()])?;
+    let control_points = curves[0]
+        .control_points()
+        .iter()
+        .zip(curves[1].control_points().iter())
+        .map(|(p, q)| vec![*p, *q])
+        .collect();
+    ControlNet::try_new(
+        curves[0].degree(),
+        1,
+        curves[0].knots().as_slice().to_vec(),
+        vec![T::zero(), T::zero(), T::one(), T::one()],
+        control_points,
+    )
+}
+
+/// Sweeps the profile along the rail
+///
+/// The profile is defined in its local space and placed at each section with its x, y and z axes
+/// mapped to the normal, binormal and tangent of the rail frame. The sections are lofted into the surface.
+pub fn try_sweep<T: FloatingPoint>(
+    profile: &NurbsCurve3D<T>,
+    rail: &NurbsCurve3D<T>,
+    options: &SweepOptions<T>,
+) -> anyhow::Result<ControlNet<T, Const<4>>> {
+    let sections = rail_frames(rail, options.sections.max(2), &options.orientation)
+        .into_iter()
+        .map(|(point, normal, tangent)| {
+            let binormal = tangent.cross(&normal);
+            let matrix = Matrix4::from_columns(&[
+                normal.push(T::zero()),
+                binormal.push(T::zero()),
+                tangent.push(T::zero()),
+                point.coords.push(T::one()),
+            ]);
+            transform_curve(profile, &matrix)
+                .ok_or_else(|| anyhow::anyhow!("failed to place the profile on the rail"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    ControlNet::try_loft(&sections, Some(options.degree))
+}
+
+/// Sweeps the profile along two rails
+///
+/// At each section the profile is rotated and uniformly scaled so that its start point lies on the first rail
+/// and its end point on the second rail, with its local z axis along the mean tangent of the rails.
+pub fn try_sweep2<T: FloatingPoint>(
+    profile: &NurbsCurve3D<T>,
+    rails: (&NurbsCurve3D<T>, &NurbsCurve3D<T>),
+    options: &SweepOptions<T>,
+) -> anyhow::Result<ControlNet<T, Const<4>>> {
+    let eps = T::default_epsilon();
+    let (t0, t1) = profile.knots_domain();
+    let (p0, p1) = (profile.point_at(t0), profile.point_at(t1));
+    let chord = p1 - p0;
+    let e1 = chord
+        .try_normalize(eps)
+        .ok_or_else(|| anyhow::anyhow!("profile end points coincide"))?;
+    let e3 = orthogonalize(&Vector3::z(), &e1);
+    let local = Matrix3::from_columns(&[e1, e3.cross(&e1), e3]);
+
+    let (a, b) = rails;
+    let ((a0, a1), (b0, b1)) = (a.knots_domain(), b.knots_domain());
+    let n = options.sections.max(2);
+    let sections = (0..n)
+        .map(|i| {
+            let s = T::from_usize(i).unwrap() / T::from_usize(n - 1).unwrap();
+            let (ta, tb) = (a0 + (a1 - a0) * s, b0 + (b1 - b0) * s);
+            let (pa, pb) = (a.point_at(ta), b.point_at(tb));
+            let span = pb - pa;
+            let f1 = span
+                .try_normalize(eps)
+                .ok_or_else(|| anyhow::anyhow!("rails touch at section {}", i))?;
+            let tangent = a.tangent_at(ta) + b.tangent_at(tb);
+            let f3 = orthogonalize(&tangent, &f1);
+            let world = Matrix3::from_columns(&[f1, f3.cross(&f1), f3]);
+
+            let scaled = world * local.transpose() * (span.norm() / chord.norm());
+            let mut matrix = Matrix4::identity();
+            matrix.fixed_view_mut::<3, 3>(0, 0).copy_from(&scaled);
+            matrix
+                .fixed_view_mut::<3, 1>(0, 3)
+                .copy_from(&(pa.coords - scaled * p0.coords));
+            transform_curve(profile, &matrix)
+                .ok_or_else(|| anyhow::anyhow!("failed to place the profile between the rails"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    ControlNet::try_loft(&sections, Some(options.degree))
+}
+
+/// Returns the points, unit normals and unit tangents of the frames at uniform parameters of the rail
+fn rail_frames<T: FloatingPoint>(
+    rail: &NurbsCurve3D<T>,
+    count: usize,
+    orientation: &SweepOrientation<T>,
+) -> Vec<(Point3<T>, Vector3<T>, Vector3<T>)> {
+    let eps = T::default_epsilon();
+    let (t0, t1) = rail.knots_domain();
+    let mut previous: Option<(Point3<T>, Vector3<T>, Vector3<T>)> = None;
+
+    (0..count)
+        .map(|i| {
+            let t = t0 + (t1 - t0) * T::from_usize(i).unwrap() / T::from_usize(count - 1).unwrap();
+            let derivs = curve_derivatives(rail, t, 2);
+            let point = Point3::from(derivs[0]);
+            let tangent = derivs[1]
+                .try_normalize(eps)
+                .or(previous.map(|p| p.2))
+                .unwrap_or_else(Vector3::z);
+
+            let candidate = match orientation {
+                SweepOrientation::Frenet => {
+                    (derivs[2] - tangent * tangent.dot(&derivs[2])).try_normalize(eps)
+                }
+                SweepOrientation::Up(up) => (up - tangent * tangent.dot(up)).try_normalize(eps),
+                SweepOrientation::RotationMinimizing => previous.map(|(x0, r0, t0)| {
+                    // double reflection method
+                    let v1 = point - x0;
+                    let c1 = v1.norm_squared();
+                    if c1 <= eps {
+                        return r0;
+                    }
+                    let two = T::one() + T::one();
+                    let rl = r0 - v1 * (two / c1 * v1.dot(&r0));
+                    let tl = t0 - v1 * (two / c1 * v1.dot(&t0));
+                    let v2 = tangent - tl;
+                    let c2 = v2.norm_squared();
+                    if c2 <= eps {
+                        rl
+                    } else {
+                        rl - v2 * (two / c2 * v2.dot(&rl))
+                    }
+                }),
+            };
+            let normal = candidate
+                .or(previous.map(|p| orthogonalize(&p.1, &tangent)))
+                .unwrap_or_else(|| orthogonalize(&Vector3::x(), &tangent));
+            let normal = orthogonalize(&normal, &tangent);
+
+            previous = Some((point, normal, tangent));
+            (point, normal, tangent)
+        })
+        .collect()
+}
+
+/// Returns the unit component of the vector orthogonal to the unit axis, or any unit vector orthogonal to the axis
+fn orthogonalize<T: FloatingPoint>(v: &Vector3<T>, axis: &Vector3<T>) -> Vector3<T> {
+    let eps = T::from_f64(1e-6).unwrap();
+    (v - axis * axis.dot(v))
+        .try_normalize(eps)
+        .or_else(|| {
+            let other = if axis.x * axis.x < T::from_f64(0.5).unwrap() {
+                Vector3::x()
+            } else {
+                Vector3::y()
+            };
+            (other - axis * axis.dot(&other)).try_normalize(eps)
+        })
+        .unwrap_or_else(Vector3::x)
+}
+
+/// Surfacing operation building a NURBS surface from curves
+#[derive(Debug, Clone)]
+pub enum SurfaceOperation<T: FloatingPoint = f32> {
+    /// Revolves the profile around the axis by the angle in radians
+    Revolve {
+        profile: NurbsCurve3D<T>,
+        axis: RevolutionAxis<T>,
+        angle: T,
+    },
+    /// Sweeps the profile along the rail
+    Sweep {
+        profile: NurbsCurve3D<T>,
+        rail: NurbsCurve3D<T>,
+        options: SweepOptions<T>,
+    },
+    /// Sweeps the profile along two rails
+    Sweep2 {
+        profile: NurbsCurve3D<T>,
+        rails: (NurbsCurve3D<T>, NurbsCurve3D<T>),
+        options: SweepOptions<T>,
+    },
+    /// Builds the ruled surface between two curves
+    Ruled {
+        curves: (NurbsCurve3D<T>, NurbsCurve3D<T>),
+    },
+}
+
+impl<T: FloatingPoint> SurfaceOperation<T> {
+    /// Builds the surface of the operation
+    pub fn try_build(&self) -> anyhow::Result<ControlNet<T, Const<4>>> {
+        match self {
+            SurfaceOperation::Revolve {
+                profile,
+                axis,
+                angle,
+            } => try_revolve(profile, axis, *angle),
+            SurfaceOperation::Sweep {
+                profile,
+                rail,
+                options,
+            } => try_sweep(profile, rail, options),
+            SurfaceOperation::Sweep2 {
+                profile,
+                rails,
+                options,
+            } => try_sweep2(profile, (&rails.0, &rails.1), options),
+            SurfaceOperation::Ruled { curves } => try_ruled(&curves.0, &curves.1),
+        }
+    }
+}
+
+/// Event requesting a surface entity built by the operation
+#[derive(Event, Debug, Clone)]
+pub struct SpawnNurbsSurface<T: FloatingPoint = f32>(pub SurfaceOperation<T>);
+
+/// Event sent when a surfacing operation fails. The entity reserved for the surface is despawned
+#[derive(Event, Debug, Clone)]
+pub struct NurbsSurfacingFailed {
+    /// The despawned entity
+    pub entity: Entity,
+    /// The description of the failure
+    pub error: String,
+}
+
+/// Extension of `Commands` to spawn surface entities from surfacing operations
+///
+/// The spawned entities hold `NurbsSurfaceGeometry`, which `NurbsGeometryPlugin` meshes with `NurbsSurfaceMesh`.
+/// The surface is built when the commands are applied, so the returned entity can be extended with materials or transforms.
+pub trait NurbsSurfacingCommandsExt<T: FloatingPoint> {
+    /// Spawns a surface entity built by the operation
+    fn spawn_surface(&mut self, operation: SurfaceOperation<T>) -> EntityCommands<'_>;
+
+    /// Spawns a surface entity revolving the profile around the axis by the angle in radians
+    fn spawn_revolve(
+        &mut self,
+        profile: NurbsCurve3D<T>,
+        axis: RevolutionAxis<T>,
+        angle: T,
+    ) -> EntityCommands<'_> {
+        self.spawn_surface(SurfaceOperation::Revolve {
+            profile,
+            axis,
+            angle,
+        })
+    }
+
+    /// Spawns a surface entity sweeping the profile along the rail
+    fn spawn_sweep(
+        &mut self,
+        profile: NurbsCurve3D<T>,
+        rail: NurbsCurve3D<T>,
+        options: SweepOptions<T>,
+    ) -> EntityCommands<'_> {
+        self.spawn_surface(SurfaceOperation::Sweep {
+            profile,
+            rail,
+            options,
+        })
+    }
+
+    /// Spawns a surface entity sweeping the profile along two rails
+    fn spawn_sweep2(
+        &mut self,
+        profile: NurbsCurve3D<T>,
+        rails: (NurbsCurve3D<T>, NurbsCurve3D<T>),
+        options: SweepOptions<T>,
+    ) -> EntityCommands<'_> {
+        self.spawn_surface(SurfaceOperation::Sweep2 {
+            profile,
+            rails,
+            options,
+        })
+    }
+
+    /// Spawns the ruled surface entity between two curves
+    fn spawn_ruled(&mut self, a: NurbsCurve3D<T>, b: NurbsCurve3D<T>) -> EntityCommands<'_> {
+        self.spawn_surface(SurfaceOperation::Ruled { curves: (a, b) })
+    }
+}
+
+impl<T: FloatingPoint> NurbsSurfacingCommandsExt<T> for Commands<'_, '_> {
+    fn spawn_surface(&mut self, operation: SurfaceOperation<T>) -> EntityCommands<'_> {
+        let mut entity = self.spawn_empty();
+        entity.add(
+            move |entity: Entity, world: &mut World| match operation.try_build() {
+                Ok(surface) => {
+                    if let Some(mut e) = world.get_entity_mut(entity) {
+                        e.insert(NurbsSurfaceGeometry(surface));
+                    }
+                }
+                Err(error) => {
+                    world.despawn(entity);
+                    world.send_event(NurbsSurfacingFailed {
+                        entity,
+                        error: error.to_string(),
+                    });
+                }
+            },
+        );
+        entity
+    }
+}
+
+/// Plugin to spawn surface entities requested by `SpawnNurbsSurface` events
+pub struct NurbsSurfacingPlugin<T: FloatingPoint = f32>(PhantomData<T>);
+
+impl<T: FloatingPoint> Default for NurbsSurfacingPlugin<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: FloatingPoint> Plugin for NurbsSurfacingPlugin<T> {
+    fn build(&self, app: &mut App) {
+        if !app.is_plugin_added::<NurbsGeometryPlugin<T>>() {
+            app.add_plugins(NurbsGeometryPlugin::<T>::default());
+        }
+        app.add_event::<SpawnNurbsSurface<T>>()
+            .add_event::<NurbsSurfacingFailed>()
+            .add_systems(Update, spawn_requested_surfaces::<T>);
+    }
+}
+
+/// Spawns the surface entities requested by events
+pub fn spawn_requested_surfaces<T: FloatingPoint>(
+    mut commands: Commands,
+    mut requests: EventReader<SpawnNurbsSurface<T>>,
+) {
+    requests.read().for_each(|request| {
+        commands.spawn_surface(request.0.clone());
+    });
+}
@@ -0,0 +1,300 @@
+use std::sync::Arc;
+
+use bevy::{
+    ecs::component::Component,
+    prelude::{Deref, DerefMut},
+    render::color::Color,
+};
+use curvo::prelude::FloatingPoint;
+use nalgebra::{Vector2, Vector3};
+
+/// Piecewise linear color gradient over `[0, 1]`, interpolated in linear RGB
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorGradient {
+    stops: Vec<(f32, Color)>,
+}
+
+impl Default for ColorGradient {
+    fn default() -> Self {
+        Self::viridis()
+    }
+}
+
+impl ColorGradient {
+    /// Creates a gradient from the colors at their positions in `[0, 1]`
+    ///
+    /// # Panics
+    /// Panics if there are no stops.
+    pub fn new(stops: impl Into<Vec<(f32, Color)>>) -> Self {
+        let mut stops = stops.into();
+        assert!(!stops.is_empty(), "a gradient needs at least one stop");
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { stops }
+    }
+
+    /// Perceptually uniform gradient from dark purple to yellow
+    pub fn viridis() -> Self {
+        Self::new([
+            (0., Color::rgb_u8(0x44, 0x01, 0x54)),
+            (0.25, Color::rgb_u8(0x3b, 0x52, 0x8b)),
+            (0.5, Color::rgb_u8(0x21, 0x91, 0x8c)),
+            (0.75, Color::rgb_u8(0x5e, 0xc9, 0x62)),
+            (1., Color::rgb_u8(0xfd, 0xe7, 0x25)),
+        ])
+    }
+
+    /// Diverging gradient from blue through light gray to red
+    pub fn blue_to_red() -> Self {
+        Self::new([
+            (0., Color::rgb_u8(0x3b, 0x4c, 0xc0)),
+            (0.5, Color::rgb_u8(0xdd, 0xdd, 0xdd)),
+            (1., Color::rgb_u8(0xb4, 0x04, 0x26)),
+        ])
+    }
+
+    pub fn stops(&self) -> &[(f32, Color)] {
+        &self.stops
+    }
+
+    /// Samples the gradient, clamping the position to the outer stops
+    pub fn sample(&self, t: f32) -> Color {
+        let t = if t.is_nan() { 0. } else { t };
+        let upper = self.stops.iter().position(|(p, _)| t < *p);
+        match upper {
+            Some(0) => self.stops[0].1,
+            None => self.stops[self.stops.len() - 1].1,
+            Some(i) => {
+                let ((p0, c0), (p1, c1)) = (self.stops[i - 1], self.stops[i]);
+                let s = (t - p0) / (p1 - p0);
+                let (a, b) = (c0.as_linear_rgba_f32(), c1.as_linear_rgba_f32());
+                Color::rgba_linear_from_array([0, 1, 2, 3].map(|k| a[k] + (b[k] - a[k]) * s))
+            }
+        }
+    }
+}
+
+/// Mapping of scalar values onto a gradient
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScalarColorMap {
+    pub gradient: ColorGradient,
+    /// The values mapped to the ends of the gradient. If None, the range of the evaluated values is used
+    pub range: Option<(f32, f32)>,
+}
+
+impl ScalarColorMap {
+    /// Resolves the legend of the values, ignoring non-finite ones for the automatic range
+    pub fn legend(&self, values: &[f32]) -> ScalarFieldLegend {
+        let (min, max) = self.range.unwrap_or_else(|| {
+            values
+                .iter()
+                .filter(|v| v.is_finite())
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+                    (min.min(*v), max.max(*v))
+                })
+        });
+        ScalarFieldLegend {
+            min: if min.is_finite() { min } else { 0. },
+            max: if max.is_finite() { max } else { 0. },
+            gradient: self.gradient.clone(),
+        }
+    }
+}
+
+/// Legend of the scalar values and colors of a surface mesh for UI
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScalarFieldLegend {
+    pub min: f32,
+    pub max: f32,
+    pub gradient: ColorGradient,
+}
+
+impl ScalarFieldLegend {
+    /// Returns the color of the value
+    pub fn color(&self, value: f32) -> Color {
+        let width = self.max - self.min;
+        let t = if width > 0. {
+            (value - self.min) / width
+        } else {
+            0.
+        };
+        self.gradient.sample(t)
+    }
+
+    /// Returns the evenly spaced values from the minimum to the maximum with their colors
+    pub fn ticks(&self, count: usize) -> Vec<(f32, Color)> {
+        let segments = count.saturating_sub(1).max(1) as f32;
+        (0..count)
+            .map(|i| {
+                let value = self.min + (self.max - self.min) * i as f32 / segments;
+                (value, self.color(value))
+            })
+            .collect()
+    }
+}
+
+type FieldFn<T, V> = Arc<dyn Fn(Vector2<T>, Vector3<T>, Vector3<T>) -> V + Send + Sync>;
+
+/// Vertex colors evaluated from the uv, position and normal of each vertex
+#[derive(Clone)]
+pub enum VertexColorField<T: FloatingPoint = f32> {
+    /// Scalar values mapped onto a gradient
+    Scalar {
+        field: FieldFn<T, f32>,
+        map: ScalarColorMap,
+    },
+    /// Colors given directly
+    Color(FieldFn<T, Color>),
+}
+
+impl<T: FloatingPoint> VertexColorField<T> {
+    /// Creates a field of scalar values mapped onto a gradient
+    pub fn scalar(
+        field: impl Fn(Vector2<T>, Vector3<T>, Vector3<T>) -> f32 + Send + Sync + 'static,
+        map: ScalarColorMap,
+    ) -> Self {
+        Self::Scalar {
+            field: Arc::new(field),
+            map,
+        }
+    }
+
+    /// Creates a field of colors
+    pub fn color(
+        field: impl Fn(Vector2<T>, Vector3<T>, Vector3<T>) -> Color + Send + Sync + 'static,
+    ) -> Self {
+        Self::Color(Arc::new(field))
+    }
+
+    /// Evaluates the legend of a scalar field over the vertices, or None for a color field
+    pub(crate) fn legend(
+        &self,
+        uvs: &[Vector2<T>],
+        positions: &[Vector3<T>],
+        normals: &[Vector3<T>],
+    ) -> Option<ScalarFieldLegend> {
+        match self {
+            VertexColorField::Scalar { field, map } => {
+                let values: Vec<_> = scalars(field, uvs, positions, normals).collect();
+                Some(map.legend(&values))
+            }
+            VertexColorField::Color(_) => None,
+        }
+    }
+
+    /// Evaluates the linear RGBA colors of the vertices with the legend of a scalar field
+    /// * `legend` - The legend mapping scalar values to colors. If None, it is resolved over the vertices
+    pub(crate) fn colors(
+        &self,
+        uvs: &[Vector2<T>],
+        positions: &[Vector3<T>],
+        normals: &[Vector3<T>],
+        legend: Option<&ScalarFieldLegend>,
+    ) -> (Vec<[f32; 4]>, Option<ScalarFieldLegend>) {
+        match self {
+            VertexColorField::Scalar { field, map } => {
+                let values: Vec<_> = scalars(field, uvs, positions, normals).collect();
+                let legend = legend.cloned().unwrap_or_else(|| map.legend(&values));
+                let colors = values
+                    .iter()
+                    .map(|v| legend.color(*v).as_linear_rgba_f32())
+                    .collect();
+                (colors, Some(legend))
+            }
+            VertexColorField::Color(field) => (
+                uvs.iter()
+                    .zip(positions.iter().zip(normals.iter()))
+                    .map(|(uv, (p, n))| field(*uv, *p, *n).as_linear_rgba_f32())
+                    .collect(),
+                None,
+            ),
+        }
+    }
+}
+
+/// Component holding the vertex colors written to the mesh of a `NurbsSurfaceGeometry`
+#[derive(Component, Clone, Deref, DerefMut)]
+pub struct NurbsVertexColors<T: FloatingPoint = f32>(pub VertexColorField<T>);
+
+/// Component holding the legend of the scalar `NurbsVertexColors` of a surface, updated whenever its mesh is rewritten
+#[derive(Component, Debug, Clone, Default, PartialEq, Deref, DerefMut)]
+pub struct NurbsScalarLegend(pub ScalarFieldLegend);
+
+fn scalars<'a, T: FloatingPoint>(
+    field: &'a FieldFn<T, f32>,
+    uvs: &'a [Vector2<T>],
+    positions: &'a [Vector3<T>],
+    normals: &'a [Vector3<T>],
+) -> impl Iterator<Item = f32> + 'a {
+    uvs.iter()
+        .zip(positions.iter().zip(normals.iter()))
+        .map(|(uv, (p, n))| field(*uv, *p, *n))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::{Mesh, PrimitiveTopology, VertexAttributeValues};
+    use curvo::prelude::NurbsCurve3D;
+    use nalgebra::{Const, Point4, Vector3};
+
+    use super::*;
+    use crate::prelude::{ControlNet, MeshOrigin, NurbsSurfaceMesh};
+
+    /// Plane from x = 0 to x = 2 along z
+    fn plane() -> NurbsSurfaceMesh<f64, Const<4>> {
+        let profile = NurbsCurve3D::try_new(
+            1,
+            vec![Point4::new(0., 0., 0., 1.), Point4::new(2., 0., 0., 1.)],
+            vec![0., 0., 1., 1.],
+        )
+        .unwrap();
+        let surface = ControlNet::extrude(&profile, Vector3::z());
+        NurbsSurfaceMesh::from(surface.tessellate(None))
+    }
+
+    fn legend_of(mesh: &NurbsSurfaceMesh<f64, Const<4>>) -> ScalarFieldLegend {
+        let mut written = Mesh::new(PrimitiveTopology::TriangleList, Default::default());
+        mesh.try_write_surface_triangle_list(&mut written, &Default::default())
+            .unwrap()
+            .legend
+            .unwrap()
+    }
+
+    #[test]
+    fn resolves_legend_over_positions_before_origin() {
+        let mesh = plane()
+            .with_origin(MeshOrigin::BoundingBoxCenter)
+            .with_vertex_colors(VertexColorField::scalar(
+                |_, p, _| p.x as f32,
+                ScalarColorMap::default(),
+            ));
+        let legend = legend_of(&mesh);
+        assert!((legend.min - 0.).abs() < 1e-6);
+        assert!((legend.max - 2.).abs() < 1e-6);
+
+        let fixed = plane().with_vertex_colors(VertexColorField::scalar(
+            |_, p, _| p.x as f32,
+            ScalarColorMap {
+                range: Some((-1., 1.)),
+                ..Default::default()
+            },
+        ));
+        let legend = legend_of(&fixed);
+        assert_eq!((legend.min, legend.max), (-1., 1.));
+        assert_eq!(legend.color(5.), legend.gradient.sample(1.));
+    }
+
+    #[test]
+    fn removes_colors_when_rewritten_without_them() {
+        let mut mesh = plane()
+            .with_vertex_colors(VertexColorField::color(|_, _, _| Color::RED))
+            .build_surface_triangle_list(None);
+        let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR)
+        else {
+            panic!("expected colors");
+        };
+        assert!(colors.iter().all(|c| *c == Color::RED.as_linear_rgba_f32()));
+
+        plane().write_surface_triangle_list(&mut mesh);
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_COLOR).is_none());
+    }
+}